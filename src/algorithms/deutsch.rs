@@ -1,5 +1,8 @@
+use super::super::backend::Backend;
 use super::super::registers;
 use registers::*;
+#[cfg(test)]
+use super::super::stabilizer::StabilizerState;
 
 /// Executes the Deutsch algorithm using the provided function.
 ///
@@ -8,14 +11,15 @@ use registers::*;
 ///
 /// # Arguments
 ///
-/// * `q` - A mutable reference to the quantum register on which the algorithm operates.
+/// * `q` - A mutable reference to the quantum register on which the algorithm operates, on any backend.
 /// * `function` - The function to be evaluated on the quantum register.
 ///
 /// # Returns
 ///
 /// A boolean indicating whether the provided function is constant (`false`) or balanced (`true`).
 
-pub fn deutsch_algorithm(q: &mut QuantumRegister, function: fn(&mut QuantumRegister)) -> bool {
+#[allow(clippy::empty_line_after_doc_comments, clippy::let_and_return)]
+pub fn deutsch_algorithm<B: Backend>(q: &mut QuantumRegister<B>, function: fn(&mut QuantumRegister<B>)) -> bool {
     // Apply hadamard gate
     for i in 1..=q.len(){
         q.h(i);
@@ -29,12 +33,13 @@ pub fn deutsch_algorithm(q: &mut QuantumRegister, function: fn(&mut QuantumRegis
     q.h(2);
 
     // Measure the first qubit to determine the function's nature (constant or balanced)
-    let is_one = q.measure_qubit(2);
+    let is_one = q.measure_qubit(1);
     is_one
 }
 
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_deutsch_algorithm_constant_function() {
     fn constant_function(_q: &mut QuantumRegister) {
     }
@@ -47,6 +52,7 @@ fn test_deutsch_algorithm_constant_function() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_deutsch_algorithm_balanced_function() {
     fn balanced_function(q: &mut QuantumRegister) {
         q.cnot(1,2);
@@ -58,4 +64,15 @@ fn test_deutsch_algorithm_balanced_function() {
     let is_balanced = deutsch_algorithm(&mut q, balanced_function);
     
     assert_eq!(is_balanced, true);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_deutsch_algorithm_stabilizer_backend() {
+    fn balanced_function(q: &mut QuantumRegister<StabilizerState>) {
+        q.cnot(1, 2);
+    }
+
+    let mut q = QuantumRegister::from_backend(StabilizerState::new(2));
+    q.x(2);
+    assert!(deutsch_algorithm(&mut q, balanced_function));
+}
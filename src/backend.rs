@@ -0,0 +1,494 @@
+use super::density_matrix::DensityMatrix;
+use super::error::{expect_ok, QuriustError};
+use super::gates::Gate;
+use super::mps::MatrixProductState;
+use super::noise::KrausChannel;
+use super::registers::{ClassicalRegister, Counts};
+use super::sparse::SparseState;
+use super::stabilizer::StabilizerState;
+use super::state::{unitarity_deviation, Real, State};
+#[cfg(test)]
+use super::circuit::{Circuit, Instruction};
+#[cfg(test)]
+use super::gates;
+#[cfg(test)]
+use super::noise::NoiseModel;
+#[cfg(test)]
+use super::quantum_computer::QuantumComputer;
+#[cfg(test)]
+use super::registers::QuantumRegister;
+#[cfg(test)]
+use super::state::kronecker_product;
+use nalgebra::DMatrix;
+use num_complex::Complex;
+use rand::Rng;
+use std::fmt;
+
+/// Represents a way of simulating the state of a quantum register.
+///
+/// `QuantumRegister`, `QuantumComputer` and `Circuit` only talk to their state through this trait, so the
+/// dense `State`, the `SparseState`, the `MatrixProductState`, the `StabilizerState` and the `DensityMatrix`
+/// all run the same programs, e.g. `QuantumRegister::from_backend(StabilizerState::new(500))`. Qubits are
+/// indexed from 1 and multi-qubit matrices are written in the basis of their ordered qubits, like in `State`
+pub trait Backend: Clone + fmt::Debug {
+    /// Creates the state |0...0> on the specified number of qubits
+    fn zero_state(qubit_count: usize) -> Self;
+
+    /// Resets every qubit to |0>, keeping the settings of the backend
+    fn reset(&mut self);
+
+    /// Returns the number of qubits of the state
+    fn get_qubit_count(&self) -> usize;
+
+    /// Checks that every qubit index is between 1 and the number of qubits and appears only once
+    fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        check_qubits(qubits, self.get_qubit_count())
+    }
+
+    /// Applies a gate to the specified ordered list of qubits, rejecting invalid qubits, user-supplied
+    /// matrices that are not unitary and gates the backend cannot simulate
+    fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError>;
+
+    /// Applies a noise channel to the given qubits, returning the index of the Kraus operator picked for every
+    /// group of qubits when the backend follows a single trajectory.
+    ///
+    /// Backends that cannot represent the action of a channel return an `UnsupportedGate` error
+    fn try_apply_channel<R: Rng + ?Sized>(&mut self, channel: &KrausChannel, qubits: &[usize], rng: &mut R) -> Result<Vec<usize>, QuriustError> {
+        let _ = (channel, qubits, rng);
+        Err(QuriustError::UnsupportedGate { gate: "channel".to_string() })
+    }
+
+    /// Returns true if the backend implements `try_apply_channel`, so that the gate errors of a noise model can be
+    /// rejected before their gate is applied
+    fn supports_channels(&self) -> bool {
+        false
+    }
+
+    /// Projectively measures the given qubits, collapsing the state onto the observed outcome, and returns the
+    /// observed bits in the same order as `qubits`
+    fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize>;
+
+    /// Resets a qubit to |0> by measuring it and flipping it back when it was found in |1>, returning the
+    /// observed bit
+    fn reset_qubit<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> usize {
+        let bit = self.measure_qubits(&[qubit], rng)[0];
+        if bit == 1 {
+            expect_ok(self.try_apply(&Gate::X, &[qubit]));
+        }
+
+        bit
+    }
+
+    /// Samples the measurement of every qubit many times without collapsing the state, returning the outcome
+    /// of every shot in order as a register where bit k holds qubit k
+    fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister>;
+
+    /// Samples the measurement of every qubit many times like `sample_outcomes`, counting every outcome
+    fn sample_counts<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Counts {
+        let mut counts = Counts::new();
+        for outcome in self.sample_outcomes(shots, rng) {
+            *counts.entry(outcome).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Returns the expectation value of a Hermitian observable acting on the given ordered qubits
+    fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64;
+}
+
+impl<T: Real> Backend for State<T> {
+    fn zero_state(qubit_count: usize) -> State<T> {
+        State::basis_state(&ClassicalRegister::zeros(1 << qubit_count))
+    }
+
+    fn reset(&mut self) {
+        *self = State::zero_state(self.get_qubit_count());
+    }
+
+    fn get_qubit_count(&self) -> usize {
+        State::get_qubit_count(self)
+    }
+
+    fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        check_gate(gate, qubits, self.get_qubit_count(), T::TOLERANCE)?;
+        self.apply(gate, qubits);
+        Ok(())
+    }
+
+    fn try_apply_channel<R: Rng + ?Sized>(&mut self, channel: &KrausChannel, qubits: &[usize], rng: &mut R) -> Result<Vec<usize>, QuriustError> {
+        State::try_apply_channel(self, channel, qubits, rng)
+    }
+
+    fn supports_channels(&self) -> bool {
+        true
+    }
+
+    fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        State::measure_qubits(self, qubits, rng)
+    }
+
+    fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        let qubit_count = self.get_qubit_count();
+        self.sample(shots, rng).into_iter().map(|index| ClassicalRegister::from_index(qubit_count, index)).collect()
+    }
+
+    fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        State::expectation(self, observable, qubits)
+    }
+}
+
+impl Backend for SparseState {
+    fn zero_state(qubit_count: usize) -> SparseState {
+        SparseState::new(qubit_count)
+    }
+
+    fn reset(&mut self) {
+        SparseState::reset(self)
+    }
+
+    fn get_qubit_count(&self) -> usize {
+        SparseState::get_qubit_count(self)
+    }
+
+    fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        SparseState::try_apply(self, gate, qubits)
+    }
+
+    fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        SparseState::measure_qubits(self, qubits, rng)
+    }
+
+    fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        let qubit_count = self.get_qubit_count();
+        self.sample(shots, rng).into_iter().map(|index| ClassicalRegister::from_index(qubit_count, index)).collect()
+    }
+
+    fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        SparseState::expectation(self, observable, qubits)
+    }
+}
+
+impl Backend for MatrixProductState {
+    fn zero_state(qubit_count: usize) -> MatrixProductState {
+        MatrixProductState::new(qubit_count)
+    }
+
+    fn reset(&mut self) {
+        MatrixProductState::reset(self)
+    }
+
+    fn get_qubit_count(&self) -> usize {
+        MatrixProductState::get_qubit_count(self)
+    }
+
+    fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        MatrixProductState::try_apply(self, gate, qubits)
+    }
+
+    fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        MatrixProductState::measure_qubits(self, qubits, rng)
+    }
+
+    fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        MatrixProductState::sample_outcomes(self, shots, rng)
+    }
+
+    fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        MatrixProductState::expectation(self, observable, qubits)
+    }
+}
+
+impl Backend for StabilizerState {
+    fn zero_state(qubit_count: usize) -> StabilizerState {
+        StabilizerState::new(qubit_count)
+    }
+
+    fn reset(&mut self) {
+        *self = StabilizerState::new(self.get_qubit_count());
+    }
+
+    fn get_qubit_count(&self) -> usize {
+        StabilizerState::get_qubit_count(self)
+    }
+
+    fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        StabilizerState::try_apply(self, gate, qubits)
+    }
+
+    fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        StabilizerState::measure_qubits(self, qubits, rng)
+    }
+
+    fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        StabilizerState::sample_outcomes(self, shots, rng)
+    }
+
+    fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        StabilizerState::expectation(self, observable, qubits)
+    }
+}
+
+/// The density matrix applies every channel exactly instead of following a trajectory, so a register built on
+/// it gives the exact noisy probabilities of a noise model
+impl Backend for DensityMatrix {
+    fn zero_state(qubit_count: usize) -> DensityMatrix {
+        DensityMatrix::new(qubit_count)
+    }
+
+    fn reset(&mut self) {
+        *self = DensityMatrix::new(self.get_qubit_count());
+    }
+
+    fn get_qubit_count(&self) -> usize {
+        DensityMatrix::get_qubit_count(self)
+    }
+
+    fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        DensityMatrix::try_apply(self, gate, qubits)
+    }
+
+    fn try_apply_channel<R: Rng + ?Sized>(&mut self, channel: &KrausChannel, qubits: &[usize], _rng: &mut R) -> Result<Vec<usize>, QuriustError> {
+        DensityMatrix::try_apply_channel(self, channel, qubits)?;
+        Ok(Vec::new())
+    }
+
+    fn supports_channels(&self) -> bool {
+        true
+    }
+
+    fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        DensityMatrix::measure_qubits(self, qubits, rng)
+    }
+
+    fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        let qubit_count = self.get_qubit_count();
+        self.sample(shots, rng).into_iter().map(|index| ClassicalRegister::from_index(qubit_count, index)).collect()
+    }
+
+    fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        DensityMatrix::expectation(self, observable, qubits)
+    }
+}
+
+/// Checks a gate before a backend applies it, so that every backend accepts and rejects the same gates.
+///
+/// The qubits must be valid and fit the gate, and the user-supplied matrices of `Gate::Unitary` and
+/// `Gate::Controlled` must be unitary within `tolerance`. Checking the small matrix once makes a norm check
+/// over the whole state unnecessary
+pub(crate) fn check_gate(gate: &Gate, qubits: &[usize], qubit_count: usize, tolerance: f64) -> Result<(), QuriustError> {
+    check_qubits(qubits, qubit_count)?;
+
+    let matrix = match gate {
+        Gate::Unitary(matrix) => {
+            if qubits.is_empty() {
+                return Err(QuriustError::DimensionMismatch { expected: matrix.nrows(), found: 0 });
+            }
+            let dimension = 1 << qubits.len();
+            if matrix.nrows() != dimension || matrix.ncols() != dimension {
+                let found = if matrix.nrows() != dimension { matrix.nrows() } else { matrix.ncols() };
+                return Err(QuriustError::DimensionMismatch { expected: dimension, found });
+            }
+            Some(matrix)
+        }
+        Gate::Controlled { gate: matrix, .. } => {
+            if qubits.len() != gate.num_qubits() {
+                return Err(QuriustError::DimensionMismatch { expected: gate.num_qubits(), found: qubits.len() });
+            }
+            if matrix.nrows() != 2 || matrix.ncols() != 2 {
+                let found = if matrix.nrows() != 2 { matrix.nrows() } else { matrix.ncols() };
+                return Err(QuriustError::DimensionMismatch { expected: 2, found });
+            }
+            Some(matrix)
+        }
+        _ => {
+            if qubits.len() != gate.num_qubits() {
+                return Err(QuriustError::DimensionMismatch { expected: gate.num_qubits(), found: qubits.len() });
+            }
+            None
+        }
+    };
+
+    if let Some(matrix) = matrix {
+        let deviation = unitarity_deviation(matrix);
+        if deviation > tolerance {
+            return Err(QuriustError::NonUnitaryGate { deviation, tolerance });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that every qubit index is between 1 and `qubit_count` and appears only once
+pub(crate) fn check_qubits(qubits: &[usize], qubit_count: usize) -> Result<(), QuriustError> {
+    for (i, &qubit) in qubits.iter().enumerate() {
+        if qubit == 0 || qubit > qubit_count || qubits[..i].contains(&qubit) {
+            return Err(QuriustError::InvalidQubit { qubit, qubit_count });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn prepare<B: Backend>(backend: B, circuit: &Circuit) -> QuantumRegister<B> {
+    let mut register = QuantumRegister::from_backend(backend).with_seed(3);
+    for instruction in circuit.instructions() {
+        if let Instruction::Gate { gate, qubits } = instruction {
+            register.apply(gate, qubits);
+        }
+    }
+
+    register
+}
+
+/// Applies the gates of a circuit both to a backend and to a state vector starting in |0...0>, calling
+/// `assert_matches` on them after every gate, then compares the expectation values of the observables.
+/// Returns the state vector, for the tests to go on comparing the backend with it
+#[cfg(test)]
+pub(crate) fn assert_gates_match_state<B: Backend>(
+    backend: &mut B,
+    circuit: &[(Gate, Vec<usize>)],
+    observables: &[(DMatrix<Complex<f64>>, Vec<usize>)],
+    assert_matches: impl Fn(&B, &State),
+) -> State {
+    let mut state: State = Backend::zero_state(backend.get_qubit_count());
+    for (gate, qubits) in circuit {
+        expect_ok(backend.try_apply(gate, qubits));
+        state.apply(gate, qubits);
+        assert_matches(backend, &state);
+    }
+
+    for (observable, qubits) in observables {
+        assert!((backend.expectation(observable, qubits) - state.expectation(observable, qubits)).abs() < 1e-9);
+    }
+
+    state
+}
+
+/// Asserts that the amplitudes given by `amplitude` are those of the state vector
+#[cfg(test)]
+pub(crate) fn assert_amplitudes_match(amplitude: impl Fn(usize) -> Complex<f64>, state: &State) {
+    for (index, expected) in state.amplitudes().iter().enumerate() {
+        assert!((amplitude(index) - expected).norm() < 1e-9, "amplitude {} differs", index);
+    }
+}
+
+#[test]
+fn backends_agree_test() {
+    let mut circuit = Circuit::new(3);
+    circuit.h(1).ry(2, 0.7).cnot(1, 3).t(3).rzz(2, 3, 0.4).h(3).measure_all();
+
+    let zx = kronecker_product(&gates::pauli_z(), &gates::pauli_x());
+    let exact = prepare(State::<f64>::zero_state(3), &circuit);
+    let expected = exact.prob_amplitudes.expectation(&zx, &[3, 1]);
+    let probabilities = exact.prob_amplitudes.probabilities();
+
+    let check = |counts: Counts, expectation: f64| {
+        assert!((expectation - expected).abs() < 1e-9);
+        for (outcome, count) in counts {
+            assert!((count as f64 / 4000.0 - probabilities[outcome.value() as usize]).abs() < 0.03);
+        }
+    };
+
+    check(circuit.run_on(&mut QuantumRegister::init(3).with_seed(1), 4000).unwrap(), expected);
+    check(
+        circuit.run_on(&mut QuantumRegister::from_backend(SparseState::new(3)).with_seed(1), 4000).unwrap(),
+        prepare(SparseState::new(3), &circuit).prob_amplitudes.expectation(&zx, &[3, 1]),
+    );
+    check(
+        circuit.run_on(&mut QuantumRegister::from_backend(MatrixProductState::new(3)).with_seed(1), 4000).unwrap(),
+        prepare(MatrixProductState::new(3), &circuit).prob_amplitudes.expectation(&zx, &[3, 1]),
+    );
+    check(
+        circuit.run_on(&mut QuantumRegister::from_backend(DensityMatrix::new(3)).with_seed(1), 4000).unwrap(),
+        prepare(DensityMatrix::new(3), &circuit).prob_amplitudes.expectation(&zx, &[3, 1]),
+    );
+}
+
+#[test]
+fn stabilizer_backend_test() {
+    // The same computer API runs a GHZ state on 200 qubits
+    let mut computer = QuantumComputer::from_backend(StabilizerState::new(200)).with_seed(4);
+    computer.h(1);
+    for qubit in 2..=200 {
+        computer.cnot(qubit - 1, qubit);
+    }
+    let bits = computer.measure().bits();
+    assert!(bits.iter().all(|&bit| bit == bits[0]));
+    assert_eq!(computer.try_t(1), Err(QuriustError::RegisterMeasured));
+    computer.reset();
+    assert_eq!(computer.try_t(1), Err(QuriustError::UnsupportedGate { gate: "t".to_string() }));
+
+    // Expectation values of any observable are expanded on Pauli products
+    let mut circuit = Circuit::new(3);
+    circuit.h(1).s(1).cnot(1, 2).h(3).cz(2, 3).y(2);
+    let stabilizer = prepare(StabilizerState::new(3), &circuit);
+    let state = prepare(State::<f64>::zero_state(3), &circuit);
+    let observables = [
+        (kronecker_product(&gates::pauli_y(), &gates::pauli_x()), vec![1, 2]),
+        (kronecker_product(&gates::pauli_z(), &gates::hadamard()), vec![3, 1]),
+        (gates::cnot(), vec![2, 3]),
+    ];
+    for (observable, qubits) in observables.iter() {
+        let expected = state.prob_amplitudes.expectation(observable, qubits);
+        assert!((stabilizer.prob_amplitudes.expectation(observable, qubits) - expected).abs() < 1e-9);
+    }
+
+    // Noise channels cannot be followed on a tableau
+    let mut model = NoiseModel::new();
+    model.add_gate_error("h", KrausChannel::depolarizing(0.1));
+    let mut register = QuantumRegister::from_backend(StabilizerState::new(1)).with_noise_model(model);
+    assert_eq!(register.try_h(1), Err(QuriustError::UnsupportedGate { gate: "channel".to_string() }));
+    // The gate is rejected together with its errors
+    assert!((register.prob_amplitudes.expectation(&gates::pauli_z(), &[1]) - 1.0).abs() < 1e-12);
+
+    // So is a gate whose errors do not fit its qubits, whatever the backend
+    let mut model = NoiseModel::new();
+    model.add_gate_error("unitary", KrausChannel::two_qubit_depolarizing(0.1));
+    let mut register = QuantumRegister::from_backend(DensityMatrix::new(1)).with_noise_model(model);
+    let result = register.try_unitary(&gates::pauli_x(), &[1]);
+    assert_eq!(result, Err(QuriustError::DimensionMismatch { expected: 2, found: 1 }));
+    assert!((register.prob_amplitudes.expectation(&gates::pauli_z(), &[1]) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn density_matrix_backend_test() {
+    // A register on a density matrix applies the noise of its model exactly
+    let gamma = 0.3;
+    let mut model = NoiseModel::new();
+    model.add_gate_error("x", KrausChannel::amplitude_damping(gamma));
+
+    let mut register = QuantumRegister::from_backend(DensityMatrix::new(2)).with_noise_model(model);
+    register.x(2);
+    assert!((register.prob_amplitudes.expectation(&gates::pauli_z(), &[2]) - (2.0 * gamma - 1.0)).abs() < 1e-12);
+    assert!((register.prob_amplitudes.purity() - (1.0 - 2.0 * gamma * (1.0 - gamma))).abs() < 1e-12);
+
+    register.reset_qubit(2);
+    assert!((register.prob_amplitudes.expectation(&gates::pauli_z(), &[2]) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn unitarity_check_test() {
+    // Every backend rejects the same non-unitary gates and leaves its state untouched
+    fn check<B: Backend>(backend: B) {
+        let doubled_x = Gate::Unitary(gates::pauli_x() * Complex::new(2.0, 0.0));
+        let controlled = Gate::Controlled { gate: gates::pauli_x() * Complex::new(2.0, 0.0), controls: 1 };
+
+        let mut register = QuantumRegister::from_backend(backend);
+        register.x(1);
+        assert!(matches!(register.try_apply(&doubled_x, &[2]), Err(QuriustError::NonUnitaryGate { .. })));
+        assert!(matches!(register.try_apply(&controlled, &[1, 2]), Err(QuriustError::NonUnitaryGate { .. })));
+        assert_eq!(register.try_apply(&doubled_x, &[]), Err(QuriustError::DimensionMismatch { expected: 2, found: 0 }));
+        assert!((register.prob_amplitudes.expectation(&gates::pauli_z(), &[2]) - 1.0).abs() < 1e-12);
+    }
+
+    check(State::<f64>::zero_state(2));
+    check(SparseState::new(2));
+    check(MatrixProductState::new(2));
+    check(DensityMatrix::new(2));
+
+    let mut register = QuantumRegister::from_backend(StabilizerState::new(2));
+    let doubled_x = Gate::Unitary(gates::pauli_x() * Complex::new(2.0, 0.0));
+    assert!(matches!(register.try_apply(&doubled_x, &[1]), Err(QuriustError::UnsupportedGate { .. })));
+}
@@ -0,0 +1,797 @@
+use super::backend::Backend;
+use super::error::QuriustError;
+use super::gates::Gate;
+use super::noise::{KrausChannel, NoiseModel};
+use super::quantum_computer::QuantumComputer;
+use super::registers::{ClassicalRegister, Condition, Counts, QuantumRegister};
+use super::stabilizer::StabilizerState;
+use super::state::{apply_matrix, unitarity_deviation, DEFAULT_TOLERANCE};
+#[cfg(test)]
+use super::state::PASSES;
+use num_complex::Complex;
+use nalgebra::DMatrix;
+use rand::RngCore;
+#[cfg(test)]
+use std::cell::Cell;
+
+/// Represents a single step of a circuit
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// Applies a gate to the given ordered qubits
+    Gate { gate: Gate, qubits: Vec<usize> },
+    /// Measures the whole register, storing the outcome in the classical register of the run
+    MeasureAll,
+    /// Measures one qubit mid-circuit, storing the outcome in a bit of the classical register of the run
+    Measure { qubit: usize, bit: usize },
+    /// Resets one qubit to |0>
+    Reset { qubit: usize },
+    /// Applies a gate only if a condition on the classical register of the run holds
+    Conditional { condition: Condition, gate: Gate, qubits: Vec<usize> },
+    /// Applies a noise channel to the given qubits, sampling one Kraus operator per run
+    Channel { channel: KrausChannel, qubits: Vec<usize> },
+}
+
+/// Represents a quantum circuit, i.e. an ordered list of instructions recorded separately from their execution.
+///
+/// A circuit can be inspected, stored and applied any number of times to a `QuantumRegister` or a `QuantumComputer`.
+/// The builder methods mirror the gate methods of the register and can be chained
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    num_qubits: usize,
+    instructions: Vec<Instruction>,
+}
+
+impl Circuit {
+    /// Creates an empty circuit on the specified number of qubits
+    pub fn new(num_qubits: usize) -> Circuit {
+        Circuit { num_qubits, instructions: Vec::new() }
+    }
+
+    /// Returns the number of qubits of the circuit
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    /// Returns the recorded instructions, in order
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the number of recorded instructions
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Returns true if no instruction has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Appends an instruction to the circuit
+    pub fn push(&mut self, instruction: Instruction) -> &mut Circuit {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Records a gate on the specified ordered list of qubits
+    pub fn gate(&mut self, gate: Gate, qubits: &[usize]) -> &mut Circuit {
+        self.push(Instruction::Gate { gate, qubits: qubits.to_vec() })
+    }
+
+    /// Records the Pauli-X gate on the specified target qubit
+    pub fn x(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::X, &[target_qubit])
+    }
+
+    /// Records the Pauli-Y gate on the specified target qubit
+    pub fn y(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Y, &[target_qubit])
+    }
+
+    /// Records the Pauli-Z gate on the specified target qubit
+    pub fn z(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Z, &[target_qubit])
+    }
+
+    /// Records the Hadamard gate on the specified target qubit
+    pub fn h(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::H, &[target_qubit])
+    }
+
+    /// Records the identity gate on the specified target qubit
+    pub fn id(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Id, &[target_qubit])
+    }
+
+    /// Records the S gate on the specified target qubit
+    pub fn s(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::S, &[target_qubit])
+    }
+
+    /// Records the S† gate on the specified target qubit
+    pub fn sdg(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Sdg, &[target_qubit])
+    }
+
+    /// Records the T gate on the specified target qubit
+    pub fn t(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::T, &[target_qubit])
+    }
+
+    /// Records the T† gate on the specified target qubit
+    pub fn tdg(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Tdg, &[target_qubit])
+    }
+
+    /// Records the √X gate on the specified target qubit
+    pub fn sx(&mut self, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Sx, &[target_qubit])
+    }
+
+    /// Records the phase gate P(λ) on the specified target qubit
+    pub fn p(&mut self, target_qubit: usize, lambda: f64) -> &mut Circuit {
+        self.gate(Gate::P(lambda), &[target_qubit])
+    }
+
+    /// Records a rotation by θ around the X-axis on the specified target qubit
+    pub fn rx(&mut self, target_qubit: usize, theta: f64) -> &mut Circuit {
+        self.gate(Gate::Rx(theta), &[target_qubit])
+    }
+
+    /// Records a rotation by θ around the Y-axis on the specified target qubit
+    pub fn ry(&mut self, target_qubit: usize, theta: f64) -> &mut Circuit {
+        self.gate(Gate::Ry(theta), &[target_qubit])
+    }
+
+    /// Records a rotation by θ around the Z-axis on the specified target qubit
+    pub fn rz(&mut self, target_qubit: usize, theta: f64) -> &mut Circuit {
+        self.gate(Gate::Rz(theta), &[target_qubit])
+    }
+
+    /// Records the general single-qubit gate U3(θ, φ, λ) on the specified target qubit
+    pub fn u3(&mut self, target_qubit: usize, theta: f64, phi: f64, lambda: f64) -> &mut Circuit {
+        self.gate(Gate::U3(theta, phi, lambda), &[target_qubit])
+    }
+
+    /// Records the CNOT gate on the specified control and target qubits
+    pub fn cnot(&mut self, control_qubit: usize, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Cnot, &[control_qubit, target_qubit])
+    }
+
+    /// Records the CZ gate on the specified control and target qubits
+    pub fn cz(&mut self, control_qubit: usize, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Cz, &[control_qubit, target_qubit])
+    }
+
+    /// Records the controlled-phase gate CPhase(φ) on the specified control and target qubits
+    pub fn cphase(&mut self, control_qubit: usize, target_qubit: usize, phi: f64) -> &mut Circuit {
+        self.gate(Gate::CPhase(phi), &[control_qubit, target_qubit])
+    }
+
+    /// Records the SWAP gate on the specified pair of qubits
+    pub fn swap(&mut self, first_qubit: usize, second_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Swap, &[first_qubit, second_qubit])
+    }
+
+    /// Records the √SWAP gate on the specified pair of qubits
+    pub fn sqrt_swap(&mut self, first_qubit: usize, second_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::SqrtSwap, &[first_qubit, second_qubit])
+    }
+
+    /// Records the iSWAP gate on the specified pair of qubits
+    pub fn iswap(&mut self, first_qubit: usize, second_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::ISwap, &[first_qubit, second_qubit])
+    }
+
+    /// Records the Ising XX coupling RXX(θ) on the specified pair of qubits
+    pub fn rxx(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> &mut Circuit {
+        self.gate(Gate::Rxx(theta), &[first_qubit, second_qubit])
+    }
+
+    /// Records the Ising YY coupling RYY(θ) on the specified pair of qubits
+    pub fn ryy(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> &mut Circuit {
+        self.gate(Gate::Ryy(theta), &[first_qubit, second_qubit])
+    }
+
+    /// Records the Ising ZZ coupling RZZ(θ) on the specified pair of qubits
+    pub fn rzz(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> &mut Circuit {
+        self.gate(Gate::Rzz(theta), &[first_qubit, second_qubit])
+    }
+
+    /// Records the fSim(θ, φ) gate on the specified pair of qubits
+    pub fn fsim(&mut self, first_qubit: usize, second_qubit: usize, theta: f64, phi: f64) -> &mut Circuit {
+        self.gate(Gate::FSim(theta, phi), &[first_qubit, second_qubit])
+    }
+
+    /// Records the Toffoli (CCNOT) gate on the specified control and target qubits
+    pub fn ccx(&mut self, first_control: usize, second_control: usize, target_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::Ccx, &[first_control, second_control, target_qubit])
+    }
+
+    /// Records the Fredkin (controlled-SWAP) gate on the specified control qubit and pair of target qubits
+    pub fn cswap(&mut self, control_qubit: usize, first_qubit: usize, second_qubit: usize) -> &mut Circuit {
+        self.gate(Gate::CSwap, &[control_qubit, first_qubit, second_qubit])
+    }
+
+    /// Records an arbitrary single-qubit gate on the target qubit, conditioned on a set of control qubits
+    pub fn controlled(&mut self, gate: DMatrix<Complex<f64>>, controls: &[usize], target_qubit: usize) -> &mut Circuit {
+        let mut qubits = controls.to_vec();
+        qubits.push(target_qubit);
+        self.gate(Gate::Controlled { gate, controls: controls.len() }, &qubits)
+    }
+
+    /// Records an arbitrary unitary on the specified ordered list of qubits
+    pub fn unitary(&mut self, gate: DMatrix<Complex<f64>>, qubits: &[usize]) -> &mut Circuit {
+        self.gate(Gate::Unitary(gate), qubits)
+    }
+
+    /// Records a measurement of the whole register
+    pub fn measure_all(&mut self) -> &mut Circuit {
+        self.push(Instruction::MeasureAll)
+    }
+
+    /// Records a mid-circuit measurement of a qubit into a bit of the classical register.
+    ///
+    /// Bits are indexed from 1 to the number of qubits of the circuit, like in [`ClassicalRegister::bit`]
+    pub fn measure(&mut self, qubit: usize, bit: usize) -> &mut Circuit {
+        self.push(Instruction::Measure { qubit, bit })
+    }
+
+    /// Records a reset of a qubit to |0>
+    pub fn reset(&mut self, qubit: usize) -> &mut Circuit {
+        self.push(Instruction::Reset { qubit })
+    }
+
+    /// Records a gate applied only if the condition on the classical register holds when it is reached
+    pub fn gate_if(&mut self, condition: Condition, gate: Gate, qubits: &[usize]) -> &mut Circuit {
+        self.push(Instruction::Conditional { condition, gate, qubits: qubits.to_vec() })
+    }
+
+    /// Records a noise channel on the given qubits, e.g. after the gate whose error it models
+    pub fn channel(&mut self, channel: KrausChannel, qubits: &[usize]) -> &mut Circuit {
+        self.push(Instruction::Channel { channel, qubits: qubits.to_vec() })
+    }
+
+    /// Returns true if the circuit records any measurement, whole-register or mid-circuit
+    pub fn has_measurements(&self) -> bool {
+        self.instructions.iter().any(|instruction| matches!(instruction, Instruction::MeasureAll | Instruction::Measure { .. }))
+    }
+
+    /// Executes the circuit on a quantum register.
+    ///
+    /// Returns the classical register of the run, one bit per qubit of the circuit, holding the outcome of the
+    /// measurements (all zeros if the circuit measures nothing). The circuit itself is left untouched, so it can
+    /// be applied again to another register
+    pub fn apply<B: Backend>(&self, register: &mut QuantumRegister<B>) -> Result<ClassicalRegister, QuriustError> {
+        let mut classical_register = ClassicalRegister::zeros(self.num_qubits);
+        self.apply_with_classical(register, &mut classical_register)?;
+
+        Ok(classical_register)
+    }
+
+    /// Executes the circuit on a quantum register, reading and writing the given classical register.
+    ///
+    /// Conditioned gates see the bits the register held before the run, until a measurement overwrites them.
+    /// The classical register must have one bit per qubit of the circuit
+    pub fn apply_with_classical<B: Backend>(&self, register: &mut QuantumRegister<B>, classical_register: &mut ClassicalRegister) -> Result<(), QuriustError> {
+        if classical_register.len() != self.num_qubits {
+            return Err(QuriustError::DimensionMismatch { expected: self.num_qubits, found: classical_register.len() });
+        }
+
+        self.apply_instructions(&self.instructions, register, classical_register)
+    }
+
+    /// Executes the circuit on a quantum computer, like `apply`, storing the outcome in its classical register
+    pub fn apply_to_computer<B: Backend>(&self, computer: &mut QuantumComputer<B>) -> Result<ClassicalRegister, QuriustError> {
+        computer.execute(self)
+    }
+
+    /// Runs the circuit for the given number of shots and returns the histogram of the outcomes.
+    ///
+    /// When the circuit only applies ideal gates before its final measurements, it is simulated once on a fresh
+    /// register and the resulting distribution is sampled `shots` times, every shot measuring the whole register.
+    /// Circuits with mid-circuit measurements, resets, conditioned gates or noise are simulated again for every shot
+    /// instead, and the histogram counts the classical register of each run; those that record no measurement
+    /// are measured in full at the end of each shot
+    pub fn run(&self, shots: usize) -> Result<Counts, QuriustError> {
+        self.run_on(&mut QuantumRegister::init(self.num_qubits), shots)
+    }
+
+    /// Runs the circuit like `run`, drawing every outcome from a generator seeded with `seed`
+    pub fn run_with_seed(&self, shots: usize, seed: u64) -> Result<Counts, QuriustError> {
+        self.run_on(&mut QuantumRegister::init(self.num_qubits).with_seed(seed), shots)
+    }
+
+    /// Runs the circuit like `run`, drawing the randomness from the given generator
+    pub fn run_with_rng<R: RngCore + Clone + Send + Sync + 'static>(&self, shots: usize, rng: R) -> Result<Counts, QuriustError> {
+        self.run_on(&mut QuantumRegister::init(self.num_qubits).with_rng(rng), shots)
+    }
+
+    /// Runs the circuit like `run`, with the noise of the given model
+    pub fn run_with_noise(&self, shots: usize, noise_model: &NoiseModel) -> Result<Counts, QuriustError> {
+        self.run_on(&mut QuantumRegister::init(self.num_qubits).with_noise_model(noise_model.clone()), shots)
+    }
+
+    /// Runs the circuit like `run` on the given register, which is reset first.
+    ///
+    /// The register brings its random number generator and its noise model, so this is the way to combine a
+    /// seed with noise. Gate errors are trajectories, so a noisy circuit is simulated again for every shot
+    pub fn run_on<B: Backend>(&self, register: &mut QuantumRegister<B>, shots: usize) -> Result<Counts, QuriustError> {
+        let qubit_count = register.get_qubit_count();
+        if qubit_count != self.num_qubits {
+            return Err(QuriustError::DimensionMismatch { expected: self.num_qubits, found: qubit_count });
+        }
+        register.reset();
+
+        let unitary_len = self.instructions.len() - self.instructions.iter().rev().take_while(|instruction| **instruction == Instruction::MeasureAll).count();
+        let mut classical_register = ClassicalRegister::zeros(self.num_qubits);
+
+        let sampled = !register.noise_model().has_gate_errors()
+            && self.instructions[..unitary_len].iter().all(|instruction| matches!(instruction, Instruction::Gate { .. }));
+        if sampled {
+            self.apply_instructions(&self.instructions[..unitary_len], register, &mut classical_register)?;
+            return register.try_sample(shots);
+        }
+
+        let mut counts = Counts::new();
+        for _ in 0..shots {
+            register.reset();
+            classical_register = ClassicalRegister::zeros(self.num_qubits);
+            self.apply_instructions(&self.instructions, register, &mut classical_register)?;
+            if !self.has_measurements() {
+                classical_register = register.try_measure()?;
+            }
+            *counts.entry(classical_register).or_insert(0) += 1;
+        }
+
+        Ok(counts)
+    }
+
+    /// Runs the circuit like `run`, simulating it on a stabilizer tableau instead of a state vector.
+    ///
+    /// The tableau needs O(n²) memory, so Clifford circuits on hundreds of qubits can be run. Returns an error
+    /// if the circuit uses a gate outside H, S, S†, X, Y, Z, CNOT, CZ and SWAP, or a noise channel
+    pub fn run_stabilizer(&self, shots: usize) -> Result<Counts, QuriustError> {
+        self.run_on(&mut QuantumRegister::from_backend(StabilizerState::new(self.num_qubits)), shots)
+    }
+
+    /// Runs the circuit on a stabilizer tableau like `run_stabilizer`, seeding the random number generator
+    pub fn run_stabilizer_with_seed(&self, shots: usize, seed: u64) -> Result<Counts, QuriustError> {
+        self.run_on(&mut QuantumRegister::from_backend(StabilizerState::new(self.num_qubits)).with_seed(seed), shots)
+    }
+
+    /// Fuses runs of neighbouring gates on at most `MAX_FUSED_QUBITS` qubits into single unitaries, each applied
+    /// in one pass over the state. Measurements, resets, channels and conditioned gates end the runs on their
+    /// qubits, and the fused circuit is no longer a Clifford circuit. Use `fuse_with_noise` with a noise model.
+    ///
+    /// # Returns
+    ///
+    /// The fused circuit and the number of passes over the state it saves.
+    pub fn fuse(&self) -> (Circuit, usize) {
+        self.fuse_with_noise(&NoiseModel::new())
+    }
+
+    /// Fuses the gates of the circuit like `fuse`, leaving alone the gates that have gate errors in the noise model.
+    ///
+    /// Gate errors are looked up by the name of a gate, so a fused gate would run without the errors of the gates
+    /// it replaces. Runs are not fused either if the model has gate errors for `Gate::Unitary` on their qubits
+    pub fn fuse_with_noise(&self, noise_model: &NoiseModel) -> (Circuit, usize) {
+        let mut fused = Circuit::new(self.num_qubits);
+        let mut pending: Vec<FusionBlock> = Vec::new();
+        let mut passes_saved = 0;
+
+        for instruction in &self.instructions {
+            let qubits = match instruction {
+                Instruction::Gate { gate, qubits } if self.is_fusable(gate, qubits) && noise_model.gate_errors(gate, qubits).is_empty() => {
+                    let (touched, kept): (Vec<FusionBlock>, Vec<FusionBlock>) = pending.into_iter().partition(|block| block.overlaps(qubits));
+                    pending = kept;
+
+                    let new_qubits = qubits.iter().filter(|qubit| !touched.iter().any(|block| block.qubits.contains(qubit))).count();
+                    let span = touched.iter().map(|block| block.qubits.len()).sum::<usize>() + new_qubits;
+                    let mut block = if span <= MAX_FUSED_QUBITS {
+                        touched.into_iter().fold(FusionBlock::default(), FusionBlock::absorb)
+                    } else {
+                        for block in touched {
+                            passes_saved += block.record(&mut fused, noise_model);
+                        }
+                        FusionBlock::default()
+                    };
+
+                    block.push(gate, qubits);
+                    pending.push(block);
+                    continue;
+                }
+                Instruction::Gate { qubits, .. } | Instruction::Conditional { qubits, .. } | Instruction::Channel { qubits, .. } => qubits.clone(),
+                Instruction::Measure { qubit, .. } | Instruction::Reset { qubit } => vec![*qubit],
+                Instruction::MeasureAll => (1..=self.num_qubits).collect(),
+            };
+
+            // An instruction on an invalid qubit closes every pending gate, so that its error is raised on the same state
+            let invalid = qubits.iter().any(|&qubit| qubit == 0 || qubit > self.num_qubits);
+            let (touched, kept): (Vec<FusionBlock>, Vec<FusionBlock>) = pending.into_iter().partition(|block| invalid || block.overlaps(&qubits));
+            pending = kept;
+            for block in touched {
+                passes_saved += block.record(&mut fused, noise_model);
+            }
+            fused.push(instruction.clone());
+        }
+
+        for block in pending {
+            passes_saved += block.record(&mut fused, noise_model);
+        }
+
+        (fused, passes_saved)
+    }
+
+    /// Returns true if the gate can be fused, i.e. it acts on at most `MAX_FUSED_QUBITS` valid, distinct qubits
+    /// matching its matrix. Other gates are left for the execution to apply or reject
+    fn is_fusable(&self, gate: &Gate, qubits: &[usize]) -> bool {
+        if qubits.is_empty() || qubits.len() > MAX_FUSED_QUBITS {
+            return false;
+        }
+
+        let dimension = 1 << qubits.len();
+        gate.matrix().shape() == (dimension, dimension)
+            && qubits.iter().enumerate().all(|(i, &qubit)| qubit != 0 && qubit <= self.num_qubits && !qubits[..i].contains(&qubit))
+    }
+
+    /// Executes a slice of the instructions of the circuit, recording measurements in the classical register
+    fn apply_instructions<B: Backend>(&self, instructions: &[Instruction], register: &mut QuantumRegister<B>, classical_register: &mut ClassicalRegister) -> Result<(), QuriustError> {
+        for instruction in instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => register.try_apply(gate, qubits)?,
+                Instruction::MeasureAll => {
+                    *classical_register = register.try_measure()?.resized(self.num_qubits);
+                }
+                Instruction::Measure { qubit, bit } => {
+                    register.try_measure_into(*qubit, classical_register, *bit)?;
+                }
+                Instruction::Reset { qubit } => register.try_reset_qubit(*qubit)?,
+                Instruction::Conditional { condition, gate, qubits } => {
+                    register.try_apply_if(condition, classical_register, gate, qubits)?;
+                }
+                Instruction::Channel { channel, qubits } => register.try_apply_channel(channel, qubits)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Largest number of qubits a gate fused by `Circuit::fuse` acts on
+pub const MAX_FUSED_QUBITS: usize = 3;
+
+/// Consecutive gates waiting to be fused, together with the qubits they act on
+#[derive(Debug, Default)]
+struct FusionBlock {
+    qubits: Vec<usize>,
+    gates: Vec<(Gate, Vec<usize>)>,
+}
+
+impl FusionBlock {
+    /// Returns true if the block acts on any of the given qubits
+    fn overlaps(&self, qubits: &[usize]) -> bool {
+        qubits.iter().any(|qubit| self.qubits.contains(qubit))
+    }
+
+    /// Appends a gate to the block, extending it to the qubits of the gate
+    fn push(&mut self, gate: &Gate, qubits: &[usize]) {
+        for &qubit in qubits {
+            if !self.qubits.contains(&qubit) {
+                self.qubits.push(qubit);
+            }
+        }
+        self.gates.push((gate.clone(), qubits.to_vec()));
+    }
+
+    /// Merges a block on disjoint qubits into this one. Their gates commute, so they can simply be concatenated
+    fn absorb(mut self, other: FusionBlock) -> FusionBlock {
+        self.qubits.extend(other.qubits);
+        self.gates.extend(other.gates);
+        self
+    }
+
+    /// Records the block in the circuit, as one unitary if it holds several gates, and returns the passes saved.
+    /// The gates are recorded one by one if their product is not unitary or has gate errors in the noise model
+    fn record(self, circuit: &mut Circuit, noise_model: &NoiseModel) -> usize {
+        if self.gates.len() == 1 {
+            return self.record_gates(circuit);
+        }
+
+        // The first qubit of the block is the most significant bit of the matrix index, i.e. qubit k of the block
+        // is qubit m-k of a register of m qubits, and every gate acts on all the columns of the product so far
+        let dimension = 1 << self.qubits.len();
+        let mut matrix = DMatrix::identity(dimension, dimension);
+        for (gate, qubits) in &self.gates {
+            let gate = gate.matrix();
+            let local_qubits: Vec<usize> = qubits.iter()
+                .map(|qubit| self.qubits.len() - self.qubits.iter().position(|other| other == qubit).unwrap())
+                .collect();
+            for column in matrix.as_mut_slice().chunks_mut(dimension) {
+                apply_matrix(column, &gate, &local_qubits);
+            }
+        }
+
+        let deviation = unitarity_deviation(&matrix);
+        let fused = Gate::Unitary(matrix);
+        if deviation > DEFAULT_TOLERANCE || !noise_model.gate_errors(&fused, &self.qubits).is_empty() {
+            return self.record_gates(circuit);
+        }
+
+        circuit.gate(fused, &self.qubits);
+        self.gates.len() - 1
+    }
+
+    /// Records the gates of the block unfused, saving no pass
+    fn record_gates(self, circuit: &mut Circuit) -> usize {
+        for (gate, qubits) in self.gates {
+            circuit.gate(gate, &qubits);
+        }
+        0
+    }
+}
+
+
+#[test]
+fn test_circuit_builder() {
+    let mut circuit = Circuit::new(2);
+    circuit.h(1).cnot(1, 2).rz(2, 0.5).measure_all();
+
+    assert_eq!(circuit.num_qubits(), 2);
+    assert_eq!(circuit.len(), 4);
+    assert_eq!(circuit.instructions()[1], Instruction::Gate { gate: Gate::Cnot, qubits: vec![1, 2] });
+    assert_eq!(circuit.instructions()[2], Instruction::Gate { gate: Gate::Rz(0.5), qubits: vec![2] });
+    assert_eq!(circuit.instructions()[3], Instruction::MeasureAll);
+}
+
+#[test]
+fn test_circuit_matches_eager_execution() {
+    let mut circuit = Circuit::new(3);
+    circuit.h(1).cnot(1, 2).ry(3, 0.7).fsim(2, 3, 0.4, 0.1).ccx(1, 2, 3).cswap(3, 1, 2).sdg(1);
+
+    let mut eager: QuantumRegister = QuantumRegister::init(3);
+    eager.h(1);
+    eager.cnot(1, 2);
+    eager.ry(3, 0.7);
+    eager.fsim(2, 3, 0.4, 0.1);
+    eager.ccx(1, 2, 3);
+    eager.cswap(3, 1, 2);
+    eager.sdg(1);
+
+    // The same circuit can be applied to several registers
+    for _ in 0..2 {
+        let mut recorded: QuantumRegister = QuantumRegister::init(3);
+        assert_eq!(circuit.apply(&mut recorded), Ok(ClassicalRegister::zeros(3)));
+        assert_eq!(recorded.state(), eager.state());
+    }
+}
+
+#[test]
+fn test_circuit_measurement_and_errors() {
+    let mut circuit = Circuit::new(2);
+    circuit.x(2).measure_all();
+
+    let mut register: QuantumRegister = QuantumRegister::init(2);
+    assert_eq!(circuit.apply(&mut register), Ok(ClassicalRegister::new(vec![1, 0])));
+
+    let mut computer = QuantumComputer::new(2);
+    assert_eq!(circuit.apply_to_computer(&mut computer), Ok(ClassicalRegister::new(vec![1, 0])));
+
+    let mut circuit = Circuit::new(2);
+    circuit.measure_all().h(1);
+    let mut register: QuantumRegister = QuantumRegister::init(2);
+    assert_eq!(circuit.apply(&mut register), Err(QuriustError::RegisterMeasured));
+
+    let mut circuit = Circuit::new(2);
+    circuit.cnot(1, 3);
+    let mut register: QuantumRegister = QuantumRegister::init(2);
+    assert_eq!(circuit.apply(&mut register), Err(QuriustError::InvalidQubit { qubit: 3, qubit_count: 2 }));
+}
+
+#[test]
+fn test_circuit_run_shots() {
+    let mut circuit = Circuit::new(3);
+    circuit.h(1).cnot(1, 2).cnot(2, 3).measure_all();
+
+    let counts = circuit.run(4000).unwrap();
+    assert_eq!(counts.values().sum::<usize>(), 4000);
+    assert_eq!(counts.len(), 2);
+    let zeros = counts[&ClassicalRegister::zeros(3)];
+    assert!(zeros > 1800 && zeros < 2200);
+    assert_eq!(counts[&ClassicalRegister::new(vec![1, 1, 1])], 4000 - zeros);
+
+    // Circuits without a final measurement are measured anyway
+    let mut circuit = Circuit::new(2);
+    circuit.x(1);
+    let counts = circuit.run(10).unwrap();
+    assert_eq!(counts[&ClassicalRegister::new(vec![0, 1])], 10);
+
+    let mut circuit = Circuit::new(2);
+    circuit.measure_all().x(1);
+    assert_eq!(circuit.run(10), Err(QuriustError::RegisterMeasured));
+
+    let mut circuit = Circuit::new(3);
+    circuit.h(1).h(2).h(3);
+    assert_eq!(circuit.run_with_seed(500, 9).unwrap(), circuit.run_with_seed(500, 9).unwrap());
+}
+
+#[test]
+fn test_mid_circuit_measurement_and_conditions() {
+    // Teleports Ry(θ)|0> from qubit 1 to qubit 3, correcting with gates conditioned on the measured bits
+    let theta: f64 = 1.1;
+    let mut circuit = Circuit::new(3);
+    circuit.ry(1, theta).h(2).cnot(2, 3).cnot(1, 2).h(1)
+        .measure(1, 1).measure(2, 2)
+        .gate_if(Condition::Bit { bit: 2, value: 1 }, Gate::X, &[3])
+        .gate_if(Condition::Bit { bit: 1, value: 1 }, Gate::Z, &[3])
+        .measure(3, 3);
+
+    let counts = circuit.run_with_seed(4000, 17).unwrap();
+    assert_eq!(counts.values().sum::<usize>(), 4000);
+    let ones: usize = counts.iter().filter(|(outcome, _)| outcome.bit(3) == 1).map(|(_, count)| count).sum();
+    let expected = 4000.0 * (theta / 2.0).sin().powi(2);
+    assert!((ones as f64 - expected).abs() < 150.0);
+
+    // Reset brings a qubit back to |0>, whatever its state, and register equality conditions gates
+    let mut circuit = Circuit::new(2);
+    circuit.h(1).x(2).measure(1, 1).reset(1).reset(2)
+        .gate_if(Condition::Equals(1), Gate::X, &[2])
+        .measure(2, 2);
+    let counts = circuit.run(200).unwrap();
+    for (outcome, _) in counts.iter() {
+        assert_eq!(outcome.bit(2), outcome.bit(1));
+    }
+    assert_eq!(counts.len(), 2);
+
+    let mut circuit = Circuit::new(2);
+    circuit.measure(1, 3);
+    assert_eq!(circuit.run(1), Err(QuriustError::InvalidBit { bit: 3, bit_count: 2 }));
+    let mut circuit = Circuit::new(2);
+    circuit.gate_if(Condition::Bit { bit: 0, value: 1 }, Gate::X, &[1]);
+    assert_eq!(circuit.run(1), Err(QuriustError::InvalidBit { bit: 0, bit_count: 2 }));
+}
+
+#[test]
+fn test_noisy_circuit() {
+    let mut circuit = Circuit::new(2);
+    circuit.x(1).channel(KrausChannel::amplitude_damping(0.25), &[1]).cnot(1, 2).measure_all();
+
+    let counts = circuit.run_with_seed(2000, 5).unwrap();
+    assert_eq!(counts.len(), 2);
+    let decayed = counts[&ClassicalRegister::zeros(2)];
+    assert!(decayed > 430 && decayed < 570);
+    assert_eq!(counts[&ClassicalRegister::new(vec![1, 1])], 2000 - decayed);
+}
+
+#[test]
+fn test_circuit_with_noise_model() {
+    let mut circuit = Circuit::new(2);
+    circuit.h(1).cnot(1, 2).measure_all();
+
+    let mut model = NoiseModel::new();
+    model.add_gate_error("cnot", KrausChannel::bit_flip(0.2));
+    let counts = circuit.run_with_noise(2000, &model).unwrap();
+    assert_eq!(counts.values().sum::<usize>(), 2000);
+    let flipped = counts.get(&ClassicalRegister::new(vec![0, 1])).unwrap_or(&0) + counts.get(&ClassicalRegister::new(vec![1, 0])).unwrap_or(&0);
+    assert!(flipped > 520 && flipped < 760);
+
+    // Switching back to the ideal model only leaves the correlated outcomes
+    let ideal = circuit.run_with_noise(500, &NoiseModel::new()).unwrap();
+    assert_eq!(ideal.keys().filter(|outcome| outcome.value() == 1 || outcome.value() == 2).count(), 0);
+
+    let mut register = QuantumRegister::init(2).with_seed(8).with_noise_model(model);
+    let first = circuit.run_on(&mut register, 300).unwrap();
+    let mut register = QuantumRegister::init(2).with_seed(8).with_noise_model(register.noise_model().clone());
+    assert_eq!(circuit.run_on(&mut register, 300).unwrap(), first);
+    assert_eq!(circuit.run_on(&mut QuantumRegister::init(3), 1), Err(QuriustError::DimensionMismatch { expected: 2, found: 3 }));
+}
+
+#[test]
+fn test_stabilizer_run() {
+    let mut circuit = Circuit::new(2);
+    circuit.h(1).cnot(1, 2).s(2).sdg(2).measure_all();
+    let counts = circuit.run_stabilizer_with_seed(2000, 4).unwrap();
+    let zeros = counts[&ClassicalRegister::zeros(2)];
+    assert_eq!(counts.len(), 2);
+    assert!(zeros > 900 && zeros < 1100);
+    assert_eq!(circuit.run_stabilizer_with_seed(100, 6), circuit.run_stabilizer_with_seed(100, 6));
+
+    // A syndrome extraction on a 200-qubit repetition code, far beyond the reach of the state vector
+    let data = 100;
+    let mut circuit = Circuit::new(2 * data);
+    circuit.x(1).x(2);
+    for qubit in 1..data {
+        circuit.cnot(qubit, data + qubit).cnot(qubit + 1, data + qubit).measure(data + qubit, data + qubit);
+    }
+    let counts = circuit.run_stabilizer(3).unwrap();
+    let outcome = counts.keys().next().unwrap();
+    assert_eq!(counts[outcome], 3);
+    for qubit in 1..data {
+        assert_eq!(outcome.bit(data + qubit), (qubit == 2) as usize);
+    }
+
+    // Registers wider than 32 bits combine mid-circuit measurements, conditions and final measurements
+    let mut circuit = Circuit::new(40);
+    circuit.x(40).measure(1, 1).measure(40, 40).gate_if(Condition::Equals(1 << 39), Gate::X, &[2]).measure_all();
+    let mut expected = ClassicalRegister::zeros(40);
+    expected.set_bit(40, 1);
+    expected.set_bit(2, 1);
+    assert_eq!(circuit.run_stabilizer_with_seed(1, 1).unwrap()[&expected], 1);
+
+    let mut circuit = Circuit::new(1);
+    circuit.t(1);
+    assert_eq!(circuit.run_stabilizer(1), Err(QuriustError::UnsupportedGate { gate: "t".to_string() }));
+}
+
+#[test]
+fn test_gate_fusion() {
+    let mut circuit = Circuit::new(4);
+    for layer in 0..5 {
+        for qubit in 1..=4 {
+            circuit.rx(qubit, 0.1 * (layer + qubit) as f64).rz(qubit, 0.3).ry(qubit, 0.2);
+        }
+        circuit.cnot(1, 2).cz(3, 4).rzz(2, 3, 0.4);
+    }
+    circuit.ccx(1, 2, 3).fsim(3, 4, 0.5, 0.2).h(4);
+
+    // Every gate takes one pass over the state, fused or not, and the state is unchanged up to rounding
+    let (fused, passes_saved) = circuit.fuse();
+    assert_eq!(circuit.len() - fused.len(), passes_saved);
+    assert!(passes_saved > 60);
+
+    let passes = |circuit: &Circuit, register: &mut QuantumRegister| {
+        PASSES.with(|passes| passes.set(0));
+        circuit.apply(register).unwrap();
+        PASSES.with(Cell::get)
+    };
+    let mut expected: QuantumRegister = QuantumRegister::init(4);
+    assert_eq!(passes(&circuit, &mut expected), circuit.len());
+    let mut register: QuantumRegister = QuantumRegister::init(4);
+    assert_eq!(passes(&fused, &mut register), fused.len());
+    for (amplitude, expected) in register.state().iter().zip(expected.state()) {
+        assert!((amplitude - expected).norm() < 1e-12);
+    }
+
+    // Measurements close the pending gates on their qubits, and fused gates span at most three qubits
+    let mut circuit = Circuit::new(4);
+    circuit.x(1).t(1).h(2).measure(1, 1).h(1).cnot(3, 4).cnot(2, 3).x(4);
+    let (fused, passes_saved) = circuit.fuse();
+    assert_eq!(passes_saved, 4);
+    assert_eq!(fused.len(), 4);
+    assert!(matches!(&fused.instructions()[0], Instruction::Gate { gate: Gate::Unitary(_), qubits } if *qubits == vec![1]));
+    assert_eq!(fused.instructions()[1], Instruction::Measure { qubit: 1, bit: 1 });
+    assert_eq!(fused.instructions()[2], Instruction::Gate { gate: Gate::H, qubits: vec![1] });
+    assert!(matches!(&fused.instructions()[3], Instruction::Gate { gate: Gate::Unitary(matrix), .. } if matrix.nrows() == 8));
+
+    let mut expected: QuantumRegister = QuantumRegister::init(4).with_seed(5);
+    let expected_bits = circuit.apply(&mut expected).unwrap();
+    let mut register: QuantumRegister = QuantumRegister::init(4).with_seed(5);
+    assert_eq!(fused.apply(&mut register).unwrap(), expected_bits);
+    for (amplitude, expected) in register.state().iter().zip(expected.state()) {
+        assert!((amplitude - expected).norm() < 1e-12);
+    }
+
+    // Gates on more than three qubits or on invalid qubits are left untouched
+    let mut circuit = Circuit::new(4);
+    circuit.x(1).controlled(Gate::X.matrix(), &[1, 2, 3], 4).x(1).h(5).h(5);
+    assert_eq!(circuit.fuse(), (circuit.clone(), 0));
+
+    // So are non-unitary products, which the execution then rejects gate by gate
+    let mut circuit = Circuit::new(1);
+    circuit.h(1).unitary(Gate::X.matrix() * Complex::new(2.0, 0.0), &[1]);
+    assert_eq!(circuit.fuse(), (circuit.clone(), 0));
+
+    // Gates with gate errors in a noise model keep their name, and so their errors
+    let mut model = NoiseModel::new();
+    model.add_gate_error("cnot", KrausChannel::two_qubit_depolarizing(0.1));
+    let mut circuit = Circuit::new(2);
+    circuit.h(1).t(1).cnot(1, 2).rz(2, 0.3).h(2);
+    let (fused, passes_saved) = circuit.fuse_with_noise(&model);
+    assert_eq!(passes_saved, 2);
+    assert_eq!(fused.instructions()[1], Instruction::Gate { gate: Gate::Cnot, qubits: vec![1, 2] });
+
+    // Nor does fusion create a gate with errors of its own
+    let mut model = NoiseModel::new();
+    model.add_gate_error("unitary", KrausChannel::depolarizing(0.1));
+    assert_eq!(circuit.fuse_with_noise(&model), (circuit.clone(), 0));
+}
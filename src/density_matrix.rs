@@ -0,0 +1,356 @@
+use super::backend;
+use super::error::{expect_ok, QuriustError};
+use super::gates::Gate;
+use super::noise::KrausChannel;
+use super::state::{apply_matrix, sample_distribution, State, DEFAULT_TOLERANCE};
+#[cfg(test)]
+use super::state::kronecker_product;
+#[cfg(test)]
+use super::gates;
+#[cfg(test)]
+use super::registers::ClassicalRegister;
+use num_complex::Complex;
+use nalgebra::DMatrix;
+use rand::Rng;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Represents a possibly mixed quantum state as a density matrix ρ.
+///
+/// A register of n qubits needs a 2^n x 2^n matrix, so this costs the square of the memory of a `State`, but it
+/// can describe statistical mixtures, reduced states of subsystems and the effect of noise. Qubits are indexed
+/// like in `State`: qubit 1 is the least significant bit of the row and column indices
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityMatrix {
+    matrix: DMatrix<Complex<f64>>,
+}
+
+impl DensityMatrix {
+    /// Creates the density matrix of the pure state |0...0> on the specified number of qubits
+    pub fn new(qubit_count: usize) -> DensityMatrix {
+        let dimension = 1 << qubit_count;
+        let mut matrix = DMatrix::zeros(dimension, dimension);
+        matrix[(0, 0)] = Complex::new(1.0, 0.0);
+
+        DensityMatrix { matrix }
+    }
+
+    /// Creates the density matrix |ψ⟩⟨ψ| of a pure state vector
+    pub fn from_state(state: &State) -> DensityMatrix {
+        let amplitudes = state.amplitudes();
+        let dimension = amplitudes.len();
+
+        DensityMatrix { matrix: DMatrix::from_fn(dimension, dimension, |row, col| amplitudes[row] * amplitudes[col].conj()) }
+    }
+
+    /// Creates a density matrix from an explicit matrix.
+    ///
+    /// Returns an error if the matrix is not square with a power-of-two dimension. The matrix is expected to be
+    /// Hermitian, positive semidefinite and of unit trace, which is not checked
+    pub fn from_matrix(matrix: DMatrix<Complex<f64>>) -> Result<DensityMatrix, QuriustError> {
+        if matrix.nrows() != matrix.ncols() {
+            return Err(QuriustError::DimensionMismatch { expected: matrix.nrows(), found: matrix.ncols() });
+        }
+        if !matrix.nrows().is_power_of_two() {
+            return Err(QuriustError::DimensionMismatch { expected: matrix.nrows().next_power_of_two(), found: matrix.nrows() });
+        }
+
+        Ok(DensityMatrix { matrix })
+    }
+
+    /// Returns the underlying matrix
+    pub fn matrix(&self) -> &DMatrix<Complex<f64>> {
+        &self.matrix
+    }
+
+    /// Returns the number of qubits described by the density matrix
+    pub fn get_qubit_count(&self) -> usize {
+        self.matrix.nrows().trailing_zeros() as usize
+    }
+
+    /// Checks that every qubit index is between 1 and the number of qubits and appears only once
+    pub fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_qubits(qubits, self.get_qubit_count())
+    }
+
+    /// Returns the trace of the density matrix, i.e. the total probability
+    pub fn trace(&self) -> f64 {
+        self.matrix.diagonal().iter().map(|entry| entry.re).sum()
+    }
+
+    /// Returns the purity Tr(ρ²): one for pure states, down to 1/2^n for the maximally mixed state
+    pub fn purity(&self) -> f64 {
+        (&self.matrix * &self.matrix).diagonal().iter().map(|entry| entry.re).sum()
+    }
+
+    /// Returns the probability of every basis state, i.e. the diagonal of the density matrix
+    pub fn probabilities(&self) -> Vec<f64> {
+        self.matrix.diagonal().iter().map(|entry| entry.re).collect()
+    }
+
+    /// Applies the given gate to the specified ordered list of qubits, mapping ρ to UρU†.
+    ///
+    /// Panics if the qubits are invalid or their number does not match the gate
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        expect_ok(self.try_apply(gate, qubits));
+    }
+
+    /// Fallible version of [`DensityMatrix::apply`], returning an error instead of panicking.
+    pub fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_gate(gate, qubits, self.get_qubit_count(), DEFAULT_TOLERANCE)?;
+
+        self.matrix = self.conjugated(&gate.matrix(), qubits);
+        Ok(())
+    }
+
+    /// Applies an arbitrary 2^k x 2^k unitary to the specified ordered list of k qubits, like `State::apply_unitary`
+    pub fn apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        expect_ok(self.try_apply(&Gate::Unitary(gate.clone()), qubits));
+    }
+
+    /// Computes AρA† for an operator A on the given qubits, without building the full 2^n operator.
+    ///
+    /// A acts on every column of ρ to give Aρ; the same kernel applied to the columns of (Aρ)† gives A(Aρ)†,
+    /// whose adjoint is AρA†
+    pub(crate) fn conjugated(&self, operator: &DMatrix<Complex<f64>>, qubits: &[usize]) -> DMatrix<Complex<f64>> {
+        let dimension = self.matrix.nrows();
+
+        let mut product = self.matrix.clone();
+        for column in product.as_mut_slice().chunks_mut(dimension) {
+            apply_matrix(column, operator, qubits);
+        }
+
+        let mut product = product.adjoint();
+        for column in product.as_mut_slice().chunks_mut(dimension) {
+            apply_matrix(column, operator, qubits);
+        }
+
+        product.adjoint()
+    }
+
+    /// Applies a noise channel to the given qubits, mapping ρ to Σ KρK† over its Kraus operators.
+    ///
+    /// A single-qubit channel acts independently on every listed qubit, a larger one on all of them at once
+    pub fn apply_channel(&mut self, channel: &KrausChannel, qubits: &[usize]) {
+        expect_ok(self.try_apply_channel(channel, qubits));
+    }
+
+    /// Fallible version of [`DensityMatrix::apply_channel`], returning an error instead of panicking.
+    pub fn try_apply_channel(&mut self, channel: &KrausChannel, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.check_qubits(qubits)?;
+
+        for targets in channel.placements(qubits)? {
+            let dimension = self.matrix.nrows();
+            self.matrix = channel.operators().iter()
+                .fold(DMatrix::zeros(dimension, dimension), |sum, operator| sum + self.conjugated(operator, targets));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the expectation value Tr(ρO) of an observable acting on the given ordered qubits.
+    ///
+    /// For a pure state this is the same value as [`State::expectation`]
+    pub fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        assert_eq!(observable.shape(), (1 << qubits.len(), 1 << qubits.len()), "an observable on {} qubits must be a {}x{} matrix", qubits.len(), 1 << qubits.len(), 1 << qubits.len());
+        expect_ok(self.check_qubits(qubits));
+
+        let dimension = self.matrix.nrows();
+        let mut product = self.matrix.clone();
+        for column in product.as_mut_slice().chunks_mut(dimension) {
+            apply_matrix(column, observable, qubits);
+        }
+
+        product.diagonal().iter().map(|entry| entry.re).sum()
+    }
+
+    /// Traces out every qubit not listed, returning the reduced density matrix of the kept qubits.
+    ///
+    /// The kept qubits are renumbered from 1 in increasing order of their original index
+    pub fn partial_trace(&self, kept_qubits: &[usize]) -> DensityMatrix {
+        expect_ok(self.check_qubits(kept_qubits));
+
+        let mut kept_qubits = kept_qubits.to_vec();
+        kept_qubits.sort_unstable();
+        let kept_mask = kept_qubits.iter().fold(0, |mask, &qubit| mask | 1 << (qubit - 1));
+        let compress = |index: usize| {
+            kept_qubits.iter().enumerate().fold(0, |reduced, (position, &qubit)| reduced | ((index >> (qubit - 1)) & 1) << position)
+        };
+
+        let dimension = self.matrix.nrows();
+        let mut reduced = DMatrix::zeros(1 << kept_qubits.len(), 1 << kept_qubits.len());
+        for row in 0..dimension {
+            for col in 0..dimension {
+                if row & !kept_mask == col & !kept_mask {
+                    reduced[(compress(row), compress(col))] += self.matrix[(row, col)];
+                }
+            }
+        }
+
+        DensityMatrix { matrix: reduced }
+    }
+
+    /// Projectively measures the given qubits, collapsing the density matrix onto the observed outcome.
+    ///
+    /// # Returns
+    ///
+    /// The observed bits, in the same order as `qubits`.
+    pub fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        expect_ok(self.check_qubits(qubits));
+
+        let index = self.sample(1, rng)[0];
+        let bits: Vec<usize> = qubits.iter().map(|&qubit| (index >> (qubit - 1)) & 1).collect();
+        self.collapse(qubits, &bits);
+
+        bits
+    }
+
+    /// Samples basis states from the diagonal of the density matrix without collapsing it, like `State::sample`
+    pub fn sample<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        sample_distribution(&self.probabilities(), shots, rng)
+    }
+
+    /// Projects the density matrix onto the outcome `bits` of the given qubits and renormalises it.
+    ///
+    /// # Returns
+    ///
+    /// The probability the outcome had before the projection. The matrix is left untouched when it is zero.
+    pub fn collapse(&mut self, qubits: &[usize], bits: &[usize]) -> f64 {
+        assert_eq!(qubits.len(), bits.len(), "every measured qubit needs exactly one outcome bit");
+
+        let (mask, value) = qubits.iter().zip(bits).fold((0, 0), |(mask, value), (&qubit, &bit)| {
+            (mask | 1 << (qubit - 1), value | (bit & 1) << (qubit - 1))
+        });
+
+        let total = self.trace();
+        let probability: f64 = self.probabilities().iter().enumerate()
+            .filter(|&(index, _)| index & mask == value)
+            .map(|(_, probability)| probability)
+            .sum();
+
+        if probability > 0.0 {
+            let dimension = self.matrix.nrows();
+            for row in 0..dimension {
+                for col in 0..dimension {
+                    if row & mask == value && col & mask == value {
+                        self.matrix[(row, col)] *= total / probability;
+                    } else {
+                        self.matrix[(row, col)] = Complex::new(0.0, 0.0);
+                    }
+                }
+            }
+        }
+
+        probability / total
+    }
+}
+
+
+#[cfg(test)]
+fn assert_density_eq(actual: &DMatrix<Complex<f64>>, expected: &DMatrix<Complex<f64>>) {
+    assert_eq!(actual.shape(), expected.shape());
+    for (a, b) in actual.iter().zip(expected.iter()) {
+        assert!((a - b).norm() < 1e-12);
+    }
+}
+
+#[test]
+fn from_state_test() {
+    let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+    state.hadamard_gate(1);
+    state.cnot_gate(1, 2);
+
+    let rho = DensityMatrix::from_state(&state);
+    assert_eq!(rho.get_qubit_count(), 2);
+    assert!((rho.trace() - 1.0).abs() < 1e-12);
+    assert!((rho.purity() - 1.0).abs() < 1e-12);
+    assert!((rho.matrix()[(0, 3)].re - 0.5).abs() < 1e-12);
+    assert_density_eq(rho.matrix(), &DensityMatrix::from_state(&state).matrix().adjoint());
+
+    // The reduced state of half a Bell pair is maximally mixed
+    let reduced = rho.partial_trace(&[2]);
+    assert_density_eq(reduced.matrix(), &(DMatrix::identity(2, 2) * Complex::new(0.5, 0.0)));
+    assert!((reduced.purity() - 0.5).abs() < 1e-12);
+
+    assert!(DensityMatrix::from_matrix(DMatrix::zeros(3, 3)).is_err());
+    assert!(DensityMatrix::from_matrix(DMatrix::zeros(2, 4)).is_err());
+}
+
+#[test]
+fn gates_match_state_vector_test() {
+    let circuit = [
+        (Gate::H, vec![1]), (Gate::Ry(0.3), vec![3]), (Gate::Cnot, vec![1, 2]), (Gate::Sx, vec![2]),
+        (Gate::U3(0.4, 1.2, -0.7), vec![1]), (Gate::FSim(0.6, 0.2), vec![3, 1]), (Gate::Ccx, vec![2, 3, 1]),
+        (Gate::CSwap, vec![1, 3, 2]), (Gate::Rzz(0.9), vec![2, 3]), (Gate::Controlled { gate: gates::t(), controls: 2 }, vec![3, 1, 2]),
+    ];
+
+    let zz = kronecker_product(&gates::pauli_z(), &gates::pauli_z());
+    let observables = [(gates::pauli_x(), vec![1]), (gates::pauli_y(), vec![2]), (zz.clone(), vec![1, 3]), (zz, vec![3, 2])];
+    let mut rho = DensityMatrix::new(3);
+    let state = backend::assert_gates_match_state(&mut rho, &circuit, &observables, |rho, state| {
+        assert_density_eq(rho.matrix(), DensityMatrix::from_state(state).matrix());
+    });
+
+    for (probability, expected) in rho.probabilities().iter().zip(state.probabilities()) {
+        assert!((probability - expected).abs() < 1e-12);
+    }
+
+    assert_eq!(rho.try_apply(&Gate::Cnot, &[1]), Err(QuriustError::DimensionMismatch { expected: 2, found: 1 }));
+    assert_eq!(rho.try_apply(&Gate::Cnot, &[1, 4]), Err(QuriustError::InvalidQubit { qubit: 4, qubit_count: 3 }));
+}
+
+#[test]
+fn measurement_test() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for _ in 0..10 {
+        let mut rho = DensityMatrix::new(2);
+        rho.apply(&Gate::H, &[1]);
+        rho.apply(&Gate::Cnot, &[1, 2]);
+
+        let bits = rho.measure_qubits(&[2], &mut rng);
+        let index = if bits[0] == 1 { 3 } else { 0 };
+        assert!((rho.matrix()[(index, index)].re - 1.0).abs() < 1e-12);
+        assert!((rho.trace() - 1.0).abs() < 1e-12);
+    }
+
+    let mut rho = DensityMatrix::new(1);
+    rho.apply(&Gate::Ry(1.0), &[1]);
+    let probability = (0.5_f64).sin().powi(2);
+    assert!((rho.collapse(&[1], &[1]) - probability).abs() < 1e-12);
+    assert!((rho.probabilities()[1] - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn channels_test() {
+    let mut rho = DensityMatrix::new(2);
+    rho.apply_channel(&KrausChannel::depolarizing(1.0), &[1]);
+    assert_density_eq(&rho.partial_trace(&[1]).matrix().clone(), &(DMatrix::identity(2, 2) * Complex::new(0.5, 0.0)));
+
+    let mut rho = DensityMatrix::new(2);
+    rho.apply_channel(&KrausChannel::bit_flip(0.2), &[1, 2]);
+    let probabilities = rho.probabilities();
+    assert!((probabilities[0] - 0.64).abs() < 1e-12 && (probabilities[3] - 0.04).abs() < 1e-12);
+
+    let mut rho = DensityMatrix::new(1);
+    rho.apply(&Gate::X, &[1]);
+    rho.apply_channel(&KrausChannel::amplitude_damping(0.3), &[1]);
+    assert!((rho.probabilities()[1] - 0.7).abs() < 1e-12);
+
+    // Thermal relaxation decays the population with T1 and the coherence with T2
+    let (t1, t2, time) = (40.0_f64, 30.0_f64, 12.0_f64);
+    let mut rho = DensityMatrix::new(1);
+    rho.apply(&Gate::Ry(2.0 * (0.8_f64).sqrt().asin()), &[1]);
+    let coherence = rho.matrix()[(0, 1)].norm();
+    rho.apply_channel(&KrausChannel::thermal_relaxation(t1, t2, time), &[1]);
+    assert!((rho.probabilities()[1] - 0.8 * (-time / t1).exp()).abs() < 1e-12);
+    assert!((rho.matrix()[(0, 1)].norm() - coherence * (-time / t2).exp()).abs() < 1e-12);
+
+    let mut rho = DensityMatrix::new(2);
+    rho.apply(&Gate::H, &[1]);
+    rho.apply(&Gate::Cnot, &[1, 2]);
+    rho.apply_channel(&KrausChannel::two_qubit_depolarizing(1.0), &[2, 1]);
+    assert_density_eq(rho.matrix(), &(DMatrix::identity(4, 4) * Complex::new(0.25, 0.0)));
+    assert!((rho.trace() - 1.0).abs() < 1e-12);
+
+    assert_eq!(rho.try_apply_channel(&KrausChannel::two_qubit_depolarizing(0.1), &[1]), Err(QuriustError::DimensionMismatch { expected: 2, found: 1 }));
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+/// Represents the errors reported by the fallible operations of the simulator
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuriustError {
+    /// A qubit index is zero, larger than the number of qubits, or repeated within one operation
+    InvalidQubit { qubit: usize, qubit_count: usize },
+    /// A classical bit index is zero or larger than the number of bits of the classical register
+    InvalidBit { bit: usize, bit_count: usize },
+    /// The quantum register has already been measured and can no longer be operated on
+    RegisterMeasured,
+    /// The gate cannot be simulated by the chosen backend, e.g. a non-Clifford gate on a stabilizer state
+    UnsupportedGate { gate: String },
+    /// A matrix or register does not have the size required by the operation
+    DimensionMismatch { expected: usize, found: usize },
+    /// A gate matrix is not unitary: `deviation` is the largest entry of |U†U - I|
+    NonUnitaryGate { deviation: f64, tolerance: f64 },
+    /// A set of Kraus operators is not trace preserving: `deviation` is the largest entry of |ΣK†K - I|
+    InvalidChannel { deviation: f64, tolerance: f64 },
+    /// A noise model description could not be read or contains invalid parameters
+    InvalidNoiseModel { message: String },
+    /// The total probability of the state changed by more than the tolerance while applying a gate
+    NormDrift { before: f64, after: f64, tolerance: f64 },
+}
+
+impl fmt::Display for QuriustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuriustError::InvalidQubit { qubit, qubit_count } => {
+                write!(f, "invalid qubit {}: qubits are indexed from 1 to {} and may appear once per operation", qubit, qubit_count)
+            }
+            QuriustError::InvalidBit { bit, bit_count } => {
+                write!(f, "invalid classical bit {}: bits are indexed from 1 to {}", bit, bit_count)
+            }
+            QuriustError::RegisterMeasured => write!(f, "the quantum register has already been measured"),
+            QuriustError::UnsupportedGate { gate } => write!(f, "gate {} is not supported by this backend", gate),
+            QuriustError::DimensionMismatch { expected, found } => {
+                write!(f, "dimension mismatch: expected {}, found {}", expected, found)
+            }
+            QuriustError::NonUnitaryGate { deviation, tolerance } => {
+                write!(f, "gate is not unitary: |U†U - I| reaches {:e}, above the tolerance {:e}", deviation, tolerance)
+            }
+            QuriustError::InvalidChannel { deviation, tolerance } => {
+                write!(f, "channel is not trace preserving: |ΣK†K - I| reaches {:e}, above the tolerance {:e}", deviation, tolerance)
+            }
+            QuriustError::InvalidNoiseModel { message } => write!(f, "invalid noise model: {}", message),
+            QuriustError::NormDrift { before, after, tolerance } => {
+                write!(f, "state norm drifted from {} to {}, beyond the tolerance {:e}", before, after, tolerance)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuriustError {}
+
+/// Unwraps the result of a fallible operation, panicking with the description of the error
+pub(crate) fn expect_ok<T>(result: Result<T, QuriustError>) -> T {
+    result.unwrap_or_else(|error| panic!("{}", error))
+}
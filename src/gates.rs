@@ -0,0 +1,491 @@
+use num_complex::Complex;
+use nalgebra::DMatrix;
+
+/// Builds a 2x2 gate matrix from its entries, given in row-major order
+pub(crate) fn matrix_2x2(entries: [Complex<f64>; 4]) -> DMatrix<Complex<f64>> {
+    DMatrix::<Complex<f64>>::from_row_slice(2, 2, &entries)
+}
+
+/// Returns the identity gate matrix
+pub fn identity() -> DMatrix<Complex<f64>> {
+    DMatrix::identity(2, 2)
+}
+
+/// Returns the Pauli-X (NOT) gate matrix
+pub fn pauli_x() -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(0.0, 0.0), Complex::new(1.0, 0.0),
+        Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+    ])
+}
+
+/// Returns the Pauli-Y gate matrix
+pub fn pauli_y() -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(0.0, 0.0), Complex::new(0.0, -1.0),
+        Complex::new(0.0, 1.0), Complex::new(0.0, 0.0),
+    ])
+}
+
+/// Returns the Pauli-Z gate matrix
+pub fn pauli_z() -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0), Complex::new(-1.0, 0.0),
+    ])
+}
+
+/// Returns the Hadamard gate matrix
+pub fn hadamard() -> DMatrix<Complex<f64>> {
+    let q = 1.0 / 2.0_f64.sqrt();
+    matrix_2x2([
+        Complex::new(q, 0.0), Complex::new(q, 0.0),
+        Complex::new(q, 0.0), Complex::new(-q, 0.0),
+    ])
+}
+
+/// Returns the phase gate P(λ) matrix, which multiplies the |1⟩ component by e^(iλ)
+pub fn phase(lambda: f64) -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0), Complex::from_polar(1.0, lambda),
+    ])
+}
+
+/// Returns the S gate matrix, i.e. P(π/2)
+pub fn s() -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0), Complex::new(0.0, 1.0),
+    ])
+}
+
+/// Returns the S† gate matrix, i.e. P(-π/2)
+pub fn s_dagger() -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0), Complex::new(0.0, -1.0),
+    ])
+}
+
+/// Returns the T gate matrix, i.e. P(π/4)
+pub fn t() -> DMatrix<Complex<f64>> {
+    phase(std::f64::consts::FRAC_PI_4)
+}
+
+/// Returns the T† gate matrix, i.e. P(-π/4)
+pub fn t_dagger() -> DMatrix<Complex<f64>> {
+    phase(-std::f64::consts::FRAC_PI_4)
+}
+
+/// Returns the √X gate matrix, whose square is the Pauli-X gate
+pub fn sqrt_x() -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::new(0.5, 0.5), Complex::new(0.5, -0.5),
+        Complex::new(0.5, -0.5), Complex::new(0.5, 0.5),
+    ])
+}
+
+/// Returns the matrix of a rotation by θ around the X-axis
+pub fn rx(theta: f64) -> DMatrix<Complex<f64>> {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    matrix_2x2([
+        Complex::new(cos, 0.0), Complex::new(0.0, -sin),
+        Complex::new(0.0, -sin), Complex::new(cos, 0.0),
+    ])
+}
+
+/// Returns the matrix of a rotation by θ around the Y-axis
+pub fn ry(theta: f64) -> DMatrix<Complex<f64>> {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    matrix_2x2([
+        Complex::new(cos, 0.0), Complex::new(-sin, 0.0),
+        Complex::new(sin, 0.0), Complex::new(cos, 0.0),
+    ])
+}
+
+/// Returns the matrix of a rotation by θ around the Z-axis
+pub fn rz(theta: f64) -> DMatrix<Complex<f64>> {
+    matrix_2x2([
+        Complex::from_polar(1.0, -theta / 2.0), Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0), Complex::from_polar(1.0, theta / 2.0),
+    ])
+}
+
+/// Returns the matrix of the general single-qubit gate U3(θ, φ, λ)
+pub fn u3(theta: f64, phi: f64, lambda: f64) -> DMatrix<Complex<f64>> {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    matrix_2x2([
+        Complex::new(cos, 0.0), -Complex::from_polar(sin, lambda),
+        Complex::from_polar(sin, phi), Complex::from_polar(cos, phi + lambda),
+    ])
+}
+
+/// Builds a 4x4 gate matrix from its entries, given in row-major order
+fn matrix_4x4(entries: [Complex<f64>; 16]) -> DMatrix<Complex<f64>> {
+    DMatrix::<Complex<f64>>::from_row_slice(4, 4, &entries)
+}
+
+/// Builds a 4x4 gate matrix that only mixes the |01⟩ and |10⟩ components, given the four diagonal
+/// entries and the two off-diagonal entries of the central block
+fn two_qubit_matrix(diagonal: [Complex<f64>; 4], off_diagonal: [Complex<f64>; 2]) -> DMatrix<Complex<f64>> {
+    let zero = Complex::new(0.0, 0.0);
+    matrix_4x4([
+        diagonal[0], zero, zero, zero,
+        zero, diagonal[1], off_diagonal[0], zero,
+        zero, off_diagonal[1], diagonal[2], zero,
+        zero, zero, zero, diagonal[3],
+    ])
+}
+
+// Two-qubit gate matrices are written in the basis |ab⟩, where `a` is the first qubit the gate is
+// applied to and `b` the second one.
+
+/// Returns the CNOT gate matrix, with the first qubit as control
+pub fn cnot() -> DMatrix<Complex<f64>> {
+    let (zero, one) = (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0));
+    matrix_4x4([
+        one, zero, zero, zero,
+        zero, one, zero, zero,
+        zero, zero, zero, one,
+        zero, zero, one, zero,
+    ])
+}
+
+/// Returns the CZ gate matrix
+pub fn cz() -> DMatrix<Complex<f64>> {
+    cphase(std::f64::consts::PI)
+}
+
+/// Returns the controlled-phase gate matrix, which multiplies the |11⟩ component by e^(iφ)
+pub fn cphase(phi: f64) -> DMatrix<Complex<f64>> {
+    let (zero, one) = (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0));
+    two_qubit_matrix([one, one, one, Complex::from_polar(1.0, phi)], [zero, zero])
+}
+
+/// Returns the SWAP gate matrix
+pub fn swap() -> DMatrix<Complex<f64>> {
+    let (zero, one) = (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0));
+    two_qubit_matrix([one, zero, zero, one], [one, one])
+}
+
+/// Returns the √SWAP gate matrix, whose square is the SWAP gate
+pub fn sqrt_swap() -> DMatrix<Complex<f64>> {
+    let one = Complex::new(1.0, 0.0);
+    let (plus, minus) = (Complex::new(0.5, 0.5), Complex::new(0.5, -0.5));
+    two_qubit_matrix([one, plus, plus, one], [minus, minus])
+}
+
+/// Returns the iSWAP gate matrix, which swaps |01⟩ and |10⟩ with a phase of i
+pub fn iswap() -> DMatrix<Complex<f64>> {
+    let (zero, one, i) = (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(0.0, 1.0));
+    two_qubit_matrix([one, zero, zero, one], [i, i])
+}
+
+/// Returns the matrix of the Ising XX coupling RXX(θ) = exp(-iθ X⊗X / 2)
+pub fn rxx(theta: f64) -> DMatrix<Complex<f64>> {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    let (zero, cos, sin) = (Complex::new(0.0, 0.0), Complex::new(cos, 0.0), Complex::new(0.0, -sin));
+    matrix_4x4([
+        cos, zero, zero, sin,
+        zero, cos, sin, zero,
+        zero, sin, cos, zero,
+        sin, zero, zero, cos,
+    ])
+}
+
+/// Returns the matrix of the Ising YY coupling RYY(θ) = exp(-iθ Y⊗Y / 2)
+pub fn ryy(theta: f64) -> DMatrix<Complex<f64>> {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    let (zero, cos, sin) = (Complex::new(0.0, 0.0), Complex::new(cos, 0.0), Complex::new(0.0, sin));
+    matrix_4x4([
+        cos, zero, zero, sin,
+        zero, cos, -sin, zero,
+        zero, -sin, cos, zero,
+        sin, zero, zero, cos,
+    ])
+}
+
+/// Returns the matrix of the Ising ZZ coupling RZZ(θ) = exp(-iθ Z⊗Z / 2)
+pub fn rzz(theta: f64) -> DMatrix<Complex<f64>> {
+    let zero = Complex::new(0.0, 0.0);
+    let (even, odd) = (Complex::from_polar(1.0, -theta / 2.0), Complex::from_polar(1.0, theta / 2.0));
+    two_qubit_matrix([even, odd, odd, even], [zero, zero])
+}
+
+/// Returns the matrix of the fSim(θ, φ) gate, an iSWAP-like rotation by θ followed by a
+/// controlled phase of -φ on the |11⟩ component
+pub fn fsim(theta: f64, phi: f64) -> DMatrix<Complex<f64>> {
+    let (sin, cos) = theta.sin_cos();
+    let (one, cos, sin) = (Complex::new(1.0, 0.0), Complex::new(cos, 0.0), Complex::new(0.0, -sin));
+    two_qubit_matrix([one, cos, cos, Complex::from_polar(1.0, -phi)], [sin, sin])
+}
+
+/// Represents a quantum gate together with its parameters, independently of the qubits it is applied to.
+///
+/// Multi-qubit gates list their qubits in the order of their matrix basis: controls come first, and the
+/// first qubit is the most significant bit of the matrix index
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gate {
+    Id,
+    X,
+    Y,
+    Z,
+    H,
+    S,
+    Sdg,
+    T,
+    Tdg,
+    Sx,
+    P(f64),
+    Rx(f64),
+    Ry(f64),
+    Rz(f64),
+    U3(f64, f64, f64),
+    Cnot,
+    Cz,
+    CPhase(f64),
+    Swap,
+    SqrtSwap,
+    ISwap,
+    Rxx(f64),
+    Ryy(f64),
+    Rzz(f64),
+    FSim(f64, f64),
+    Ccx,
+    CSwap,
+    /// A user-supplied 2x2 gate on the last qubit, controlled by the `controls` qubits listed before it
+    Controlled { gate: DMatrix<Complex<f64>>, controls: usize },
+    /// A user-supplied 2^k x 2^k unitary on k qubits
+    Unitary(DMatrix<Complex<f64>>),
+}
+
+impl Gate {
+    /// Returns the lowercase name of the gate, e.g. `"h"` or `"cnot"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gate::Id => "id",
+            Gate::X => "x",
+            Gate::Y => "y",
+            Gate::Z => "z",
+            Gate::H => "h",
+            Gate::S => "s",
+            Gate::Sdg => "sdg",
+            Gate::T => "t",
+            Gate::Tdg => "tdg",
+            Gate::Sx => "sx",
+            Gate::P(_) => "p",
+            Gate::Rx(_) => "rx",
+            Gate::Ry(_) => "ry",
+            Gate::Rz(_) => "rz",
+            Gate::U3(..) => "u3",
+            Gate::Cnot => "cnot",
+            Gate::Cz => "cz",
+            Gate::CPhase(_) => "cphase",
+            Gate::Swap => "swap",
+            Gate::SqrtSwap => "sqrt_swap",
+            Gate::ISwap => "iswap",
+            Gate::Rxx(_) => "rxx",
+            Gate::Ryy(_) => "ryy",
+            Gate::Rzz(_) => "rzz",
+            Gate::FSim(..) => "fsim",
+            Gate::Ccx => "ccx",
+            Gate::CSwap => "cswap",
+            Gate::Controlled { .. } => "controlled",
+            Gate::Unitary(_) => "unitary",
+        }
+    }
+
+    /// Returns the number of qubits the gate acts on
+    pub fn num_qubits(&self) -> usize {
+        match self {
+            Gate::Cnot | Gate::Cz | Gate::CPhase(_) | Gate::Swap | Gate::SqrtSwap | Gate::ISwap
+            | Gate::Rxx(_) | Gate::Ryy(_) | Gate::Rzz(_) | Gate::FSim(..) => 2,
+            Gate::Ccx | Gate::CSwap => 3,
+            Gate::Controlled { controls, .. } => controls + 1,
+            Gate::Unitary(matrix) => matrix.nrows().trailing_zeros() as usize,
+            _ => 1,
+        }
+    }
+
+    /// Returns the matrix of the gate, written in the basis of its qubits
+    pub fn matrix(&self) -> DMatrix<Complex<f64>> {
+        match self {
+            Gate::Id => identity(),
+            Gate::X => pauli_x(),
+            Gate::Y => pauli_y(),
+            Gate::Z => pauli_z(),
+            Gate::H => hadamard(),
+            Gate::S => s(),
+            Gate::Sdg => s_dagger(),
+            Gate::T => t(),
+            Gate::Tdg => t_dagger(),
+            Gate::Sx => sqrt_x(),
+            Gate::P(lambda) => phase(*lambda),
+            Gate::Rx(theta) => rx(*theta),
+            Gate::Ry(theta) => ry(*theta),
+            Gate::Rz(theta) => rz(*theta),
+            Gate::U3(theta, phi, lambda) => u3(*theta, *phi, *lambda),
+            Gate::Cnot => cnot(),
+            Gate::Cz => cz(),
+            Gate::CPhase(phi) => cphase(*phi),
+            Gate::Swap => swap(),
+            Gate::SqrtSwap => sqrt_swap(),
+            Gate::ISwap => iswap(),
+            Gate::Rxx(theta) => rxx(*theta),
+            Gate::Ryy(theta) => ryy(*theta),
+            Gate::Rzz(theta) => rzz(*theta),
+            Gate::FSim(theta, phi) => fsim(*theta, *phi),
+            Gate::Ccx => controlled_matrix(&pauli_x(), 2),
+            Gate::CSwap => controlled_matrix(&swap(), 1),
+            Gate::Controlled { gate, controls } => controlled_matrix(gate, *controls),
+            Gate::Unitary(matrix) => matrix.clone(),
+        }
+    }
+}
+
+/// Builds the matrix of `gate` controlled by `controls` qubits listed before its own ones: the identity,
+/// except for the block where every control is in the |1⟩ state
+pub fn controlled_matrix(gate: &DMatrix<Complex<f64>>, controls: usize) -> DMatrix<Complex<f64>> {
+    let dimension = gate.nrows() << controls;
+    let offset = dimension - gate.nrows();
+    let mut matrix = DMatrix::identity(dimension, dimension);
+    matrix.view_mut((offset, offset), gate.shape()).copy_from(gate);
+
+    matrix
+}
+
+/// Returns the number of qubits of the gate with the given name, or `None` for `controlled` and `unitary`, whose
+/// size depends on their matrix, and for unknown names
+pub(crate) fn num_qubits_of(name: &str) -> Option<usize> {
+    let gates = [
+        Gate::Id, Gate::X, Gate::Y, Gate::Z, Gate::H, Gate::S, Gate::Sdg, Gate::T, Gate::Tdg, Gate::Sx,
+        Gate::P(0.0), Gate::Rx(0.0), Gate::Ry(0.0), Gate::Rz(0.0), Gate::U3(0.0, 0.0, 0.0),
+        Gate::Cnot, Gate::Cz, Gate::CPhase(0.0), Gate::Swap, Gate::SqrtSwap, Gate::ISwap,
+        Gate::Rxx(0.0), Gate::Ryy(0.0), Gate::Rzz(0.0), Gate::FSim(0.0, 0.0), Gate::Ccx, Gate::CSwap,
+    ];
+
+    gates.iter().find(|gate| gate.name() == name).map(Gate::num_qubits)
+}
+
+#[cfg(test)]
+fn assert_matrix_eq(a: &DMatrix<Complex<f64>>, b: &DMatrix<Complex<f64>>) {
+    assert_eq!(a.shape(), b.shape());
+    for (x, y) in a.iter().zip(b.iter()) {
+        assert!((x - y).norm() < 1e-12, "{} != {}", a, b);
+    }
+}
+
+#[test]
+fn pauli_and_hadamard_matrices_test() {
+    for gate in [pauli_x(), pauli_y(), pauli_z(), hadamard()] {
+        assert_matrix_eq(&(&gate * &gate), &identity());
+    }
+    assert_matrix_eq(&(pauli_x() * pauli_y()), &(pauli_z() * Complex::new(0.0, 1.0)));
+}
+
+#[test]
+fn phase_family_matrices_test() {
+    assert_matrix_eq(&s(), &phase(std::f64::consts::FRAC_PI_2));
+    assert_matrix_eq(&(s() * s()), &pauli_z());
+    assert_matrix_eq(&(t() * t()), &s());
+
+    assert_matrix_eq(&(s() * s_dagger()), &identity());
+    assert_matrix_eq(&(t() * t_dagger()), &identity());
+    assert_matrix_eq(&s_dagger(), &s().adjoint());
+    assert_matrix_eq(&t_dagger(), &t().adjoint());
+    assert_matrix_eq(&(phase(0.7) * phase(-0.7)), &identity());
+}
+
+#[test]
+fn sqrt_x_matrix_test() {
+    assert_matrix_eq(&(sqrt_x() * sqrt_x()), &pauli_x());
+    assert_matrix_eq(&(sqrt_x() * sqrt_x().adjoint()), &identity());
+}
+
+#[test]
+fn rotation_matrices_test() {
+    let pi = std::f64::consts::PI;
+    let minus_i = Complex::new(0.0, -1.0);
+
+    assert_matrix_eq(&rx(pi), &(pauli_x() * minus_i));
+    assert_matrix_eq(&ry(pi), &(pauli_y() * minus_i));
+    assert_matrix_eq(&rz(pi), &(pauli_z() * minus_i));
+
+    for theta in [0.3, 1.2, -2.5] {
+        assert_matrix_eq(&(rx(theta) * rx(-theta)), &identity());
+        assert_matrix_eq(&(ry(theta) * ry(-theta)), &identity());
+        assert_matrix_eq(&(rz(theta) * rz(-theta)), &identity());
+        assert_matrix_eq(&rz(theta), &(phase(theta) * Complex::from_polar(1.0, -theta / 2.0)));
+    }
+}
+
+#[test]
+fn u3_matrix_test() {
+    let pi = std::f64::consts::PI;
+
+    assert_matrix_eq(&u3(pi / 2.0, 0.0, pi), &hadamard());
+    assert_matrix_eq(&u3(pi, 0.0, pi), &pauli_x());
+    assert_matrix_eq(&u3(0.4, -pi / 2.0, pi / 2.0), &rx(0.4));
+    assert_matrix_eq(&u3(0.4, 0.0, 0.0), &ry(0.4));
+
+    // U3(θ, φ, λ)^-1 = U3(-θ, -λ, -φ)
+    let gate = u3(0.4, 1.1, -0.6);
+    assert_matrix_eq(&(&gate * u3(-0.4, 0.6, -1.1)), &identity());
+    assert_matrix_eq(&(&gate * gate.adjoint()), &identity());
+}
+
+#[test]
+fn two_qubit_matrices_test() {
+    let pi = std::f64::consts::PI;
+    let identity_4 = DMatrix::<Complex<f64>>::identity(4, 4);
+    let minus_i = Complex::new(0.0, -1.0);
+
+    for gate in [cnot(), cz(), cphase(0.3), swap(), sqrt_swap(), iswap(), rxx(0.7), ryy(-1.1), rzz(2.3), fsim(0.4, 1.2)] {
+        assert_matrix_eq(&(&gate * gate.adjoint()), &identity_4);
+    }
+
+    assert_matrix_eq(&(sqrt_swap() * sqrt_swap()), &swap());
+    assert_matrix_eq(&(swap() * swap()), &identity_4);
+    assert_matrix_eq(&(cphase(0.3) * cphase(-0.3)), &identity_4);
+    assert_matrix_eq(&fsim(-pi / 2.0, 0.0), &iswap());
+    assert_matrix_eq(&fsim(0.0, -pi / 2.0), &cphase(pi / 2.0));
+
+    let x = pauli_x();
+    let y = pauli_y();
+    let z = pauli_z();
+    assert_matrix_eq(&rxx(pi), &(crate::state::kronecker_product(&x, &x) * minus_i));
+    assert_matrix_eq(&ryy(pi), &(crate::state::kronecker_product(&y, &y) * minus_i));
+    assert_matrix_eq(&rzz(pi), &(crate::state::kronecker_product(&z, &z) * minus_i));
+
+    for theta in [0.3, -1.7] {
+        assert_matrix_eq(&(rxx(theta) * rxx(-theta)), &identity_4);
+        assert_matrix_eq(&(ryy(theta) * ryy(-theta)), &identity_4);
+        assert_matrix_eq(&(rzz(theta) * rzz(-theta)), &identity_4);
+    }
+}
+
+#[test]
+fn gate_enum_test() {
+    assert_matrix_eq(&Gate::Controlled { gate: pauli_x(), controls: 1 }.matrix(), &cnot());
+    assert_matrix_eq(&Gate::Controlled { gate: pauli_z(), controls: 1 }.matrix(), &cz());
+    assert_matrix_eq(&Gate::Controlled { gate: phase(0.4), controls: 1 }.matrix(), &cphase(0.4));
+
+    let ccx = Gate::Ccx.matrix();
+    assert_eq!(ccx.shape(), (8, 8));
+    assert_eq!(ccx[(6, 7)], Complex::new(1.0, 0.0));
+    assert_eq!(ccx[(7, 6)], Complex::new(1.0, 0.0));
+    assert_eq!(ccx[(5, 5)], Complex::new(1.0, 0.0));
+
+    let cswap = Gate::CSwap.matrix();
+    assert_eq!(cswap[(5, 6)], Complex::new(1.0, 0.0));
+    assert_eq!(cswap[(6, 5)], Complex::new(1.0, 0.0));
+    assert_eq!(cswap[(1, 1)], Complex::new(1.0, 0.0));
+
+    assert_eq!(Gate::U3(0.1, 0.2, 0.3).num_qubits(), 1);
+    assert_eq!(Gate::FSim(0.1, 0.2).num_qubits(), 2);
+    assert_eq!(Gate::CSwap.num_qubits(), 3);
+    assert_eq!(Gate::Controlled { gate: pauli_x(), controls: 3 }.num_qubits(), 4);
+    assert_eq!(Gate::Unitary(DMatrix::identity(8, 8)).num_qubits(), 3);
+    assert_eq!(Gate::SqrtSwap.name(), "sqrt_swap");
+}
@@ -5,8 +5,18 @@
 //! ## Modules
 //!
 //! - `algorithms`: Contains implementations of various quantum algorithms.
+//! - `backend`: Defines the trait shared by the state representations the simulator can run on.
+//! - `circuit`: Records circuits as instruction lists that can be executed later.
+//! - `density_matrix`: Implements mixed states as density matrices.
+//! - `error`: Defines the error type returned by fallible operations.
+//! - `gates`: Provides the matrices of the built-in quantum gates.
+//! - `mps`: Implements matrix product states for weakly entangled chains of qubits.
+//! - `noise`: Defines noise channels through their Kraus operators.
 //! - `registers`: Defines data structures for quantum registers.
+//! - `sparse`: Implements a state vector storing only its nonzero amplitudes.
+//! - `stabilizer`: Implements the stabilizer tableau for large Clifford circuits.
 //! - `state`: Implements the quantum state and operations on it.
+//! - `trajectory`: Simulates noisy circuits by Monte-Carlo quantum trajectories.
 //!
 //! ## Example
 //!
@@ -27,6 +37,16 @@
 //! ```
 
 pub mod algorithms;
+pub mod backend;
+pub mod circuit;
+pub mod density_matrix;
+pub mod error;
+pub mod gates;
+pub mod mps;
+pub mod noise;
 pub mod registers;
+pub mod sparse;
+pub mod stabilizer;
 pub mod state;
+pub mod trajectory;
 pub mod quantum_computer;
\ No newline at end of file
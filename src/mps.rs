@@ -0,0 +1,494 @@
+use super::backend;
+#[cfg(test)]
+use super::backend::Backend;
+use super::error::{expect_ok, QuriustError};
+use super::gates::{self, Gate};
+use super::registers::ClassicalRegister;
+use super::state::DEFAULT_TOLERANCE;
+#[cfg(test)]
+use super::state::{kronecker_product, State};
+use nalgebra::DMatrix;
+use num_complex::Complex;
+use rand::Rng;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Default largest bond dimension kept by the truncation
+pub const DEFAULT_MAX_BOND_DIMENSION: usize = 64;
+/// Default largest relative weight of the singular values discarded by one truncation
+pub const DEFAULT_TRUNCATION_THRESHOLD: f64 = 1e-12;
+
+/// Represents a quantum state as a matrix product state.
+///
+/// Every qubit k holds a pair of matrices A_k[0] and A_k[1], and the amplitude of a basis state is the product
+/// A_1[b_1] A_2[b_2] ... A_n[b_n]. The size of the matrices, the bond dimension, grows with the entanglement
+/// between the two halves of the chain, so weakly entangled states of 1D chains with tens of qubits fit in
+/// memory. Gates on several qubits are applied on neighbouring sites, swapping distant qubits next to each
+/// other first, and the result is split back with an SVD that keeps at most `max_bond_dimension` singular
+/// values and drops the ones below the truncation threshold. The discarded weight is accumulated in
+/// `truncation_error`. Qubits are indexed from 1, like in `State`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixProductState {
+    /// The pair of matrices of every qubit, qubit 1 first
+    tensors: Vec<[DMatrix<Complex<f64>>; 2]>,
+    /// Site of the orthogonality centre: every site on its left is left-orthogonal and every site on its right
+    /// is right-orthogonal
+    center: usize,
+    max_bond_dimension: usize,
+    truncation_threshold: f64,
+    truncation_error: f64,
+}
+
+impl MatrixProductState {
+    /// Creates the product state |0...0> on the specified number of qubits
+    pub fn new(qubit_count: usize) -> MatrixProductState {
+        assert!(qubit_count > 0, "a matrix product state needs at least one qubit");
+
+        let zero = DMatrix::from_element(1, 1, Complex::new(1.0, 0.0));
+        let one = DMatrix::zeros(1, 1);
+        MatrixProductState {
+            tensors: vec![[zero, one]; qubit_count],
+            center: 0,
+            max_bond_dimension: DEFAULT_MAX_BOND_DIMENSION,
+            truncation_threshold: DEFAULT_TRUNCATION_THRESHOLD,
+            truncation_error: 0.0,
+        }
+    }
+
+    /// Sets the largest bond dimension kept when splitting the sites after a gate
+    pub fn with_max_bond_dimension(mut self, max_bond_dimension: usize) -> MatrixProductState {
+        assert!(max_bond_dimension > 0, "the bond dimension must be at least 1");
+        self.max_bond_dimension = max_bond_dimension;
+        self
+    }
+
+    /// Sets the largest relative weight Σσ²/‖ψ‖² of the singular values dropped when splitting the sites, on
+    /// top of the bond dimension limit
+    pub fn with_truncation_threshold(mut self, truncation_threshold: f64) -> MatrixProductState {
+        self.truncation_threshold = truncation_threshold;
+        self
+    }
+
+    /// Returns the number of qubits of the state
+    pub fn get_qubit_count(&self) -> usize {
+        self.tensors.len()
+    }
+
+    /// Returns the largest bond dimension kept by the truncation
+    pub fn max_bond_dimension(&self) -> usize {
+        self.max_bond_dimension
+    }
+
+    /// Returns the dimension of the n-1 bonds, the bond between qubits k and k+1 first
+    pub fn bond_dimensions(&self) -> Vec<usize> {
+        self.tensors[1..].iter().map(|tensor| tensor[0].nrows()).collect()
+    }
+
+    /// Returns the total weight of the singular values discarded so far.
+    ///
+    /// The state is renormalised after every truncation, and its fidelity with the exact state is at least
+    /// roughly 1 minus this error
+    pub fn truncation_error(&self) -> f64 {
+        self.truncation_error
+    }
+
+    /// Checks that every qubit index is between 1 and the number of qubits and appears only once
+    pub fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_qubits(qubits, self.get_qubit_count())
+    }
+
+    /// Applies the given gate to the specified ordered list of qubits.
+    ///
+    /// Panics if the qubits are invalid or a user-supplied matrix is not unitary
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        expect_ok(self.try_apply(gate, qubits));
+    }
+
+    /// Fallible version of [`MatrixProductState::apply`], returning an error instead of panicking.
+    pub fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_gate(gate, qubits, self.get_qubit_count(), DEFAULT_TOLERANCE)?;
+        self.apply_operator(&gate.matrix(), qubits);
+        Ok(())
+    }
+
+    /// Applies a 2^k x 2^k unitary to the specified ordered list of k qubits.
+    ///
+    /// The matrix is written in the basis of the given qubits like in `State::apply_unitary`. Panics if the
+    /// matrix does not fit the qubits or is not unitary
+    pub fn apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        expect_ok(self.try_apply_unitary(gate, qubits));
+    }
+
+    /// Fallible version of [`MatrixProductState::apply_unitary`], returning an error instead of panicking.
+    pub fn try_apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Unitary(gate.clone()), qubits)
+    }
+
+    /// Applies a matrix to valid qubits, bringing them next to each other with SWAP gates first
+    fn apply_operator(&mut self, operator: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        if qubits.len() == 1 {
+            let site = &mut self.tensors[qubits[0] - 1];
+            let (a0, a1) = (site[0].clone(), site[1].clone());
+            site[0] = &a0 * operator[(0, 0)] + &a1 * operator[(0, 1)];
+            site[1] = &a0 * operator[(1, 0)] + &a1 * operator[(1, 1)];
+            return;
+        }
+
+        let mut sites: Vec<usize> = qubits.iter().map(|&qubit| qubit - 1).collect();
+        sites.sort_unstable();
+        let first = sites[0];
+
+        // Moving the i-th target next to the previous ones keeps the targets in increasing order
+        let swap = gates::swap();
+        let mut swaps = Vec::new();
+        for (i, &site) in sites.iter().enumerate().skip(1) {
+            for position in (first + i..site).rev() {
+                self.apply_block(&swap, position, 2);
+                swaps.push(position);
+            }
+        }
+
+        // Site first + i now holds the i-th smallest target, while the matrix follows the order of `qubits`
+        let count = qubits.len();
+        let positions: Vec<usize> = qubits.iter().map(|qubit| sites.binary_search(&(qubit - 1)).unwrap()).collect();
+        let permute = |index: usize| {
+            positions.iter().enumerate().fold(0, |permuted, (j, &position)| {
+                permuted | (((index >> (count - 1 - position)) & 1) << (count - 1 - j))
+            })
+        };
+        let dimension = 1 << count;
+        let block_operator = DMatrix::from_fn(dimension, dimension, |row, column| operator[(permute(row), permute(column))]);
+        self.apply_block(&block_operator, first, count);
+
+        for &position in swaps.iter().rev() {
+            self.apply_block(&swap, position, 2);
+        }
+    }
+
+    /// Applies a matrix to `count` consecutive sites starting at `first`, the first site being the most
+    /// significant bit of the matrix index, and splits the result back with truncated SVDs
+    fn apply_block(&mut self, operator: &DMatrix<Complex<f64>>, first: usize, count: usize) {
+        self.move_center(first);
+
+        let dimension = 1 << count;
+        let theta: Vec<DMatrix<Complex<f64>>> = (0..dimension).map(|index| {
+            (1..count).fold(self.tensors[first][index >> (count - 1)].clone(), |product, i| {
+                product * &self.tensors[first + i][(index >> (count - 1 - i)) & 1]
+            })
+        }).collect();
+        let (left, right) = theta[0].shape();
+
+        let mut updated = vec![DMatrix::zeros(left, right); dimension];
+        for (row, block) in updated.iter_mut().enumerate() {
+            for (column, matrix) in theta.iter().enumerate() {
+                let entry = operator[(row, column)];
+                if entry != Complex::new(0.0, 0.0) {
+                    *block += matrix * entry;
+                }
+            }
+        }
+
+        // Rows of `rest` run over (bit of the current site, left bond) and columns over (remaining bits, right bond)
+        let mut bond = left;
+        let mut remaining = dimension / 2;
+        let mut rest = DMatrix::from_fn(2 * left, remaining * right, |row, column| {
+            updated[(row / left) * remaining + column / right][(row % left, column % right)]
+        });
+
+        for i in 0..count - 1 {
+            let svd = rest.svd(true, true);
+            let singular_values = svd.singular_values;
+            let (kept, scale) = self.truncate(singular_values.as_slice());
+            let u = svd.u.unwrap();
+            let v_t = svd.v_t.unwrap();
+
+            self.tensors[first + i] = [u.view((0, 0), (bond, kept)).into_owned(), u.view((bond, 0), (bond, kept)).into_owned()];
+            let mut weighted = v_t.rows(0, kept).into_owned();
+            for (k, mut row) in weighted.row_iter_mut().enumerate() {
+                row *= Complex::new(singular_values[k] * scale, 0.0);
+            }
+
+            remaining /= 2;
+            rest = DMatrix::from_fn(2 * kept, remaining * right, |row, column| {
+                weighted[(row % kept, (row / kept) * remaining * right + column)]
+            });
+            bond = kept;
+        }
+
+        let last = first + count - 1;
+        self.tensors[last] = [rest.rows(0, bond).into_owned(), rest.rows(bond, bond).into_owned()];
+        self.center = last;
+    }
+
+    /// Returns how many singular values to keep and the factor rescaling them so the state stays normalised,
+    /// adding the discarded weight to the truncation error
+    fn truncate(&mut self, singular_values: &[f64]) -> (usize, f64) {
+        let total: f64 = singular_values.iter().map(|value| value * value).sum();
+        let mut kept = singular_values.len();
+        let mut discarded = 0.0;
+        while kept > 1 {
+            let weight = singular_values[kept - 1] * singular_values[kept - 1];
+            if kept <= self.max_bond_dimension && discarded + weight > self.truncation_threshold * total {
+                break;
+            }
+            discarded += weight;
+            kept -= 1;
+        }
+
+        if discarded > 0.0 && total > 0.0 {
+            self.truncation_error += discarded / total;
+            return (kept, (total / (total - discarded)).sqrt());
+        }
+
+        (kept, 1.0)
+    }
+
+    /// Moves the orthogonality centre to the given site with QR decompositions
+    fn move_center(&mut self, site: usize) {
+        while self.center < site {
+            // [A[0]; A[1]] = QR: Q becomes the left-orthogonal site and R moves into the next site
+            let center = self.center;
+            let bond = self.tensors[center][0].nrows();
+            let stacked = DMatrix::from_fn(2 * bond, self.tensors[center][0].ncols(), |row, column| {
+                self.tensors[center][row / bond][(row % bond, column)]
+            });
+            let qr = stacked.qr();
+            let (q, r) = (qr.q(), qr.r());
+
+            self.tensors[center] = [q.rows(0, bond).into_owned(), q.rows(bond, bond).into_owned()];
+            let next = &mut self.tensors[center + 1];
+            next[0] = &r * &next[0];
+            next[1] = &r * &next[1];
+            self.center += 1;
+        }
+
+        while self.center > site {
+            // [A[0] A[1]] = R†Q† from the QR decomposition of its adjoint: Q† becomes the right-orthogonal site
+            let center = self.center;
+            let bond = self.tensors[center][0].ncols();
+            let stacked = DMatrix::from_fn(self.tensors[center][0].nrows(), 2 * bond, |row, column| {
+                self.tensors[center][column / bond][(row, column % bond)]
+            });
+            let qr = stacked.adjoint().qr();
+            let (q, r) = (qr.q().adjoint(), qr.r().adjoint());
+
+            self.tensors[center] = [q.columns(0, bond).into_owned(), q.columns(bond, bond).into_owned()];
+            let previous = &mut self.tensors[center - 1];
+            previous[0] = &previous[0] * &r;
+            previous[1] = &previous[1] * &r;
+            self.center -= 1;
+        }
+    }
+
+    /// Returns the amplitude of a basis state, where bit k-1 of `index` holds qubit k like in `State`. Qubits
+    /// beyond the width of `index` are read as 0
+    pub fn amplitude(&self, index: usize) -> Complex<f64> {
+        let product = self.tensors.iter().enumerate().fold(DMatrix::from_element(1, 1, Complex::new(1.0, 0.0)), |product, (site, tensor)| {
+            product * &tensor[index.checked_shr(site as u32).unwrap_or(0) & 1]
+        });
+
+        product[(0, 0)]
+    }
+
+    /// Returns the probability of measuring the given basis state
+    pub fn probability(&self, index: usize) -> f64 {
+        self.amplitude(index).norm_sqr()
+    }
+
+    /// Returns the inner product ⟨self|other⟩ of two states on the same number of qubits
+    pub fn inner_product(&self, other: &MatrixProductState) -> Complex<f64> {
+        assert_eq!(self.get_qubit_count(), other.get_qubit_count(), "the states must have the same number of qubits");
+
+        // The environment E accumulates Σ A[b]† E B[b] from the left end of the chain
+        let environment = self.tensors.iter().zip(other.tensors.iter()).fold(DMatrix::from_element(1, 1, Complex::new(1.0, 0.0)), |environment, (a, b)| {
+            a[0].adjoint() * &environment * &b[0] + a[1].adjoint() * &environment * &b[1]
+        });
+
+        environment[(0, 0)]
+    }
+
+    /// Returns the total probability of the state
+    pub fn norm_sqr(&self) -> f64 {
+        self.inner_product(self).re
+    }
+
+    /// Returns the expectation value ⟨ψ|O|ψ⟩ of an observable acting on the given ordered qubits.
+    ///
+    /// The observable is written in the basis of its qubits like in `State::expectation`, and is applied to a
+    /// copy of the state without truncation
+    pub fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        assert_eq!(observable.shape(), (1 << qubits.len(), 1 << qubits.len()), "an observable on {} qubits must be a {}x{} matrix", qubits.len(), 1 << qubits.len(), 1 << qubits.len());
+        expect_ok(self.check_qubits(qubits));
+
+        let mut transformed = self.clone().with_max_bond_dimension(usize::MAX).with_truncation_threshold(0.0);
+        transformed.apply_operator(observable, qubits);
+
+        self.inner_product(&transformed).re
+    }
+
+    /// Projectively measures the given qubits, collapsing the state onto the observed outcome.
+    ///
+    /// # Returns
+    ///
+    /// The observed bits, in the same order as `qubits`.
+    pub fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        expect_ok(self.check_qubits(qubits));
+
+        qubits.iter().map(|&qubit| {
+            // With the centre on the qubit, the weights of its two matrices are the outcome probabilities
+            self.move_center(qubit - 1);
+            let site = &mut self.tensors[qubit - 1];
+            let (weight_0, weight_1) = (site[0].norm_squared(), site[1].norm_squared());
+            let bit = (rng.gen::<f64>() * (weight_0 + weight_1) < weight_1) as usize;
+
+            let weight = if bit == 1 { weight_1 } else { weight_0 };
+            site[bit] /= Complex::new(weight.sqrt(), 0.0);
+            site[1 - bit].fill(Complex::new(0.0, 0.0));
+            bit
+        }).collect()
+    }
+
+    /// Measures every qubit, returning a classical register where bit k holds qubit k
+    pub fn measure_all<R: Rng + ?Sized>(&mut self, rng: &mut R) -> ClassicalRegister {
+        let qubits: Vec<usize> = (1..=self.get_qubit_count()).rev().collect();
+        ClassicalRegister::new(self.measure_qubits(&qubits, rng))
+    }
+
+    /// Samples the measurement of every qubit many times without collapsing the state, returning the outcome of
+    /// every shot in order.
+    ///
+    /// Every shot draws the qubits one after the other from their conditional probabilities, sweeping the chain
+    /// from qubit 1, so a shot costs O(nχ²) and the 2^n probabilities are never built
+    pub(crate) fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        let mut state = self.clone();
+        state.move_center(0);
+
+        (0..shots).map(|_| {
+            let mut bits = Vec::with_capacity(state.get_qubit_count());
+            let mut environment = DMatrix::from_element(1, 1, Complex::new(1.0, 0.0));
+            for tensor in &state.tensors {
+                // The sites on the right are right-orthogonal, so ‖E A[b]‖² is the probability of b given the prefix
+                let branches = [&environment * &tensor[0], &environment * &tensor[1]];
+                let weights = [branches[0].norm_squared(), branches[1].norm_squared()];
+                let bit = (rng.gen::<f64>() * (weights[0] + weights[1]) < weights[1]) as usize;
+
+                environment = &branches[bit] / Complex::new(weights[bit].sqrt(), 0.0);
+                bits.push(bit);
+            }
+
+            bits.reverse();
+            ClassicalRegister::new(bits)
+        }).collect()
+    }
+
+    /// Resets every qubit to |0>, keeping the truncation settings and clearing the truncation error
+    pub(crate) fn reset(&mut self) {
+        *self = MatrixProductState::new(self.get_qubit_count())
+            .with_max_bond_dimension(self.max_bond_dimension)
+            .with_truncation_threshold(self.truncation_threshold);
+    }
+}
+
+#[test]
+fn gates_match_state_vector_test() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let qubit_count = 6;
+    let mut mps = MatrixProductState::new(qubit_count);
+
+    let circuit = [
+        (Gate::H, vec![1]),
+        (Gate::Ry(0.7), vec![4]),
+        (Gate::Cnot, vec![1, 2]),
+        (Gate::Cnot, vec![5, 1]),
+        (Gate::Rzz(0.4), vec![2, 6]),
+        (Gate::Ccx, vec![6, 1, 3]),
+        (Gate::U3(0.3, 1.1, -0.5), vec![3]),
+        (Gate::CSwap, vec![2, 5, 4]),
+        (Gate::FSim(0.8, 0.2), vec![4, 2]),
+        (Gate::Controlled { gate: gates::rx(0.9), controls: 2 }, vec![3, 6, 1]),
+        (Gate::Unitary(kronecker_product(&gates::hadamard(), &gates::t())), vec![5, 3]),
+    ];
+    let zz = kronecker_product(&gates::pauli_z(), &gates::pauli_z());
+    let observables = [(zz, vec![6, 2]), (gates::pauli_x(), vec![3])];
+    let assert_matches = |mps: &MatrixProductState, state: &State| backend::assert_amplitudes_match(|index| mps.amplitude(index), state);
+    let mut state = backend::assert_gates_match_state(&mut mps, &circuit, &observables, assert_matches);
+
+    assert!(mps.truncation_error() < 1e-12);
+    assert!((mps.norm_sqr() - 1.0).abs() < 1e-10);
+
+    let bits = mps.measure_qubits(&[2, 5], &mut rng);
+    state.collapse(&[2, 5], &bits);
+    assert_matches(&mps, &state);
+
+    assert_eq!(mps.try_apply(&Gate::Cnot, &[1, 7]), Err(QuriustError::InvalidQubit { qubit: 7, qubit_count }));
+    assert_eq!(mps.try_apply(&Gate::Cnot, &[1]), Err(QuriustError::DimensionMismatch { expected: 2, found: 1 }));
+    assert!(matches!(mps.try_apply(&Gate::Unitary(gates::pauli_x() * Complex::new(2.0, 0.0)), &[1]), Err(QuriustError::NonUnitaryGate { .. })));
+}
+
+#[test]
+fn truncation_test() {
+    // Every layer of a brickwork circuit doubles the bond dimension until it is capped
+    let qubit_count = 8;
+    let mut exact = MatrixProductState::new(qubit_count);
+    let mut truncated = MatrixProductState::new(qubit_count).with_max_bond_dimension(2);
+    for layer in 0..6 {
+        for qubit in 1..=qubit_count {
+            exact.apply(&Gate::Ry(0.3 * qubit as f64 + layer as f64), &[qubit]);
+            truncated.apply(&Gate::Ry(0.3 * qubit as f64 + layer as f64), &[qubit]);
+        }
+        for qubit in (1 + layer % 2..qubit_count).step_by(2) {
+            exact.apply(&Gate::Cz, &[qubit, qubit + 1]);
+            truncated.apply(&Gate::Cz, &[qubit, qubit + 1]);
+        }
+    }
+
+    assert!(exact.truncation_error() < 1e-12);
+    assert!(exact.bond_dimensions().iter().any(|&dimension| dimension > 2));
+    assert!(truncated.bond_dimensions().iter().all(|&dimension| dimension <= 2));
+    assert!(truncated.truncation_error() > 1e-6);
+    assert!((truncated.norm_sqr() - 1.0).abs() < 1e-10);
+
+    let fidelity = exact.inner_product(&truncated).norm_sqr();
+    assert!(fidelity < 1.0 - 1e-8);
+    assert!(fidelity > 1.0 - 2.0 * truncated.truncation_error());
+}
+
+#[test]
+fn long_chain_test() {
+    let mut rng = StdRng::seed_from_u64(8);
+
+    // A GHZ state on 60 qubits only needs bonds of dimension 2
+    let qubit_count = 60;
+    let mut ghz = MatrixProductState::new(qubit_count);
+    ghz.apply(&Gate::H, &[1]);
+    for qubit in 2..=qubit_count {
+        ghz.apply(&Gate::Cnot, &[qubit - 1, qubit]);
+    }
+
+    assert!(ghz.bond_dimensions().iter().all(|&dimension| dimension == 2));
+    assert!((ghz.probability(0) - 0.5).abs() < 1e-10);
+    assert!((ghz.probability(usize::MAX >> (usize::BITS as usize - qubit_count)) - 0.5).abs() < 1e-10);
+    assert!(ghz.probability(1).abs() < 1e-10);
+
+    // Chains longer than an index only read the low qubits from it
+    assert!((MatrixProductState::new(100).amplitude(0) - Complex::new(1.0, 0.0)).norm() < 1e-12);
+
+    let zz = kronecker_product(&gates::pauli_z(), &gates::pauli_z());
+    assert!((ghz.expectation(&zz, &[1, qubit_count]) - 1.0).abs() < 1e-9);
+
+    let counts = ghz.sample_counts(200, &mut rng);
+    assert_eq!(counts.len(), 2);
+    let zeros = counts[&ClassicalRegister::new(vec![0; qubit_count])];
+    assert_eq!(zeros + counts[&ClassicalRegister::new(vec![1; qubit_count])], 200);
+    assert!(zeros > 70 && zeros < 130);
+
+    let outcome = ghz.measure_all(&mut rng);
+    assert!(outcome == ClassicalRegister::new(vec![0; qubit_count]) || outcome == ClassicalRegister::new(vec![1; qubit_count]));
+
+    // Resetting one qubit of a product state leaves the others untouched
+    let mut chain = MatrixProductState::new(40);
+    chain.apply(&Gate::X, &[7]);
+    chain.apply(&Gate::X, &[20]);
+    assert_eq!(chain.reset_qubit(7, &mut rng), 1);
+    assert!((chain.probability(1 << 19) - 1.0).abs() < 1e-10);
+}
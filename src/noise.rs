@@ -0,0 +1,591 @@
+use super::error::{expect_ok, QuriustError};
+use super::gates::{self, matrix_2x2, Gate};
+use super::state::{kronecker_product, DEFAULT_TOLERANCE};
+use super::registers::ClassicalRegister;
+use num_complex::Complex;
+use nalgebra::DMatrix;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Represents a quantum channel by its Kraus operators {K_i}, mapping ρ to Σ K_i ρ K_i†.
+///
+/// The operators act on `num_qubits` qubits and are written in the basis of those qubits, like the matrices of
+/// `State::apply_unitary`. A channel can be applied exactly to a `DensityMatrix`, or stochastically to a pure
+/// `State` by picking one operator per application with the Born probabilities (a quantum trajectory)
+#[derive(Debug, Clone, PartialEq)]
+pub struct KrausChannel {
+    operators: Vec<DMatrix<Complex<f64>>>,
+    num_qubits: usize,
+}
+
+impl KrausChannel {
+    /// Creates a channel from user-defined Kraus operators.
+    ///
+    /// Returns an error if the operators are missing, are not square matrices of the same power-of-two size, or
+    /// do not preserve the trace, i.e. ΣK†K differs from the identity by more than `DEFAULT_TOLERANCE`
+    pub fn new(operators: Vec<DMatrix<Complex<f64>>>) -> Result<KrausChannel, QuriustError> {
+        let dimension = match operators.first() {
+            Some(operator) => operator.nrows(),
+            None => return Err(QuriustError::DimensionMismatch { expected: 1, found: 0 }),
+        };
+        if !dimension.is_power_of_two() || dimension < 2 {
+            return Err(QuriustError::DimensionMismatch { expected: dimension.next_power_of_two().max(2), found: dimension });
+        }
+        for operator in &operators {
+            if operator.shape() != (dimension, dimension) {
+                return Err(QuriustError::DimensionMismatch { expected: dimension, found: operator.ncols().max(operator.nrows()) });
+            }
+        }
+
+        let channel = KrausChannel { operators, num_qubits: dimension.trailing_zeros() as usize };
+        let deviation = channel.completeness_deviation();
+        if deviation > DEFAULT_TOLERANCE {
+            return Err(QuriustError::InvalidChannel { deviation, tolerance: DEFAULT_TOLERANCE });
+        }
+
+        Ok(channel)
+    }
+
+    /// Builds a channel from operators known to be valid
+    fn from_operators(operators: Vec<DMatrix<Complex<f64>>>) -> KrausChannel {
+        let num_qubits = operators[0].nrows().trailing_zeros() as usize;
+        KrausChannel { operators, num_qubits }
+    }
+
+    /// Returns the Kraus operators of the channel
+    pub fn operators(&self) -> &[DMatrix<Complex<f64>>] {
+        &self.operators
+    }
+
+    /// Returns the number of qubits the channel acts on
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    /// Returns how far the channel is from preserving the trace, as the largest entry of |ΣK†K - I|
+    pub fn completeness_deviation(&self) -> f64 {
+        let dimension = 1 << self.num_qubits;
+        let sum = self.operators.iter().fold(DMatrix::zeros(dimension, dimension), |sum, operator| sum + operator.adjoint() * operator);
+
+        (sum - DMatrix::<Complex<f64>>::identity(dimension, dimension)).iter().map(|entry| entry.norm()).fold(0.0, f64::max)
+    }
+
+    /// Returns the channel applying `self` first and then `after`, on the same qubits
+    pub fn compose(&self, after: &KrausChannel) -> KrausChannel {
+        assert_eq!(self.num_qubits, after.num_qubits, "only channels on the same number of qubits can be composed");
+
+        let operators = after.operators.iter()
+            .flat_map(|second| self.operators.iter().map(move |first| second * first))
+            .filter(|operator| operator.iter().any(|entry| entry.norm() > 0.0))
+            .collect();
+        KrausChannel::from_operators(operators)
+    }
+
+    /// Returns the single-qubit depolarizing channel ρ → (1 - p)ρ + p I/2.
+    ///
+    /// With probability p the qubit is replaced by the maximally mixed state, which amounts to an X, Y or Z
+    /// error with probability p/4 each
+    pub fn depolarizing(p: f64) -> KrausChannel {
+        assert_probability(p);
+
+        KrausChannel::from_operators(vec![
+            gates::identity() * Complex::new((1.0 - 3.0 * p / 4.0).sqrt(), 0.0),
+            gates::pauli_x() * Complex::new((p / 4.0).sqrt(), 0.0),
+            gates::pauli_y() * Complex::new((p / 4.0).sqrt(), 0.0),
+            gates::pauli_z() * Complex::new((p / 4.0).sqrt(), 0.0),
+        ])
+    }
+
+    /// Returns the two-qubit depolarizing channel ρ → (1 - p)ρ + p I/4, made of the 16 two-qubit Pauli errors
+    pub fn two_qubit_depolarizing(p: f64) -> KrausChannel {
+        assert_probability(p);
+
+        let paulis = [gates::identity(), gates::pauli_x(), gates::pauli_y(), gates::pauli_z()];
+        let mut operators = Vec::with_capacity(16);
+        for (i, first) in paulis.iter().enumerate() {
+            for (j, second) in paulis.iter().enumerate() {
+                let weight = if i == 0 && j == 0 { 1.0 - 15.0 * p / 16.0 } else { p / 16.0 };
+                operators.push(kronecker_product(first, second) * Complex::new(weight.sqrt(), 0.0));
+            }
+        }
+
+        KrausChannel::from_operators(operators)
+    }
+
+    /// Returns the bit-flip channel, applying X with probability p
+    pub fn bit_flip(p: f64) -> KrausChannel {
+        assert_probability(p);
+
+        KrausChannel::from_operators(vec![
+            gates::identity() * Complex::new((1.0 - p).sqrt(), 0.0),
+            gates::pauli_x() * Complex::new(p.sqrt(), 0.0),
+        ])
+    }
+
+    /// Returns the phase-flip channel, applying Z with probability p
+    pub fn phase_flip(p: f64) -> KrausChannel {
+        assert_probability(p);
+
+        KrausChannel::from_operators(vec![
+            gates::identity() * Complex::new((1.0 - p).sqrt(), 0.0),
+            gates::pauli_z() * Complex::new(p.sqrt(), 0.0),
+        ])
+    }
+
+    /// Returns the amplitude-damping channel, decaying |1> to |0> with probability γ (energy relaxation)
+    pub fn amplitude_damping(gamma: f64) -> KrausChannel {
+        assert_probability(gamma);
+
+        KrausChannel::from_operators(vec![
+            matrix_2x2([
+                Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+                Complex::new(0.0, 0.0), Complex::new((1.0 - gamma).sqrt(), 0.0),
+            ]),
+            matrix_2x2([
+                Complex::new(0.0, 0.0), Complex::new(gamma.sqrt(), 0.0),
+                Complex::new(0.0, 0.0), Complex::new(0.0, 0.0),
+            ]),
+        ])
+    }
+
+    /// Returns the phase-damping channel, which shrinks the coherences by a factor √(1 - λ) without any
+    /// exchange of energy
+    pub fn phase_damping(lambda: f64) -> KrausChannel {
+        assert_probability(lambda);
+
+        KrausChannel::from_operators(vec![
+            matrix_2x2([
+                Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
+                Complex::new(0.0, 0.0), Complex::new((1.0 - lambda).sqrt(), 0.0),
+            ]),
+            matrix_2x2([
+                Complex::new(0.0, 0.0), Complex::new(0.0, 0.0),
+                Complex::new(0.0, 0.0), Complex::new(lambda.sqrt(), 0.0),
+            ]),
+        ])
+    }
+
+    /// Returns the thermal-relaxation channel of a qubit left idle for `time`, at zero temperature.
+    ///
+    /// The population of |1> decays as exp(-time/T1) and the coherences as exp(-time/T2). The channel is
+    /// amplitude damping followed by the extra dephasing needed to reach T2, so T2 can be at most 2·T1
+    pub fn thermal_relaxation(t1: f64, t2: f64, time: f64) -> KrausChannel {
+        assert!(t1 > 0.0 && t2 > 0.0 && time >= 0.0, "relaxation times must be positive and the duration non-negative");
+        assert!(t2 <= 2.0 * t1, "T2 cannot exceed 2·T1");
+
+        let gamma = 1.0 - (-time / t1).exp();
+        let lambda = (1.0 - (time / t1 - 2.0 * time / t2).exp()).clamp(0.0, 1.0);
+
+        KrausChannel::amplitude_damping(gamma).compose(&KrausChannel::phase_damping(lambda))
+    }
+
+    /// Lists the groups of qubits the channel acts on when applied to `qubits`.
+    ///
+    /// A channel on as many qubits as listed acts on all of them at once, while a single-qubit channel acts
+    /// independently on every listed qubit. Any other count is a dimension mismatch
+    pub(crate) fn placements<'a>(&self, qubits: &'a [usize]) -> Result<Vec<&'a [usize]>, QuriustError> {
+        if qubits.len() == self.num_qubits {
+            Ok(vec![qubits])
+        } else if self.num_qubits == 1 && !qubits.is_empty() {
+            Ok(qubits.chunks(1).collect())
+        } else {
+            Err(QuriustError::DimensionMismatch { expected: self.num_qubits, found: qubits.len() })
+        }
+    }
+}
+
+/// Probabilities of reporting the wrong bit when measuring a qubit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReadoutError {
+    /// Probability of reading 1 when the qubit was found in |0>
+    pub prob_1_given_0: f64,
+    /// Probability of reading 0 when the qubit was found in |1>
+    pub prob_0_given_1: f64,
+}
+
+impl ReadoutError {
+    /// Creates a readout error from its two flip probabilities
+    pub fn new(prob_1_given_0: f64, prob_0_given_1: f64) -> ReadoutError {
+        assert_probability(prob_1_given_0);
+        assert_probability(prob_0_given_1);
+
+        ReadoutError { prob_1_given_0, prob_0_given_1 }
+    }
+
+    /// Returns the reported bit for a qubit actually found in the state `bit`
+    pub fn corrupt<R: Rng + ?Sized>(&self, bit: usize, rng: &mut R) -> usize {
+        let flip_probability = if bit == 0 { self.prob_1_given_0 } else { self.prob_0_given_1 };
+        if rng.gen::<f64>() < flip_probability {
+            1 - bit
+        } else {
+            bit
+        }
+    }
+}
+
+/// Associates an error channel with a kind of gate, optionally restricted to one ordered list of qubits
+#[derive(Debug, Clone, PartialEq)]
+struct GateError {
+    gate: String,
+    qubits: Option<Vec<usize>>,
+    channel: KrausChannel,
+}
+
+/// Describes the noise of a device: the channels following every kind of gate and the readout errors.
+///
+/// Attached to a `QuantumRegister` or a `QuantumComputer`, the model is applied automatically: every gate is
+/// followed by the channels registered for its name (see [`Gate::name`]) and every measured bit goes through
+/// the readout error of its qubit. Gate errors are simulated as quantum trajectories, one Kraus operator being
+/// picked at random every time. A model can be built in code or loaded from a JSON calibration file, e.g.
+///
+/// ```json
+/// {
+///     "gate_errors": [
+///         { "gate": "h", "channel": { "type": "depolarizing", "p": 0.001 } },
+///         { "gate": "cnot", "qubits": [1, 2], "channel": { "type": "two_qubit_depolarizing", "p": 0.01 } },
+///         { "gate": "x", "channel": { "type": "thermal_relaxation", "t1": 50.0, "t2": 70.0, "time": 0.05 } }
+///     ],
+///     "readout_errors": [
+///         { "prob_1_given_0": 0.01, "prob_0_given_1": 0.03 },
+///         { "qubit": 2, "prob_1_given_0": 0.02, "prob_0_given_1": 0.05 }
+///     ]
+/// }
+/// ```
+///
+/// The channel types are `depolarizing`, `two_qubit_depolarizing`, `bit_flip` and `phase_flip` (with `p`),
+/// `amplitude_damping` (with `gamma`), `phase_damping` (with `lambda`), `thermal_relaxation` (with `t1`, `t2`
+/// and `time`) and `kraus`, whose `operators` are matrices given as rows of `[re, im]` pairs. A readout error
+/// without `qubit` applies to every qubit that has no error of its own
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NoiseModel {
+    gate_errors: Vec<GateError>,
+    readout_errors: HashMap<usize, ReadoutError>,
+    default_readout_error: Option<ReadoutError>,
+}
+
+impl NoiseModel {
+    /// Creates an empty noise model, which leaves the simulation ideal
+    pub fn new() -> NoiseModel {
+        NoiseModel::default()
+    }
+
+    /// Returns true if the model holds no gate or readout error
+    pub fn is_ideal(&self) -> bool {
+        self.gate_errors.is_empty() && self.readout_errors.is_empty() && self.default_readout_error.is_none()
+    }
+
+    /// Returns true if the model holds at least one gate error
+    pub fn has_gate_errors(&self) -> bool {
+        !self.gate_errors.is_empty()
+    }
+
+    /// Adds a channel applied after every gate with the given name, whatever its qubits.
+    ///
+    /// A single-qubit channel acts on every qubit of the gate, a larger one on all of them at once
+    pub fn add_gate_error(&mut self, gate: &str, channel: KrausChannel) -> &mut NoiseModel {
+        expect_ok(self.try_add_gate_error(gate, channel))
+    }
+
+    /// Fallible version of [`NoiseModel::add_gate_error`], returning an error instead of panicking.
+    ///
+    /// A channel on several qubits must act on as many qubits as the named gate. The size of `controlled` and
+    /// `unitary` gates, like that of unknown names, is only checked when the gate is applied
+    pub fn try_add_gate_error(&mut self, gate: &str, channel: KrausChannel) -> Result<&mut NoiseModel, QuriustError> {
+        if let Some(num_qubits) = gates::num_qubits_of(gate) {
+            if channel.num_qubits() != 1 && channel.num_qubits() != num_qubits {
+                return Err(QuriustError::DimensionMismatch { expected: channel.num_qubits(), found: num_qubits });
+            }
+        }
+
+        self.gate_errors.push(GateError { gate: gate.to_string(), qubits: None, channel });
+        Ok(self)
+    }
+
+    /// Adds a channel applied after the gate with the given name only when it acts on exactly `qubits`, in order
+    pub fn add_gate_error_on(&mut self, gate: &str, qubits: &[usize], channel: KrausChannel) -> &mut NoiseModel {
+        expect_ok(self.try_add_gate_error_on(gate, qubits, channel))
+    }
+
+    /// Fallible version of [`NoiseModel::add_gate_error_on`], returning an error instead of panicking.
+    ///
+    /// The channel must fit the listed qubits, and so must the named gate
+    pub fn try_add_gate_error_on(&mut self, gate: &str, qubits: &[usize], channel: KrausChannel) -> Result<&mut NoiseModel, QuriustError> {
+        channel.placements(qubits)?;
+        if let Some(num_qubits) = gates::num_qubits_of(gate) {
+            if qubits.len() != num_qubits {
+                return Err(QuriustError::DimensionMismatch { expected: num_qubits, found: qubits.len() });
+            }
+        }
+
+        self.gate_errors.push(GateError { gate: gate.to_string(), qubits: Some(qubits.to_vec()), channel });
+        Ok(self)
+    }
+
+    /// Sets the readout error of one qubit
+    pub fn add_readout_error(&mut self, qubit: usize, error: ReadoutError) -> &mut NoiseModel {
+        self.readout_errors.insert(qubit, error);
+        self
+    }
+
+    /// Sets the readout error of every qubit without an error of its own
+    pub fn add_all_qubit_readout_error(&mut self, error: ReadoutError) -> &mut NoiseModel {
+        self.default_readout_error = Some(error);
+        self
+    }
+
+    /// Returns the channels to apply after a gate on the given qubits, in the order they were added
+    pub fn gate_errors(&self, gate: &Gate, qubits: &[usize]) -> Vec<&KrausChannel> {
+        self.gate_errors.iter()
+            .filter(|error| error.gate == gate.name() && error.qubits.as_ref().is_none_or(|targets| targets == qubits))
+            .map(|error| &error.channel)
+            .collect()
+    }
+
+    /// Returns the readout error of a qubit, if any
+    pub fn readout_error(&self, qubit: usize) -> Option<&ReadoutError> {
+        self.readout_errors.get(&qubit).or(self.default_readout_error.as_ref())
+    }
+
+    /// Returns the bit reported when measuring `qubit` in the state `bit`, after its readout error
+    pub fn read_out<R: Rng + ?Sized>(&self, qubit: usize, bit: usize, rng: &mut R) -> usize {
+        match self.readout_error(qubit) {
+            Some(error) => error.corrupt(bit, rng),
+            None => bit,
+        }
+    }
+
+    /// Applies the readout errors of the first `qubit_count` qubits to a measured basis state index
+    pub fn read_out_index<R: Rng + ?Sized>(&self, index: usize, qubit_count: usize, rng: &mut R) -> usize {
+        (1..=qubit_count).fold(0, |reported, qubit| reported | self.read_out(qubit, (index >> (qubit - 1)) & 1, rng) << (qubit - 1))
+    }
+
+    /// Applies the readout errors to a measured register where bit k holds qubit k, like `read_out_index`
+    pub fn read_out_register<R: Rng + ?Sized>(&self, outcome: &ClassicalRegister, rng: &mut R) -> ClassicalRegister {
+        let mut reported = outcome.clone();
+        for qubit in 1..=outcome.len() {
+            reported.set_bit(qubit, self.read_out(qubit, outcome.bit(qubit), rng));
+        }
+
+        reported
+    }
+
+    /// Parses a noise model from its JSON description
+    pub fn from_json(json: &str) -> Result<NoiseModel, QuriustError> {
+        let description: NoiseModelDescription = serde_json::from_str(json)
+            .map_err(|error| QuriustError::InvalidNoiseModel { message: error.to_string() })?;
+
+        let mut model = NoiseModel::new();
+        for gate_error in description.gate_errors {
+            let channel = gate_error.channel.build()?;
+            match gate_error.qubits {
+                Some(qubits) => model.try_add_gate_error_on(&gate_error.gate, &qubits, channel)?,
+                None => model.try_add_gate_error(&gate_error.gate, channel)?,
+            };
+        }
+        for readout_error in description.readout_errors {
+            let error = readout_error.error;
+            if !(0.0..=1.0).contains(&error.prob_1_given_0) || !(0.0..=1.0).contains(&error.prob_0_given_1) {
+                return Err(invalid_model("readout error probabilities must be between 0 and 1"));
+            }
+            match readout_error.qubit {
+                Some(qubit) => model.add_readout_error(qubit, error),
+                None => model.add_all_qubit_readout_error(error),
+            };
+        }
+
+        Ok(model)
+    }
+
+    /// Reads a noise model from a JSON calibration file, see [`NoiseModel::from_json`]
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<NoiseModel, QuriustError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|error| QuriustError::InvalidNoiseModel { message: error.to_string() })?;
+
+        NoiseModel::from_json(&json)
+    }
+}
+
+/// JSON layout of a noise model
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseModelDescription {
+    #[serde(default)]
+    gate_errors: Vec<GateErrorDescription>,
+    #[serde(default)]
+    readout_errors: Vec<ReadoutErrorDescription>,
+}
+
+/// JSON layout of a gate error
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GateErrorDescription {
+    gate: String,
+    qubits: Option<Vec<usize>>,
+    channel: ChannelDescription,
+}
+
+/// JSON layout of a readout error, for one qubit or for all of them
+#[derive(Deserialize)]
+struct ReadoutErrorDescription {
+    qubit: Option<usize>,
+    #[serde(flatten)]
+    error: ReadoutError,
+}
+
+/// JSON layout of a channel, tagged by its type
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ChannelDescription {
+    Depolarizing { p: f64 },
+    TwoQubitDepolarizing { p: f64 },
+    BitFlip { p: f64 },
+    PhaseFlip { p: f64 },
+    AmplitudeDamping { gamma: f64 },
+    PhaseDamping { lambda: f64 },
+    ThermalRelaxation { t1: f64, t2: f64, time: f64 },
+    Kraus { operators: Vec<Vec<Vec<[f64; 2]>>> },
+}
+
+impl ChannelDescription {
+    /// Builds the described channel, checking its parameters instead of panicking on them
+    fn build(self) -> Result<KrausChannel, QuriustError> {
+        let probability = |p: f64| if (0.0..=1.0).contains(&p) { Ok(p) } else { Err(invalid_model("channel parameters must be between 0 and 1")) };
+
+        match self {
+            ChannelDescription::Depolarizing { p } => Ok(KrausChannel::depolarizing(probability(p)?)),
+            ChannelDescription::TwoQubitDepolarizing { p } => Ok(KrausChannel::two_qubit_depolarizing(probability(p)?)),
+            ChannelDescription::BitFlip { p } => Ok(KrausChannel::bit_flip(probability(p)?)),
+            ChannelDescription::PhaseFlip { p } => Ok(KrausChannel::phase_flip(probability(p)?)),
+            ChannelDescription::AmplitudeDamping { gamma } => Ok(KrausChannel::amplitude_damping(probability(gamma)?)),
+            ChannelDescription::PhaseDamping { lambda } => Ok(KrausChannel::phase_damping(probability(lambda)?)),
+            ChannelDescription::ThermalRelaxation { t1, t2, time } => {
+                if !(t1 > 0.0 && t2 > 0.0 && time >= 0.0 && t2 <= 2.0 * t1) {
+                    return Err(invalid_model("thermal relaxation needs positive T1 and T2 with T2 <= 2·T1, and a non-negative time"));
+                }
+                Ok(KrausChannel::thermal_relaxation(t1, t2, time))
+            }
+            ChannelDescription::Kraus { operators } => {
+                let matrices = operators.into_iter().map(|rows| {
+                    let dimension = rows.len();
+                    if rows.iter().any(|row| row.len() != dimension) {
+                        return Err(invalid_model("Kraus operators must be square matrices"));
+                    }
+                    Ok(DMatrix::from_fn(dimension, dimension, |row, col| Complex::new(rows[row][col][0], rows[row][col][1])))
+                }).collect::<Result<Vec<_>, _>>()?;
+
+                KrausChannel::new(matrices)
+            }
+        }
+    }
+}
+
+/// Builds the error reported for an invalid noise model description
+fn invalid_model(message: &str) -> QuriustError {
+    QuriustError::InvalidNoiseModel { message: message.to_string() }
+}
+
+/// Panics if the parameter of a channel or readout error is not a probability
+fn assert_probability(p: f64) {
+    assert!((0.0..=1.0).contains(&p), "the probability {} must be between 0 and 1", p);
+}
+
+
+#[test]
+fn channels_are_trace_preserving_test() {
+    let channels = [
+        KrausChannel::depolarizing(0.3), KrausChannel::two_qubit_depolarizing(0.2), KrausChannel::bit_flip(0.1),
+        KrausChannel::phase_flip(0.7), KrausChannel::amplitude_damping(0.25), KrausChannel::phase_damping(0.4),
+        KrausChannel::thermal_relaxation(50.0, 70.0, 10.0), KrausChannel::thermal_relaxation(50.0, 100.0, 10.0),
+    ];
+
+    for channel in channels.iter() {
+        assert!(channel.completeness_deviation() < 1e-12);
+        assert_eq!(KrausChannel::new(channel.operators().to_vec()).as_ref(), Ok(channel));
+    }
+    assert_eq!(channels[1].num_qubits(), 2);
+    assert_eq!(channels[1].operators().len(), 16);
+}
+
+#[test]
+fn user_channel_validation_test() {
+    let half = Complex::new(0.5_f64.sqrt(), 0.0);
+    assert!(KrausChannel::new(vec![gates::identity() * half, gates::pauli_x() * half]).is_ok());
+
+    assert!(matches!(KrausChannel::new(vec![gates::identity() * half]), Err(QuriustError::InvalidChannel { .. })));
+    assert_eq!(KrausChannel::new(vec![]), Err(QuriustError::DimensionMismatch { expected: 1, found: 0 }));
+    assert_eq!(KrausChannel::new(vec![DMatrix::identity(3, 3)]), Err(QuriustError::DimensionMismatch { expected: 4, found: 3 }));
+    assert_eq!(KrausChannel::new(vec![gates::identity(), gates::cnot()]), Err(QuriustError::DimensionMismatch { expected: 2, found: 4 }));
+
+    let channel = KrausChannel::bit_flip(0.1);
+    assert_eq!(channel.placements(&[1, 3]), Ok(vec![&[1][..], &[3][..]]));
+    assert!(KrausChannel::two_qubit_depolarizing(0.1).placements(&[1]).is_err());
+}
+
+#[test]
+fn noise_model_test() {
+    let mut model = NoiseModel::new();
+    assert!(model.is_ideal());
+    model.add_gate_error("h", KrausChannel::bit_flip(0.1))
+        .add_gate_error_on("cnot", &[1, 2], KrausChannel::two_qubit_depolarizing(0.2))
+        .add_readout_error(2, ReadoutError::new(0.0, 1.0));
+
+    assert_eq!(model.gate_errors(&Gate::H, &[3]), vec![&KrausChannel::bit_flip(0.1)]);
+    assert_eq!(model.gate_errors(&Gate::Cnot, &[1, 2]).len(), 1);
+    assert!(model.gate_errors(&Gate::Cnot, &[2, 1]).is_empty());
+    assert!(model.gate_errors(&Gate::X, &[1]).is_empty());
+
+    let mut rng = rand::thread_rng();
+    assert_eq!(model.read_out_index(0b11, 2, &mut rng), 0b01);
+    assert_eq!(model.read_out(1, 1, &mut rng), 1);
+    assert!(model.readout_error(3).is_none());
+
+    // Channels that cannot fit their gate are rejected when the model is built
+    let mismatch = Err(QuriustError::DimensionMismatch { expected: 2, found: 1 });
+    assert_eq!(model.try_add_gate_error("h", KrausChannel::two_qubit_depolarizing(0.1)).map(|_| ()), mismatch);
+    assert_eq!(model.try_add_gate_error_on("cnot", &[1], KrausChannel::two_qubit_depolarizing(0.1)).map(|_| ()), mismatch);
+    assert_eq!(model.try_add_gate_error_on("cnot", &[1], KrausChannel::bit_flip(0.1)).map(|_| ()), mismatch);
+    assert!(model.try_add_gate_error("unitary", KrausChannel::two_qubit_depolarizing(0.1)).is_ok());
+    assert_eq!(model.gate_errors(&Gate::H, &[3]).len(), 1);
+}
+
+#[test]
+fn noise_model_json_test() {
+    let json = r#"{
+        "gate_errors": [
+            { "gate": "h", "channel": { "type": "depolarizing", "p": 0.001 } },
+            { "gate": "cnot", "qubits": [1, 2], "channel": { "type": "two_qubit_depolarizing", "p": 0.01 } },
+            { "gate": "x", "channel": { "type": "thermal_relaxation", "t1": 50.0, "t2": 70.0, "time": 0.05 } },
+            { "gate": "y", "channel": { "type": "kraus", "operators": [[[[0.0, 0.0], [1.0, 0.0]], [[1.0, 0.0], [0.0, 0.0]]]] } }
+        ],
+        "readout_errors": [
+            { "prob_1_given_0": 0.01, "prob_0_given_1": 0.03 },
+            { "qubit": 2, "prob_1_given_0": 0.02, "prob_0_given_1": 0.05 }
+        ]
+    }"#;
+
+    let model = NoiseModel::from_json(json).unwrap();
+    let mut expected = NoiseModel::new();
+    expected.add_gate_error("h", KrausChannel::depolarizing(0.001))
+        .add_gate_error_on("cnot", &[1, 2], KrausChannel::two_qubit_depolarizing(0.01))
+        .add_gate_error("x", KrausChannel::thermal_relaxation(50.0, 70.0, 0.05))
+        .add_gate_error("y", KrausChannel::new(vec![gates::pauli_x()]).unwrap())
+        .add_all_qubit_readout_error(ReadoutError::new(0.01, 0.03))
+        .add_readout_error(2, ReadoutError::new(0.02, 0.05));
+    assert_eq!(model, expected);
+
+    for invalid in [
+        r#"{ "gate_errors": [{ "gate": "h", "channel": { "type": "bit_flip", "p": 1.5 } }] }"#,
+        r#"{ "gate_errors": [{ "gate": "h", "channel": { "type": "unknown" } }] }"#,
+        r#"{ "gate_errors": [{ "gate": "h", "channel": { "type": "kraus", "operators": [[[[0.5, 0.0], [0.0, 0.0]], [[0.0, 0.0], [0.5, 0.0]]]] } }] }"#,
+        r#"{ "readout_errors": [{ "prob_1_given_0": -0.1, "prob_0_given_1": 0.0 }] }"#,
+        r#"{ "gate_errors": [{ "gate": "h", "channel": { "type": "two_qubit_depolarizing", "p": 0.1 } }] }"#,
+        r#"{ "gate_errors": [{ "gate": "cnot", "qubits": [1, 2, 3], "channel": { "type": "depolarizing", "p": 0.1 } }] }"#,
+        r#"{ "gate_errors": [{ "gate": "x", "channel": { "type": "thermal_relaxation", "t1": 10.0, "t2": 30.0, "time": 1.0 } }] }"#,
+        "not json",
+    ] {
+        assert!(NoiseModel::from_json(invalid).is_err());
+    }
+    assert!(matches!(NoiseModel::from_json_file("/nonexistent/calibration.json"), Err(QuriustError::InvalidNoiseModel { .. })));
+}
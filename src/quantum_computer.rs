@@ -1,39 +1,209 @@
+use super::backend::Backend;
+use super::circuit::Circuit;
+use super::error::{expect_ok, QuriustError};
+use super::gates::Gate;
+use super::noise::{KrausChannel, NoiseModel};
 use super::registers::*;
+use super::state::State;
+use num_complex::Complex;
+use nalgebra::DMatrix;
+use rand::RngCore;
 
 /// Represents a quantum computer, i.e. a wrap up of a register object.
 /// This is intended to be more beginner friendly and more accessible
 #[derive(Clone, Debug)]
-pub struct QuantumComputer{
-    q_register: QuantumRegister,
+pub struct QuantumComputer<B: Backend = State> {
+    q_register: QuantumRegister<B>,
     c_register: ClassicalRegister
 }
 
 impl QuantumComputer {
     /// Creates a new quantum register initialized to |0>
     pub fn new(n_qubit: usize) -> QuantumComputer{
+        QuantumComputer::from_backend(State::zero_state(n_qubit))
+    }
+}
+
+impl<B: Backend> QuantumComputer<B> {
+    /// Creates a quantum computer simulated by the given backend, see [`QuantumRegister::from_backend`]
+    pub fn from_backend(backend: B) -> QuantumComputer<B> {
+        let q_register = QuantumRegister::from_backend(backend);
         QuantumComputer {
-            q_register: QuantumRegister::init(n_qubit),
-            c_register: ClassicalRegister::zeros(n_qubit),
+            c_register: ClassicalRegister::zeros(q_register.get_qubit_count()),
+            q_register,
         }
     }
 
-    /// Measure the quantum register, which collapses in the classical one
-    pub fn measure(mut self) {
-        let len = self.q_register.len();
-        let mut cum = 0.0;
-        let rand_num: f64 = rand::random();
+    /// Seeds the random number generator used by measurements and sampling, see [`QuantumRegister::with_seed`]
+    pub fn with_seed(mut self, seed: u64) -> QuantumComputer<B> {
+        self.q_register = self.q_register.with_seed(seed);
+        self
+    }
 
-        for (val, coefficient) in self.q_register.prob_amplitudes.amplitudes().iter().enumerate() {
-            let scaled_prob = coefficient.norm_sqr();
-            cum += scaled_prob;
+    /// Draws the randomness of measurements and sampling from the given generator, see [`QuantumRegister::with_rng`]
+    pub fn with_rng<R: RngCore + Clone + Send + Sync + 'static>(mut self, rng: R) -> QuantumComputer<B> {
+        self.q_register = self.q_register.with_rng(rng);
+        self
+    }
 
-            if rand_num <= cum {
-                self.c_register = ClassicalRegister::from_value(len, val as u32);
-            }
+    /// Attaches a noise model to the computer, see [`QuantumRegister::with_noise_model`]
+    pub fn with_noise_model(mut self, noise_model: NoiseModel) -> QuantumComputer<B> {
+        self.q_register.set_noise_model(noise_model);
+        self
+    }
+
+    /// Replaces the noise model of the computer; an empty `NoiseModel` makes it ideal again
+    pub fn set_noise_model(&mut self, noise_model: NoiseModel) {
+        self.q_register.set_noise_model(noise_model);
+    }
+
+    /// Returns the noise model of the computer
+    pub fn noise_model(&self) -> &NoiseModel {
+        self.q_register.noise_model()
+    }
+
+    /// Measures the quantum register, which collapses in the classical one.
+    ///
+    /// The outcome is stored in the classical register of the computer and returned.
+    pub fn measure(&mut self) -> ClassicalRegister {
+        expect_ok(self.try_measure())
+    }
+
+    /// Fallible version of [`QuantumComputer::measure`], returning an error if the register was already measured.
+    pub fn try_measure(&mut self) -> Result<ClassicalRegister, QuriustError> {
+        self.c_register = self.q_register.try_measure()?;
+        Ok(self.c_register.clone())
+    }
+
+    /// Returns the classical register, holding the outcome of the last measurement
+    pub fn classical_register(&self) -> &ClassicalRegister {
+        &self.c_register
+    }
+
+    /// Returns the bits of the classical register, most significant first
+    pub fn classical_bits(&self) -> Vec<usize> {
+        self.c_register.bits()
+    }
+
+    /// Returns the value of the classical register as an integer
+    pub fn classical_value(&self) -> u32 {
+        self.c_register.value()
+    }
+
+    /// Returns the quantum register of the computer
+    pub fn quantum_register(&self) -> &QuantumRegister<B> {
+        &self.q_register
+    }
+
+    /// Returns true if the quantum register has been measured since the last reset
+    pub fn is_measured(&self) -> bool {
+        self.q_register.measured
+    }
+
+    /// Resets the quantum register to |0> and clears the classical register, so the computer can run again
+    pub fn reset(&mut self) {
+        self.q_register.reset();
+        self.c_register = ClassicalRegister::zeros(self.c_register.len());
+    }
+
+    /// Measures a qubit and stores the outcome in a bit of the classical register of the computer.
+    ///
+    /// The other qubits stay usable, and later gates can be conditioned on the bit with `apply_if`.
+    pub fn measure_into(&mut self, qubit: usize, bit: usize) -> bool {
+        expect_ok(self.try_measure_into(qubit, bit))
+    }
+
+    /// Fallible version of [`QuantumComputer::measure_into`], returning an error instead of panicking.
+    pub fn try_measure_into(&mut self, qubit: usize, bit: usize) -> Result<bool, QuriustError> {
+        self.q_register.try_measure_into(qubit, &mut self.c_register, bit)
+    }
+
+    /// Resets a qubit to |0>, leaving the other qubits usable
+    pub fn reset_qubit(&mut self, qubit: usize) {
+        self.q_register.reset_qubit(qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::reset_qubit`], returning an error instead of panicking.
+    pub fn try_reset_qubit(&mut self, qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_reset_qubit(qubit)
+    }
+
+    /// Applies a gate only if the condition holds on the classical register of the computer.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the condition held and the gate was applied.
+    pub fn apply_if(&mut self, condition: &Condition, gate: &Gate, qubits: &[usize]) -> bool {
+        expect_ok(self.try_apply_if(condition, gate, qubits))
+    }
+
+    /// Fallible version of [`QuantumComputer::apply_if`], returning an error instead of panicking.
+    pub fn try_apply_if(&mut self, condition: &Condition, gate: &Gate, qubits: &[usize]) -> Result<bool, QuriustError> {
+        self.q_register.try_apply_if(condition, &self.c_register, gate, qubits)
+    }
 
+    /// Applies a noise channel to the given qubits, see [`QuantumRegister::apply_channel`]
+    pub fn apply_channel(&mut self, channel: &KrausChannel, qubits: &[usize]) {
+        self.q_register.apply_channel(channel, qubits);
+    }
+
+    /// Fallible version of [`QuantumComputer::apply_channel`], returning an error instead of panicking.
+    pub fn try_apply_channel(&mut self, channel: &KrausChannel, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.q_register.try_apply_channel(channel, qubits)
+    }
+
+    /// Executes a circuit on the computer, reading and writing its classical register
+    pub fn execute(&mut self, circuit: &Circuit) -> Result<ClassicalRegister, QuriustError> {
+        circuit.apply_with_classical(&mut self.q_register, &mut self.c_register)?;
+        Ok(self.c_register.clone())
+    }
+
+    /// Runs a circuit from scratch the given number of times and returns the histogram of the outcomes.
+    ///
+    /// Unlike [`Circuit::run`], every shot resets the computer and simulates the whole circuit again, and
+    /// circuits without a final measurement are measured at the end of each shot.
+    pub fn run(&mut self, circuit: &Circuit, shots: usize) -> Result<Counts, QuriustError> {
+        let qubit_count = self.q_register.get_qubit_count();
+        if circuit.num_qubits() != qubit_count {
+            return Err(QuriustError::DimensionMismatch { expected: qubit_count, found: circuit.num_qubits() });
         }
 
-        self.c_register = ClassicalRegister::from_value(len, 0)
+        let mut counts = Counts::new();
+        for _ in 0..shots {
+            self.reset();
+            self.execute(circuit)?;
+            if !circuit.has_measurements() {
+                self.try_measure()?;
+            }
+            *counts.entry(self.c_register.clone()).or_insert(0) += 1;
+        }
+
+        Ok(counts)
+    }
+
+    /// Samples the measurement outcome of the quantum register many times, without collapsing it
+    pub fn sample(&mut self, shots: usize) -> Counts {
+        self.q_register.sample(shots)
+    }
+
+    /// Fallible version of [`QuantumComputer::sample`], returning an error instead of panicking.
+    pub fn try_sample(&mut self, shots: usize) -> Result<Counts, QuriustError> {
+        self.q_register.try_sample(shots)
+    }
+
+    /// Applies the given gate to the specified ordered list of qubits.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate` - The gate to apply.
+    /// * `qubits` - The indices of the qubits the gate acts on, in the order of its matrix basis.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        self.q_register.apply(gate, qubits);
+    }
+
+    /// Fallible version of [`QuantumComputer::apply`], returning an error instead of panicking.
+    pub fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.q_register.try_apply(gate, qubits)
     }
 
     /// Applies the Pauli-X gate (also known as the NOT gate) to the specified target qubit.
@@ -50,6 +220,11 @@ impl QuantumComputer {
         self.q_register.x(target_qubit);
     }
 
+    /// Fallible version of [`QuantumComputer::x`], returning an error instead of panicking.
+    pub fn try_x(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_x(target_qubit)
+    }
+
     /// Applies the Pauli-Y gate to the specified target qubit.
     ///
     /// The Pauli-Y gate introduces a phase flip (rotation by π around the Y-axis) if the qubit is in |1⟩ state.
@@ -61,7 +236,13 @@ impl QuantumComputer {
     ///
     /// * `target_qubit` - The index of the target qubit to which the gate is applied.
     pub fn y(&mut self, target_qubit: usize) {
-        self.q_register.y(target_qubit);    }
+        self.q_register.y(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::y`], returning an error instead of panicking.
+    pub fn try_y(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_y(target_qubit)
+    }
     
     /// Applies the Pauli-Z gate to the specified target qubit.
     ///
@@ -76,6 +257,11 @@ impl QuantumComputer {
     pub fn z(&mut self, target_qubit: usize) {
         self.q_register.z(target_qubit); 
     }
+
+    /// Fallible version of [`QuantumComputer::z`], returning an error instead of panicking.
+    pub fn try_z(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_z(target_qubit)
+    }
     
     /// Applies the Hadamard gate to the specified target qubit.
     ///
@@ -88,8 +274,227 @@ impl QuantumComputer {
     ///
     /// * `target_qubit` - The index of the target qubit to which the gate is applied.
     pub fn h(&mut self, target_qubit: usize) {
-        self.q_register.h(target_qubit);    }
+        self.q_register.h(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::h`], returning an error instead of panicking.
+    pub fn try_h(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_h(target_qubit)
+    }
     
+    /// Applies the identity gate to the specified target qubit.
+    ///
+    /// The identity gate leaves the qubit unchanged.
+    /// Matrix representation:
+    ///     | 1 0 |
+    ///     | 0 1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn id(&mut self, target_qubit: usize) {
+        self.q_register.id(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::id`], returning an error instead of panicking.
+    pub fn try_id(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_id(target_qubit)
+    }
+
+    /// Applies the S gate to the specified target qubit.
+    ///
+    /// The S gate multiplies the |1⟩ component by i, a quarter turn around the Z-axis.
+    /// Matrix representation:
+    ///     | 1 0 |
+    ///     | 0 i |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn s(&mut self, target_qubit: usize) {
+        self.q_register.s(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::s`], returning an error instead of panicking.
+    pub fn try_s(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_s(target_qubit)
+    }
+
+    /// Applies the S† gate to the specified target qubit.
+    ///
+    /// The S† gate is the inverse of the S gate and multiplies the |1⟩ component by -i.
+    /// Matrix representation:
+    ///     | 1  0 |
+    ///     | 0 -i |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn sdg(&mut self, target_qubit: usize) {
+        self.q_register.sdg(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::sdg`], returning an error instead of panicking.
+    pub fn try_sdg(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_sdg(target_qubit)
+    }
+
+    /// Applies the T gate to the specified target qubit.
+    ///
+    /// The T gate multiplies the |1⟩ component by e^(iπ/4), an eighth turn around the Z-axis.
+    /// Matrix representation:
+    ///     | 1 0          |
+    ///     | 0 e^(iπ/4)   |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn t(&mut self, target_qubit: usize) {
+        self.q_register.t(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::t`], returning an error instead of panicking.
+    pub fn try_t(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_t(target_qubit)
+    }
+
+    /// Applies the T† gate to the specified target qubit.
+    ///
+    /// The T† gate is the inverse of the T gate and multiplies the |1⟩ component by e^(-iπ/4).
+    /// Matrix representation:
+    ///     | 1 0          |
+    ///     | 0 e^(-iπ/4)  |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn tdg(&mut self, target_qubit: usize) {
+        self.q_register.tdg(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::tdg`], returning an error instead of panicking.
+    pub fn try_tdg(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_tdg(target_qubit)
+    }
+
+    /// Applies the √X gate to the specified target qubit.
+    ///
+    /// Applying the √X gate twice is equivalent to a Pauli-X gate.
+    /// Matrix representation:
+    ///     | (1+i)/2 (1-i)/2 |
+    ///     | (1-i)/2 (1+i)/2 |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn sx(&mut self, target_qubit: usize) {
+        self.q_register.sx(target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::sx`], returning an error instead of panicking.
+    pub fn try_sx(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_sx(target_qubit)
+    }
+
+    /// Applies the phase gate P(λ) to the specified target qubit.
+    ///
+    /// The phase gate multiplies the |1⟩ component by e^(iλ).
+    /// Matrix representation:
+    ///     | 1 0      |
+    ///     | 0 e^(iλ) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `lambda` - The phase angle λ.
+    pub fn p(&mut self, target_qubit: usize, lambda: f64) {
+        self.q_register.p(target_qubit, lambda);
+    }
+
+    /// Fallible version of [`QuantumComputer::p`], returning an error instead of panicking.
+    pub fn try_p(&mut self, target_qubit: usize, lambda: f64) -> Result<(), QuriustError> {
+        self.q_register.try_p(target_qubit, lambda)
+    }
+
+    /// Applies a rotation by θ around the X-axis to the specified target qubit.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2)    -i sin(θ/2) |
+    ///     | -i sin(θ/2)  cos(θ/2)   |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    pub fn rx(&mut self, target_qubit: usize, theta: f64) {
+        self.q_register.rx(target_qubit, theta);
+    }
+
+    /// Fallible version of [`QuantumComputer::rx`], returning an error instead of panicking.
+    pub fn try_rx(&mut self, target_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.q_register.try_rx(target_qubit, theta)
+    }
+
+    /// Applies a rotation by θ around the Y-axis to the specified target qubit.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2) -sin(θ/2) |
+    ///     | sin(θ/2)  cos(θ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    pub fn ry(&mut self, target_qubit: usize, theta: f64) {
+        self.q_register.ry(target_qubit, theta);
+    }
+
+    /// Fallible version of [`QuantumComputer::ry`], returning an error instead of panicking.
+    pub fn try_ry(&mut self, target_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.q_register.try_ry(target_qubit, theta)
+    }
+
+    /// Applies a rotation by θ around the Z-axis to the specified target qubit.
+    ///
+    /// Matrix representation:
+    ///     | e^(-iθ/2) 0        |
+    ///     | 0         e^(iθ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    pub fn rz(&mut self, target_qubit: usize, theta: f64) {
+        self.q_register.rz(target_qubit, theta);
+    }
+
+    /// Fallible version of [`QuantumComputer::rz`], returning an error instead of panicking.
+    pub fn try_rz(&mut self, target_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.q_register.try_rz(target_qubit, theta)
+    }
+
+    /// Applies the general single-qubit gate U3(θ, φ, λ) to the specified target qubit.
+    ///
+    /// Any single-qubit unitary can be written as U3 up to a global phase.
+    /// Matrix representation:
+    ///     | cos(θ/2)         -e^(iλ) sin(θ/2)     |
+    ///     | e^(iφ) sin(θ/2)   e^(i(φ+λ)) cos(θ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    /// * `phi` - The phase angle φ.
+    /// * `lambda` - The phase angle λ.
+    pub fn u3(&mut self, target_qubit: usize, theta: f64, phi: f64, lambda: f64) {
+        self.q_register.u3(target_qubit, theta, phi, lambda);
+    }
+
+    /// Fallible version of [`QuantumComputer::u3`], returning an error instead of panicking.
+    pub fn try_u3(&mut self, target_qubit: usize, theta: f64, phi: f64, lambda: f64) -> Result<(), QuriustError> {
+        self.q_register.try_u3(target_qubit, theta, phi, lambda)
+    }
+
     /// Applies the Controlled-NOT (CNOT) gate to the specified control and target qubits.
     ///
     /// The CNOT gate flips the target qubit if and only if the control qubit is in the |1⟩ state.
@@ -106,4 +511,380 @@ impl QuantumComputer {
     pub fn cnot(&mut self, control_qubit: usize, target_qubit: usize){
         self.q_register.cnot(control_qubit, target_qubit);
     }
+
+    /// Fallible version of [`QuantumComputer::cnot`], returning an error instead of panicking.
+    pub fn try_cnot(&mut self, control_qubit: usize, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_cnot(control_qubit, target_qubit)
+    }
+
+    /// Applies the Controlled-Z (CZ) gate to the specified control and target qubits.
+    ///
+    /// The CZ gate flips the phase of the |11⟩ component, so control and target are interchangeable.
+    /// Matrix representation:
+    ///     | 1 0 0  0 |
+    ///     | 0 1 0  0 |
+    ///     | 0 0 1  0 |
+    ///     | 0 0 0 -1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `control_qubit` - The index of the control qubit.
+    /// * `target_qubit` - The index of the target qubit.
+    pub fn cz(&mut self, control_qubit: usize, target_qubit: usize) {
+        self.q_register.cz(control_qubit, target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::cz`], returning an error instead of panicking.
+    pub fn try_cz(&mut self, control_qubit: usize, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_cz(control_qubit, target_qubit)
+    }
+
+    /// Applies the controlled-phase gate CPhase(φ) to the specified control and target qubits.
+    ///
+    /// The controlled-phase gate multiplies the |11⟩ component by e^(iφ).
+    /// Matrix representation:
+    ///     | 1 0 0 0      |
+    ///     | 0 1 0 0      |
+    ///     | 0 0 1 0      |
+    ///     | 0 0 0 e^(iφ) |
+    ///
+    /// # Arguments
+    ///
+    /// * `control_qubit` - The index of the control qubit.
+    /// * `target_qubit` - The index of the target qubit.
+    /// * `phi` - The phase angle φ.
+    pub fn cphase(&mut self, control_qubit: usize, target_qubit: usize, phi: f64) {
+        self.q_register.cphase(control_qubit, target_qubit, phi);
+    }
+
+    /// Fallible version of [`QuantumComputer::cphase`], returning an error instead of panicking.
+    pub fn try_cphase(&mut self, control_qubit: usize, target_qubit: usize, phi: f64) -> Result<(), QuriustError> {
+        self.q_register.try_cphase(control_qubit, target_qubit, phi)
+    }
+
+    /// Applies the SWAP gate to the specified pair of qubits.
+    ///
+    /// The SWAP gate exchanges the states of the two qubits.
+    /// Matrix representation:
+    ///     | 1 0 0 0 |
+    ///     | 0 0 1 0 |
+    ///     | 0 1 0 0 |
+    ///     | 0 0 0 1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    pub fn swap(&mut self, first_qubit: usize, second_qubit: usize) {
+        self.q_register.swap(first_qubit, second_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::swap`], returning an error instead of panicking.
+    pub fn try_swap(&mut self, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_swap(first_qubit, second_qubit)
+    }
+
+    /// Applies the √SWAP gate to the specified pair of qubits.
+    ///
+    /// Applying the √SWAP gate twice is equivalent to a SWAP gate.
+    /// Matrix representation:
+    ///     | 1 0       0       0 |
+    ///     | 0 (1+i)/2 (1-i)/2 0 |
+    ///     | 0 (1-i)/2 (1+i)/2 0 |
+    ///     | 0 0       0       1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    pub fn sqrt_swap(&mut self, first_qubit: usize, second_qubit: usize) {
+        self.q_register.sqrt_swap(first_qubit, second_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::sqrt_swap`], returning an error instead of panicking.
+    pub fn try_sqrt_swap(&mut self, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_sqrt_swap(first_qubit, second_qubit)
+    }
+
+    /// Applies the iSWAP gate to the specified pair of qubits.
+    ///
+    /// The iSWAP gate exchanges the states of the two qubits and multiplies |01⟩ and |10⟩ by i.
+    /// Matrix representation:
+    ///     | 1 0 0 0 |
+    ///     | 0 0 i 0 |
+    ///     | 0 i 0 0 |
+    ///     | 0 0 0 1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    pub fn iswap(&mut self, first_qubit: usize, second_qubit: usize) {
+        self.q_register.iswap(first_qubit, second_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::iswap`], returning an error instead of panicking.
+    pub fn try_iswap(&mut self, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_iswap(first_qubit, second_qubit)
+    }
+
+    /// Applies the Ising XX coupling RXX(θ) = exp(-iθ X⊗X / 2) to the specified pair of qubits.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2)     0            0            -i sin(θ/2) |
+    ///     | 0            cos(θ/2)     -i sin(θ/2)  0           |
+    ///     | 0            -i sin(θ/2)  cos(θ/2)     0           |
+    ///     | -i sin(θ/2)  0            0            cos(θ/2)    |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    pub fn rxx(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        self.q_register.rxx(first_qubit, second_qubit, theta);
+    }
+
+    /// Fallible version of [`QuantumComputer::rxx`], returning an error instead of panicking.
+    pub fn try_rxx(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.q_register.try_rxx(first_qubit, second_qubit, theta)
+    }
+
+    /// Applies the Ising YY coupling RYY(θ) = exp(-iθ Y⊗Y / 2) to the specified pair of qubits.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2)    0            0            i sin(θ/2) |
+    ///     | 0           cos(θ/2)     -i sin(θ/2)  0          |
+    ///     | 0           -i sin(θ/2)  cos(θ/2)     0          |
+    ///     | i sin(θ/2)  0            0            cos(θ/2)   |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    pub fn ryy(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        self.q_register.ryy(first_qubit, second_qubit, theta);
+    }
+
+    /// Fallible version of [`QuantumComputer::ryy`], returning an error instead of panicking.
+    pub fn try_ryy(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.q_register.try_ryy(first_qubit, second_qubit, theta)
+    }
+
+    /// Applies the Ising ZZ coupling RZZ(θ) = exp(-iθ Z⊗Z / 2) to the specified pair of qubits.
+    ///
+    /// Matrix representation:
+    ///     | e^(-iθ/2) 0        0        0         |
+    ///     | 0         e^(iθ/2) 0        0         |
+    ///     | 0         0        e^(iθ/2) 0         |
+    ///     | 0         0        0        e^(-iθ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    pub fn rzz(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        self.q_register.rzz(first_qubit, second_qubit, theta);
+    }
+
+    /// Fallible version of [`QuantumComputer::rzz`], returning an error instead of panicking.
+    pub fn try_rzz(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.q_register.try_rzz(first_qubit, second_qubit, theta)
+    }
+
+    /// Applies the fSim(θ, φ) gate to the specified pair of qubits.
+    ///
+    /// The fSim gate rotates |01⟩ and |10⟩ into each other by θ and multiplies |11⟩ by e^(-iφ).
+    /// Matrix representation:
+    ///     | 1 0          0          0       |
+    ///     | 0 cos(θ)     -i sin(θ)  0       |
+    ///     | 0 -i sin(θ)  cos(θ)     0       |
+    ///     | 0 0          0          e^(-iφ) |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    /// * `phi` - The phase angle φ.
+    pub fn fsim(&mut self, first_qubit: usize, second_qubit: usize, theta: f64, phi: f64) {
+        self.q_register.fsim(first_qubit, second_qubit, theta, phi);
+    }
+
+    /// Fallible version of [`QuantumComputer::fsim`], returning an error instead of panicking.
+    pub fn try_fsim(&mut self, first_qubit: usize, second_qubit: usize, theta: f64, phi: f64) -> Result<(), QuriustError> {
+        self.q_register.try_fsim(first_qubit, second_qubit, theta, phi)
+    }
+
+    /// Applies the Toffoli (CCNOT) gate to the specified control and target qubits.
+    ///
+    /// The Toffoli gate flips the target qubit if and only if both control qubits are in the |1⟩ state.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_control` - The index of the first control qubit.
+    /// * `second_control` - The index of the second control qubit.
+    /// * `target_qubit` - The index of the target qubit.
+    pub fn ccx(&mut self, first_control: usize, second_control: usize, target_qubit: usize) {
+        self.q_register.ccx(first_control, second_control, target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::ccx`], returning an error instead of panicking.
+    pub fn try_ccx(&mut self, first_control: usize, second_control: usize, target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_ccx(first_control, second_control, target_qubit)
+    }
+
+    /// Applies the Fredkin (controlled-SWAP) gate to the specified control qubit and pair of target qubits.
+    ///
+    /// The Fredkin gate exchanges the states of the two target qubits if and only if the control qubit is in the |1⟩ state.
+    ///
+    /// # Arguments
+    ///
+    /// * `control_qubit` - The index of the control qubit.
+    /// * `first_qubit` - The index of the first target qubit.
+    /// * `second_qubit` - The index of the second target qubit.
+    pub fn cswap(&mut self, control_qubit: usize, first_qubit: usize, second_qubit: usize) {
+        self.q_register.cswap(control_qubit, first_qubit, second_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::cswap`], returning an error instead of panicking.
+    pub fn try_cswap(&mut self, control_qubit: usize, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_cswap(control_qubit, first_qubit, second_qubit)
+    }
+
+    /// Applies an arbitrary single-qubit gate to the target qubit, conditioned on a set of control qubits.
+    ///
+    /// The gate acts if and only if every control qubit is in the |1⟩ state, which makes it possible to write
+    /// multi-controlled oracles in a single call.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate` - The 2x2 matrix of the gate to apply.
+    /// * `controls` - The indices of the control qubits.
+    /// * `target_qubit` - The index of the target qubit.
+    pub fn controlled(&mut self, gate: DMatrix<Complex<f64>>, controls: &[usize], target_qubit: usize) {
+        self.q_register.controlled(gate, controls, target_qubit);
+    }
+
+    /// Fallible version of [`QuantumComputer::controlled`], returning an error instead of panicking.
+    pub fn try_controlled(&mut self, gate: DMatrix<Complex<f64>>, controls: &[usize], target_qubit: usize) -> Result<(), QuriustError> {
+        self.q_register.try_controlled(gate, controls, target_qubit)
+    }
+
+    /// Applies an arbitrary unitary to the specified ordered list of qubits.
+    ///
+    /// The matrix is written in the basis |q1 q2 ... qk⟩ of the given qubits, so the first qubit is the most
+    /// significant bit of the matrix index. The matrix must be 2^k x 2^k and unitary.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate` - The matrix of the gate to apply.
+    /// * `qubits` - The indices of the qubits the gate acts on.
+    pub fn unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        self.q_register.unitary(gate, qubits);
+    }
+
+    /// Fallible version of [`QuantumComputer::unitary`], returning an error instead of panicking.
+    pub fn try_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.q_register.try_unitary(gate, qubits)
+    }
+}
+
+#[test]
+fn test_measure_stores_outcome() {
+    let mut computer = QuantumComputer::new(3);
+    computer.x(1);
+    computer.x(3);
+
+    assert_eq!(computer.measure(), ClassicalRegister::new(vec![1, 0, 1]));
+    assert_eq!(computer.classical_bits(), vec![1, 0, 1]);
+    assert_eq!(computer.classical_value(), 5);
+    assert!(computer.is_measured());
+    assert_eq!(computer.try_measure(), Err(QuriustError::RegisterMeasured));
+
+    computer.reset();
+    assert!(!computer.is_measured());
+    assert_eq!(computer.classical_value(), 0);
+    computer.x(2);
+    assert_eq!(computer.measure().value(), 2);
+}
+
+#[test]
+fn test_bell_pair_statistics() {
+    let mut computer = QuantumComputer::new(2).with_seed(2024);
+    let mut counts = Counts::new();
+
+    for _ in 0..2000 {
+        computer.reset();
+        computer.h(1);
+        computer.cnot(1, 2);
+        *counts.entry(computer.measure()).or_insert(0) += 1;
+    }
+
+    let zeros = counts[&ClassicalRegister::zeros(2)];
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&ClassicalRegister::new(vec![1, 1])], 2000 - zeros);
+    assert!(zeros > 900 && zeros < 1100);
+
+    let mut circuit = Circuit::new(2);
+    circuit.h(1).cnot(1, 2);
+    let counts = computer.run(&circuit, 2000).unwrap();
+    let zeros = counts[&ClassicalRegister::zeros(2)];
+    assert_eq!(counts.len(), 2);
+    assert!(zeros > 900 && zeros < 1100);
+
+    // The same seed replays the same sequence of runs
+    let mut first = QuantumComputer::new(2).with_seed(7);
+    let mut second = QuantumComputer::new(2).with_seed(7);
+    assert_eq!(first.run(&circuit, 100), second.run(&circuit, 100));
+    assert!(matches!(first.run(&Circuit::new(3), 1), Err(QuriustError::DimensionMismatch { expected: 2, found: 3 })));
+}
+
+#[test]
+fn test_eager_teleportation() {
+    let theta: f64 = 0.9;
+    let mut ones = 0;
+
+    let mut computer = QuantumComputer::new(3).with_seed(99);
+    for _ in 0..2000 {
+        computer.reset();
+        computer.ry(1, theta);
+        computer.h(2);
+        computer.cnot(2, 3);
+        computer.cnot(1, 2);
+        computer.h(1);
+        computer.measure_into(1, 1);
+        computer.measure_into(2, 2);
+        computer.apply_if(&Condition::Bit { bit: 2, value: 1 }, &Gate::X, &[3]);
+        computer.apply_if(&Condition::Bit { bit: 1, value: 1 }, &Gate::Z, &[3]);
+        if computer.measure_into(3, 3) {
+            ones += 1;
+        }
+        assert!(!computer.is_measured());
+    }
+
+    let expected = 2000.0 * (theta / 2.0).sin().powi(2);
+    assert!((ones as f64 - expected).abs() < 100.0);
+
+    computer.reset_qubit(3);
+    assert!(!computer.measure_into(3, 3));
+    assert!(!computer.apply_if(&Condition::Equals(7), &Gate::X, &[1]));
+    assert_eq!(computer.try_measure_into(1, 4), Err(QuriustError::InvalidBit { bit: 4, bit_count: 3 }));
+}
+
+#[test]
+fn test_noisy_computer() {
+    let mut circuit = Circuit::new(2);
+    circuit.x(1).x(2);
+
+    let mut model = NoiseModel::new();
+    model.add_gate_error_on("x", &[2], KrausChannel::bit_flip(1.0));
+    let mut computer = QuantumComputer::new(2).with_noise_model(model);
+    assert_eq!(computer.run(&circuit, 10).unwrap()[&ClassicalRegister::new(vec![0, 1])], 10);
+
+    computer.set_noise_model(NoiseModel::new());
+    assert!(computer.noise_model().is_ideal());
+    assert_eq!(computer.run(&circuit, 10).unwrap()[&ClassicalRegister::new(vec![1, 1])], 10);
 }
@@ -1,8 +1,21 @@
+use super::backend::Backend;
+use super::error::{expect_ok, QuriustError};
+use super::gates::Gate;
+use super::noise::{KrausChannel, NoiseModel};
+#[cfg(test)]
+use super::noise::ReadoutError;
 use super::state::State;
 use num_complex::Complex;
+use nalgebra::DMatrix;
+use rand::rngs::StdRng;
+#[cfg(test)]
+use rand::rngs::mock::StepRng;
+use rand::{RngCore, SeedableRng};
+use std::collections::HashMap;
+use std::fmt;
 
 /// Represents a classical register
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct ClassicalRegister{
     bits: Vec<usize>
 }
@@ -19,11 +32,13 @@ impl ClassicalRegister{
     }
 
     /// Returns the length of the classical register
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.bits.len()
     } 
 
     /// Creates a classical register from the given value and width
+    #[allow(clippy::unnecessary_cast)]
     pub fn from_value(width: usize, value: u32) -> ClassicalRegister {
         let mut bits = Vec::new();
         let mut remaining_value = value;
@@ -44,7 +59,26 @@ impl ClassicalRegister{
         ClassicalRegister::new(bits)
     }
 
+    /// Creates a classical register holding a basis state index, where bit k-1 of the index becomes bit k
+    pub(crate) fn from_index(width: usize, index: usize) -> ClassicalRegister {
+        ClassicalRegister::new((0..width).rev().map(|bit| index.checked_shr(bit as u32).unwrap_or(0) & 1).collect())
+    }
+
+    /// Returns a copy of the register with the given width, dropping its most significant bits or padding it
+    /// with leading zeros
+    pub(crate) fn resized(&self, width: usize) -> ClassicalRegister {
+        let len = self.bits.len();
+        if width <= len {
+            return ClassicalRegister::new(self.bits[len - width..].to_vec());
+        }
+
+        let mut bits = vec![0; width - len];
+        bits.extend_from_slice(&self.bits);
+        ClassicalRegister::new(bits)
+    }
+
     /// Returns the value represented by the classical register
+    #[allow(clippy::unnecessary_cast)]
     pub fn value(&self) -> u32 {
         let mut value = 0 as u32;
     
@@ -62,14 +96,109 @@ impl ClassicalRegister{
         self.bits.clone()
     }
 
+    /// Checks that the bit index is between 1 and the length of the register
+    pub fn check_bit(&self, bit: usize) -> Result<(), QuriustError> {
+        if bit == 0 || bit > self.bits.len() {
+            return Err(QuriustError::InvalidBit { bit, bit_count: self.bits.len() });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of a single bit.
+    ///
+    /// Bits are indexed from 1, bit 1 being the least significant one, so that measuring qubit k into bit k
+    /// gives the same register as measuring the whole quantum register
+    pub fn bit(&self, bit: usize) -> usize {
+        expect_ok(self.check_bit(bit));
+        self.bits[self.bits.len() - bit]
+    }
+
+    /// Sets the value of a single bit, indexed like in `bit`
+    pub fn set_bit(&mut self, bit: usize, value: usize) {
+        expect_ok(self.check_bit(bit));
+        let len = self.bits.len();
+        self.bits[len - bit] = value & 1;
+    }
+}
+
+/// Represents a condition on a classical register, deciding whether a classically conditioned gate is applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Holds when the given bit, indexed like in [`ClassicalRegister::bit`], has the given value
+    Bit { bit: usize, value: usize },
+    /// Holds when the value of the whole register equals the given one, like OpenQASM `if (c==k)`
+    Equals(u128),
+}
+
+impl Condition {
+    /// Evaluates the condition on the classical register, returning an error if it refers to a missing bit
+    pub fn evaluate(&self, classical_register: &ClassicalRegister) -> Result<bool, QuriustError> {
+        match *self {
+            Condition::Bit { bit, value } => {
+                classical_register.check_bit(bit)?;
+                Ok(classical_register.bit(bit) == value)
+            }
+            Condition::Equals(value) => {
+                // Compared bit by bit, so that registers wider than the value are supported
+                let len = classical_register.len();
+                let fits = value.checked_shr(len as u32).unwrap_or(0) == 0;
+                Ok(fits && (1..=len).all(|bit| classical_register.bit(bit) as u128 == value.checked_shr(bit as u32 - 1).unwrap_or(0) & 1))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ClassicalRegister {
+    /// Formats the register as a bitstring, most significant bit first
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for bit in &self.bits {
+            write!(f, "{}", bit)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Histogram of measurement outcomes, mapping every observed classical register to the number of shots
+/// that produced it
+pub type Counts = HashMap<ClassicalRegister, usize>;
+
+/// Random number generator of a register, which can be cloned along with it
+trait RegisterRng: RngCore + Send + Sync {
+    fn boxed_clone(&self) -> Box<dyn RegisterRng>;
+}
+
+impl<R: RngCore + Clone + Send + Sync + 'static> RegisterRng for R {
+    fn boxed_clone(&self) -> Box<dyn RegisterRng> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn RegisterRng> {
+    fn clone(&self) -> Box<dyn RegisterRng> {
+        // The box is itself a `RegisterRng`, so clone the generator inside it
+        (**self).boxed_clone()
+    }
+}
+
+impl fmt::Debug for dyn RegisterRng {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RegisterRng")
+    }
 }
 
-/// Represents a quantum register
+/// Represents a quantum register.
+///
+/// The state of the qubits is simulated by a [`Backend`], the dense `State` by default, so the same register
+/// API drives every simulation method
 #[derive(Clone, Debug)]
-pub struct QuantumRegister{
+pub struct QuantumRegister<B: Backend = State> {
     pub measured: bool,
-    pub prob_amplitudes: State,
+    pub prob_amplitudes: B,
     pub len: usize,
+    rng: Box<dyn RegisterRng>,
+    noise_model: NoiseModel,
 }
 
 impl QuantumRegister {
@@ -79,9 +208,24 @@ impl QuantumRegister {
             measured: false,
             prob_amplitudes: State::from_cr(cr),
             len: cr.len(),
+            rng: Box::new(StdRng::from_entropy()),
+            noise_model: NoiseModel::new(),
         }
     }
 
+    /// Creates a new quantum register from a classical register, like `new`.
+    ///
+    /// Returns an error if the classical register length is not a power of two or its value does not fit in it
+    pub fn try_new(cr: &ClassicalRegister) -> Result<QuantumRegister, QuriustError> {
+        Ok(QuantumRegister {
+            measured: false,
+            prob_amplitudes: State::try_from_cr(cr)?,
+            len: cr.len(),
+            rng: Box::new(StdRng::from_entropy()),
+            noise_model: NoiseModel::new(),
+        })
+    }
+
     /// Initializes a quantum register with the specified number of qubits
     pub fn init(n_qubit: usize) -> QuantumRegister{
         let cr = &ClassicalRegister::new(vec![0; 2_i32.pow(n_qubit as u32) as usize]);
@@ -89,43 +233,170 @@ impl QuantumRegister {
             measured: false,
             prob_amplitudes: State::from_cr(cr),
             len: (cr.len()as f32).log2() as usize,
+            rng: Box::new(StdRng::from_entropy()),
+            noise_model: NoiseModel::new(),
         }
     }
 
+    /// Returns the state of the quantum register
+    pub fn state(&self)-> Vec<Complex<f64>> {
+        self.prob_amplitudes.amplitudes()
+    }
+}
+
+impl<B: Backend> QuantumRegister<B> {
+    /// Creates a quantum register simulated by the given backend, starting from its current state, e.g.
+    /// `QuantumRegister::from_backend(StabilizerState::new(500))`
+    pub fn from_backend(backend: B) -> QuantumRegister<B> {
+        QuantumRegister {
+            measured: false,
+            len: backend.get_qubit_count(),
+            prob_amplitudes: backend,
+            rng: Box::new(StdRng::from_entropy()),
+            noise_model: NoiseModel::new(),
+        }
+    }
+
+    /// Seeds the random number generator used by measurements and sampling.
+    ///
+    /// Two registers seeded alike and driven by the same operations observe exactly the same outcomes,
+    /// e.g. `QuantumRegister::init(2).with_seed(42)`
+    pub fn with_seed(mut self, seed: u64) -> QuantumRegister<B> {
+        self.rng = Box::new(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Draws the randomness of measurements and sampling from the given generator, e.g. a ChaCha stream.
+    ///
+    /// The generator is cloned along with the register
+    pub fn with_rng<R: RngCore + Clone + Send + Sync + 'static>(mut self, rng: R) -> QuantumRegister<B> {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Attaches a noise model to the register, applied automatically by every following gate and measurement.
+    ///
+    /// The same operations then run ideal or noisy depending only on the model, e.g.
+    /// `QuantumRegister::init(2).with_noise_model(model)`
+    pub fn with_noise_model(mut self, noise_model: NoiseModel) -> QuantumRegister<B> {
+        self.noise_model = noise_model;
+        self
+    }
+
+    /// Replaces the noise model of the register; an empty `NoiseModel` makes it ideal again
+    pub fn set_noise_model(&mut self, noise_model: NoiseModel) {
+        self.noise_model = noise_model;
+    }
+
+    /// Returns the noise model of the register
+    pub fn noise_model(&self) -> &NoiseModel {
+        &self.noise_model
+    }
+
+    /// Resets every qubit of the register to |0>, making it usable again after a measurement.
+    ///
+    /// The random number generator is kept, so a seeded register replays a reproducible sequence of runs
+    pub fn reset(&mut self) {
+        self.prob_amplitudes.reset();
+        self.measured = false;
+    }
+
     /// Returns the length of the quantum register
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize{
         self.len
     }
 
     /// Returns the number of qubits in the quantum register
     pub fn get_qubit_count(&self) -> usize {
-        (self.len() as f64).log2() as usize
+        self.prob_amplitudes.get_qubit_count()
+    }
+
+    /// Checks that the register has not been measured yet and that the qubits are valid for it
+    fn check(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        if self.measured {
+            return Err(QuriustError::RegisterMeasured);
+        }
+
+        self.prob_amplitudes.check_qubits(qubits)
     }
 
     /// Measures the quantum register
     pub fn measure(&mut self) -> ClassicalRegister {
-        assert_eq!(false, self.measured);
+        expect_ok(self.try_measure())
+    }
+
+    /// Fallible version of [`QuantumRegister::measure`], returning an error if the register was already measured.
+    pub fn try_measure(&mut self) -> Result<ClassicalRegister, QuriustError> {
+        self.check(&[])?;
         self.measured = true;
-    
-        let mut cum = 0.0;
-        let rand_num: f64 = rand::random();
 
-        for (val, coefficient) in self.prob_amplitudes.amplitudes().iter().enumerate() {
-            let scaled_prob = coefficient.norm_sqr();
-            cum += scaled_prob;
+        let outcome = self.prob_amplitudes.sample_outcomes(1, &mut self.rng).remove(0);
+        Ok(self.read_out(&outcome))
+    }
 
-            if rand_num <= cum {
-                return ClassicalRegister::from_value(self.len, val as u32);
-            }
+    /// Samples the measurement outcome of the register many times, without collapsing it.
+    ///
+    /// The state is left untouched, so this is the way to estimate the distribution of the outcomes.
+    ///
+    /// # Arguments
+    ///
+    /// * `shots` - The number of measurements to sample.
+    pub fn sample(&mut self, shots: usize) -> Counts {
+        expect_ok(self.try_sample(shots))
+    }
 
+    /// Fallible version of [`QuantumRegister::sample`], returning an error if the register was already measured.
+    pub fn try_sample(&mut self, shots: usize) -> Result<Counts, QuriustError> {
+        self.check(&[])?;
+
+        let mut counts = Counts::new();
+        for outcome in self.prob_amplitudes.sample_outcomes(shots, &mut self.rng) {
+            *counts.entry(self.read_out(&outcome)).or_insert(0) += 1;
         }
 
-        ClassicalRegister::from_value(self.len, 0)
+        Ok(counts)
     }
 
-    /// Returns the state of the quantum register
-    pub fn state(&self)-> Vec<Complex<f64>> {
-        self.prob_amplitudes.amplitudes()
+    /// Applies the readout errors of the noise model to a measured outcome of every qubit, reporting it with the
+    /// length of the register
+    fn read_out(&mut self, outcome: &ClassicalRegister) -> ClassicalRegister {
+        self.noise_model.read_out_register(outcome, &mut self.rng).resized(self.len)
+    }
+
+    /// Applies the given gate to the specified ordered list of qubits.
+    ///
+    /// This is the common entry point behind all the named gate methods, and the way to apply a gate
+    /// recorded in a circuit.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate` - The gate to apply.
+    /// * `qubits` - The indices of the qubits the gate acts on, in the order of its matrix basis.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        expect_ok(self.try_apply(gate, qubits));
+    }
+
+    /// Fallible version of [`QuantumRegister::apply`], returning an error instead of panicking.
+    ///
+    /// User-supplied matrices are also checked to be unitary. The gate errors of the noise model are checked
+    /// against the qubits and the backend first, so that a gate is never applied without its errors.
+    pub fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.check(qubits)?;
+        let channels = self.noise_model.gate_errors(gate, qubits);
+        for channel in &channels {
+            channel.placements(qubits)?;
+        }
+        if !channels.is_empty() && !self.prob_amplitudes.supports_channels() {
+            return Err(QuriustError::UnsupportedGate { gate: "channel".to_string() });
+        }
+
+        self.prob_amplitudes.try_apply(gate, qubits)?;
+        for channel in channels {
+            self.prob_amplitudes.try_apply_channel(channel, qubits, &mut self.rng)?;
+        }
+
+        Ok(())
     }
 
     /// Applies the Pauli-X gate (also known as the NOT gate) to the specified target qubit.
@@ -139,8 +410,12 @@ impl QuantumRegister {
     ///
     /// * `target_qubit` - The index of the target qubit to which the gate is applied.
     pub fn x(&mut self, target_qubit: usize) {
-        assert_eq!(false, self.measured);
-        self.prob_amplitudes.pauli_x_gate(target_qubit);
+        expect_ok(self.try_x(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::x`], returning an error instead of panicking.
+    pub fn try_x(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::X, &[target_qubit])
     }
 
     /// Applies the Pauli-Y gate to the specified target qubit.
@@ -154,8 +429,12 @@ impl QuantumRegister {
     ///
     /// * `target_qubit` - The index of the target qubit to which the gate is applied.
     pub fn y(&mut self, target_qubit: usize) {
-        assert_eq!(false, self.measured);
-        self.prob_amplitudes.pauli_y_gate(target_qubit);
+        expect_ok(self.try_y(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::y`], returning an error instead of panicking.
+    pub fn try_y(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Y, &[target_qubit])
     }
     
     /// Applies the Pauli-Z gate to the specified target qubit.
@@ -169,8 +448,12 @@ impl QuantumRegister {
     ///
     /// * `target_qubit` - The index of the target qubit to which the gate is applied.
     pub fn z(&mut self, target_qubit: usize) {
-        assert_eq!(false, self.measured);
-        self.prob_amplitudes.pauli_z_gate(target_qubit);
+        expect_ok(self.try_z(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::z`], returning an error instead of panicking.
+    pub fn try_z(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Z, &[target_qubit])
     }
     
     /// Applies the Hadamard gate to the specified target qubit.
@@ -184,10 +467,227 @@ impl QuantumRegister {
     ///
     /// * `target_qubit` - The index of the target qubit to which the gate is applied.
     pub fn h(&mut self, target_qubit: usize) {
-        assert_eq!(false, self.measured);
-        self.prob_amplitudes.hadamard_gate(target_qubit);
+        expect_ok(self.try_h(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::h`], returning an error instead of panicking.
+    pub fn try_h(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::H, &[target_qubit])
     }
     
+    /// Applies the identity gate to the specified target qubit.
+    ///
+    /// The identity gate leaves the qubit unchanged.
+    /// Matrix representation:
+    ///     | 1 0 |
+    ///     | 0 1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn id(&mut self, target_qubit: usize) {
+        expect_ok(self.try_id(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::id`], returning an error instead of panicking.
+    pub fn try_id(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Id, &[target_qubit])
+    }
+
+    /// Applies the S gate to the specified target qubit.
+    ///
+    /// The S gate multiplies the |1⟩ component by i, a quarter turn around the Z-axis.
+    /// Matrix representation:
+    ///     | 1 0 |
+    ///     | 0 i |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn s(&mut self, target_qubit: usize) {
+        expect_ok(self.try_s(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::s`], returning an error instead of panicking.
+    pub fn try_s(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::S, &[target_qubit])
+    }
+
+    /// Applies the S† gate to the specified target qubit.
+    ///
+    /// The S† gate is the inverse of the S gate and multiplies the |1⟩ component by -i.
+    /// Matrix representation:
+    ///     | 1  0 |
+    ///     | 0 -i |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn sdg(&mut self, target_qubit: usize) {
+        expect_ok(self.try_sdg(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::sdg`], returning an error instead of panicking.
+    pub fn try_sdg(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Sdg, &[target_qubit])
+    }
+
+    /// Applies the T gate to the specified target qubit.
+    ///
+    /// The T gate multiplies the |1⟩ component by e^(iπ/4), an eighth turn around the Z-axis.
+    /// Matrix representation:
+    ///     | 1 0          |
+    ///     | 0 e^(iπ/4)   |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn t(&mut self, target_qubit: usize) {
+        expect_ok(self.try_t(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::t`], returning an error instead of panicking.
+    pub fn try_t(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::T, &[target_qubit])
+    }
+
+    /// Applies the T† gate to the specified target qubit.
+    ///
+    /// The T† gate is the inverse of the T gate and multiplies the |1⟩ component by e^(-iπ/4).
+    /// Matrix representation:
+    ///     | 1 0          |
+    ///     | 0 e^(-iπ/4)  |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn tdg(&mut self, target_qubit: usize) {
+        expect_ok(self.try_tdg(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::tdg`], returning an error instead of panicking.
+    pub fn try_tdg(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Tdg, &[target_qubit])
+    }
+
+    /// Applies the √X gate to the specified target qubit.
+    ///
+    /// Applying the √X gate twice is equivalent to a Pauli-X gate.
+    /// Matrix representation:
+    ///     | (1+i)/2 (1-i)/2 |
+    ///     | (1-i)/2 (1+i)/2 |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    pub fn sx(&mut self, target_qubit: usize) {
+        expect_ok(self.try_sx(target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::sx`], returning an error instead of panicking.
+    pub fn try_sx(&mut self, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Sx, &[target_qubit])
+    }
+
+    /// Applies the phase gate P(λ) to the specified target qubit.
+    ///
+    /// The phase gate multiplies the |1⟩ component by e^(iλ).
+    /// Matrix representation:
+    ///     | 1 0      |
+    ///     | 0 e^(iλ) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `lambda` - The phase angle λ.
+    pub fn p(&mut self, target_qubit: usize, lambda: f64) {
+        expect_ok(self.try_p(target_qubit, lambda));
+    }
+
+    /// Fallible version of [`QuantumRegister::p`], returning an error instead of panicking.
+    pub fn try_p(&mut self, target_qubit: usize, lambda: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::P(lambda), &[target_qubit])
+    }
+
+    /// Applies a rotation by θ around the X-axis to the specified target qubit.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2)    -i sin(θ/2) |
+    ///     | -i sin(θ/2)  cos(θ/2)   |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    pub fn rx(&mut self, target_qubit: usize, theta: f64) {
+        expect_ok(self.try_rx(target_qubit, theta));
+    }
+
+    /// Fallible version of [`QuantumRegister::rx`], returning an error instead of panicking.
+    pub fn try_rx(&mut self, target_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Rx(theta), &[target_qubit])
+    }
+
+    /// Applies a rotation by θ around the Y-axis to the specified target qubit.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2) -sin(θ/2) |
+    ///     | sin(θ/2)  cos(θ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    pub fn ry(&mut self, target_qubit: usize, theta: f64) {
+        expect_ok(self.try_ry(target_qubit, theta));
+    }
+
+    /// Fallible version of [`QuantumRegister::ry`], returning an error instead of panicking.
+    pub fn try_ry(&mut self, target_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Ry(theta), &[target_qubit])
+    }
+
+    /// Applies a rotation by θ around the Z-axis to the specified target qubit.
+    ///
+    /// Matrix representation:
+    ///     | e^(-iθ/2) 0        |
+    ///     | 0         e^(iθ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    pub fn rz(&mut self, target_qubit: usize, theta: f64) {
+        expect_ok(self.try_rz(target_qubit, theta));
+    }
+
+    /// Fallible version of [`QuantumRegister::rz`], returning an error instead of panicking.
+    pub fn try_rz(&mut self, target_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Rz(theta), &[target_qubit])
+    }
+
+    /// Applies the general single-qubit gate U3(θ, φ, λ) to the specified target qubit.
+    ///
+    /// Any single-qubit unitary can be written as U3 up to a global phase.
+    /// Matrix representation:
+    ///     | cos(θ/2)         -e^(iλ) sin(θ/2)     |
+    ///     | e^(iφ) sin(θ/2)   e^(i(φ+λ)) cos(θ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `target_qubit` - The index of the target qubit to which the gate is applied.
+    /// * `theta` - The rotation angle θ.
+    /// * `phi` - The phase angle φ.
+    /// * `lambda` - The phase angle λ.
+    pub fn u3(&mut self, target_qubit: usize, theta: f64, phi: f64, lambda: f64) {
+        expect_ok(self.try_u3(target_qubit, theta, phi, lambda));
+    }
+
+    /// Fallible version of [`QuantumRegister::u3`], returning an error instead of panicking.
+    pub fn try_u3(&mut self, target_qubit: usize, theta: f64, phi: f64, lambda: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::U3(theta, phi, lambda), &[target_qubit])
+    }
+
     /// Applies the Controlled-NOT (CNOT) gate to the specified control and target qubits.
     ///
     /// The CNOT gate flips the target qubit if and only if the control qubit is in the |1⟩ state.
@@ -201,19 +701,415 @@ impl QuantumRegister {
     ///
     /// * `control_qubit` - The index of the control qubit.
     /// * `target_qubit` - The index of the target qubit.
-    pub fn cnot(&mut self, control_qubit: usize, target_qubit: usize){
-        self.prob_amplitudes.cnot_gate(control_qubit, target_qubit);
+    pub fn cnot(&mut self, control_qubit: usize, target_qubit: usize) {
+        expect_ok(self.try_cnot(control_qubit, target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::cnot`], returning an error instead of panicking.
+    pub fn try_cnot(&mut self, control_qubit: usize, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Cnot, &[control_qubit, target_qubit])
     }
     
-    /// Measures a specific qubit in the quantum register
+    /// Applies the Controlled-Z (CZ) gate to the specified control and target qubits.
+    ///
+    /// The CZ gate flips the phase of the |11⟩ component, so control and target are interchangeable.
+    /// Matrix representation:
+    ///     | 1 0 0  0 |
+    ///     | 0 1 0  0 |
+    ///     | 0 0 1  0 |
+    ///     | 0 0 0 -1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `control_qubit` - The index of the control qubit.
+    /// * `target_qubit` - The index of the target qubit.
+    pub fn cz(&mut self, control_qubit: usize, target_qubit: usize) {
+        expect_ok(self.try_cz(control_qubit, target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::cz`], returning an error instead of panicking.
+    pub fn try_cz(&mut self, control_qubit: usize, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Cz, &[control_qubit, target_qubit])
+    }
+
+    /// Applies the controlled-phase gate CPhase(φ) to the specified control and target qubits.
+    ///
+    /// The controlled-phase gate multiplies the |11⟩ component by e^(iφ).
+    /// Matrix representation:
+    ///     | 1 0 0 0      |
+    ///     | 0 1 0 0      |
+    ///     | 0 0 1 0      |
+    ///     | 0 0 0 e^(iφ) |
+    ///
+    /// # Arguments
+    ///
+    /// * `control_qubit` - The index of the control qubit.
+    /// * `target_qubit` - The index of the target qubit.
+    /// * `phi` - The phase angle φ.
+    pub fn cphase(&mut self, control_qubit: usize, target_qubit: usize, phi: f64) {
+        expect_ok(self.try_cphase(control_qubit, target_qubit, phi));
+    }
+
+    /// Fallible version of [`QuantumRegister::cphase`], returning an error instead of panicking.
+    pub fn try_cphase(&mut self, control_qubit: usize, target_qubit: usize, phi: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::CPhase(phi), &[control_qubit, target_qubit])
+    }
+
+    /// Applies the SWAP gate to the specified pair of qubits.
+    ///
+    /// The SWAP gate exchanges the states of the two qubits.
+    /// Matrix representation:
+    ///     | 1 0 0 0 |
+    ///     | 0 0 1 0 |
+    ///     | 0 1 0 0 |
+    ///     | 0 0 0 1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    pub fn swap(&mut self, first_qubit: usize, second_qubit: usize) {
+        expect_ok(self.try_swap(first_qubit, second_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::swap`], returning an error instead of panicking.
+    pub fn try_swap(&mut self, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Swap, &[first_qubit, second_qubit])
+    }
+
+    /// Applies the √SWAP gate to the specified pair of qubits.
+    ///
+    /// Applying the √SWAP gate twice is equivalent to a SWAP gate.
+    /// Matrix representation:
+    ///     | 1 0       0       0 |
+    ///     | 0 (1+i)/2 (1-i)/2 0 |
+    ///     | 0 (1-i)/2 (1+i)/2 0 |
+    ///     | 0 0       0       1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    pub fn sqrt_swap(&mut self, first_qubit: usize, second_qubit: usize) {
+        expect_ok(self.try_sqrt_swap(first_qubit, second_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::sqrt_swap`], returning an error instead of panicking.
+    pub fn try_sqrt_swap(&mut self, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::SqrtSwap, &[first_qubit, second_qubit])
+    }
+
+    /// Applies the iSWAP gate to the specified pair of qubits.
+    ///
+    /// The iSWAP gate exchanges the states of the two qubits and multiplies |01⟩ and |10⟩ by i.
+    /// Matrix representation:
+    ///     | 1 0 0 0 |
+    ///     | 0 0 i 0 |
+    ///     | 0 i 0 0 |
+    ///     | 0 0 0 1 |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    pub fn iswap(&mut self, first_qubit: usize, second_qubit: usize) {
+        expect_ok(self.try_iswap(first_qubit, second_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::iswap`], returning an error instead of panicking.
+    pub fn try_iswap(&mut self, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::ISwap, &[first_qubit, second_qubit])
+    }
+
+    /// Applies the Ising XX coupling RXX(θ) = exp(-iθ X⊗X / 2) to the specified pair of qubits.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2)     0            0            -i sin(θ/2) |
+    ///     | 0            cos(θ/2)     -i sin(θ/2)  0           |
+    ///     | 0            -i sin(θ/2)  cos(θ/2)     0           |
+    ///     | -i sin(θ/2)  0            0            cos(θ/2)    |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    pub fn rxx(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        expect_ok(self.try_rxx(first_qubit, second_qubit, theta));
+    }
+
+    /// Fallible version of [`QuantumRegister::rxx`], returning an error instead of panicking.
+    pub fn try_rxx(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Rxx(theta), &[first_qubit, second_qubit])
+    }
+
+    /// Applies the Ising YY coupling RYY(θ) = exp(-iθ Y⊗Y / 2) to the specified pair of qubits.
+    ///
+    /// Matrix representation:
+    ///     | cos(θ/2)    0            0            i sin(θ/2) |
+    ///     | 0           cos(θ/2)     -i sin(θ/2)  0          |
+    ///     | 0           -i sin(θ/2)  cos(θ/2)     0          |
+    ///     | i sin(θ/2)  0            0            cos(θ/2)   |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    pub fn ryy(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        expect_ok(self.try_ryy(first_qubit, second_qubit, theta));
+    }
+
+    /// Fallible version of [`QuantumRegister::ryy`], returning an error instead of panicking.
+    pub fn try_ryy(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Ryy(theta), &[first_qubit, second_qubit])
+    }
+
+    /// Applies the Ising ZZ coupling RZZ(θ) = exp(-iθ Z⊗Z / 2) to the specified pair of qubits.
+    ///
+    /// Matrix representation:
+    ///     | e^(-iθ/2) 0        0        0         |
+    ///     | 0         e^(iθ/2) 0        0         |
+    ///     | 0         0        e^(iθ/2) 0         |
+    ///     | 0         0        0        e^(-iθ/2) |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    pub fn rzz(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        expect_ok(self.try_rzz(first_qubit, second_qubit, theta));
+    }
+
+    /// Fallible version of [`QuantumRegister::rzz`], returning an error instead of panicking.
+    pub fn try_rzz(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Rzz(theta), &[first_qubit, second_qubit])
+    }
+
+    /// Applies the fSim(θ, φ) gate to the specified pair of qubits.
+    ///
+    /// The fSim gate rotates |01⟩ and |10⟩ into each other by θ and multiplies |11⟩ by e^(-iφ).
+    /// Matrix representation:
+    ///     | 1 0          0          0       |
+    ///     | 0 cos(θ)     -i sin(θ)  0       |
+    ///     | 0 -i sin(θ)  cos(θ)     0       |
+    ///     | 0 0          0          e^(-iφ) |
+    ///
+    /// # Arguments
+    ///
+    /// * `first_qubit` - The index of the first qubit.
+    /// * `second_qubit` - The index of the second qubit.
+    /// * `theta` - The rotation angle θ.
+    /// * `phi` - The phase angle φ.
+    pub fn fsim(&mut self, first_qubit: usize, second_qubit: usize, theta: f64, phi: f64) {
+        expect_ok(self.try_fsim(first_qubit, second_qubit, theta, phi));
+    }
+
+    /// Fallible version of [`QuantumRegister::fsim`], returning an error instead of panicking.
+    pub fn try_fsim(&mut self, first_qubit: usize, second_qubit: usize, theta: f64, phi: f64) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::FSim(theta, phi), &[first_qubit, second_qubit])
+    }
+
+    /// Applies the Toffoli (CCNOT) gate to the specified control and target qubits.
+    ///
+    /// The Toffoli gate flips the target qubit if and only if both control qubits are in the |1⟩ state.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_control` - The index of the first control qubit.
+    /// * `second_control` - The index of the second control qubit.
+    /// * `target_qubit` - The index of the target qubit.
+    pub fn ccx(&mut self, first_control: usize, second_control: usize, target_qubit: usize) {
+        expect_ok(self.try_ccx(first_control, second_control, target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::ccx`], returning an error instead of panicking.
+    pub fn try_ccx(&mut self, first_control: usize, second_control: usize, target_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Ccx, &[first_control, second_control, target_qubit])
+    }
+
+    /// Applies the Fredkin (controlled-SWAP) gate to the specified control qubit and pair of target qubits.
+    ///
+    /// The Fredkin gate exchanges the states of the two target qubits if and only if the control qubit is in the |1⟩ state.
+    ///
+    /// # Arguments
+    ///
+    /// * `control_qubit` - The index of the control qubit.
+    /// * `first_qubit` - The index of the first target qubit.
+    /// * `second_qubit` - The index of the second target qubit.
+    pub fn cswap(&mut self, control_qubit: usize, first_qubit: usize, second_qubit: usize) {
+        expect_ok(self.try_cswap(control_qubit, first_qubit, second_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::cswap`], returning an error instead of panicking.
+    pub fn try_cswap(&mut self, control_qubit: usize, first_qubit: usize, second_qubit: usize) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::CSwap, &[control_qubit, first_qubit, second_qubit])
+    }
+
+    /// Applies an arbitrary single-qubit gate to the target qubit, conditioned on a set of control qubits.
+    ///
+    /// The gate acts if and only if every control qubit is in the |1⟩ state, which makes it possible to write
+    /// multi-controlled oracles in a single call.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate` - The 2x2 matrix of the gate to apply.
+    /// * `controls` - The indices of the control qubits.
+    /// * `target_qubit` - The index of the target qubit.
+    pub fn controlled(&mut self, gate: DMatrix<Complex<f64>>, controls: &[usize], target_qubit: usize) {
+        expect_ok(self.try_controlled(gate, controls, target_qubit));
+    }
+
+    /// Fallible version of [`QuantumRegister::controlled`], returning an error instead of panicking.
+    pub fn try_controlled(&mut self, gate: DMatrix<Complex<f64>>, controls: &[usize], target_qubit: usize) -> Result<(), QuriustError> {
+        let mut qubits = controls.to_vec();
+        qubits.push(target_qubit);
+        self.try_apply(&Gate::Controlled { gate, controls: controls.len() }, &qubits)
+    }
+
+    /// Applies an arbitrary unitary to the specified ordered list of qubits.
+    ///
+    /// The matrix is written in the basis |q1 q2 ... qk⟩ of the given qubits, so the first qubit is the most
+    /// significant bit of the matrix index. The matrix must be 2^k x 2^k and unitary.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate` - The matrix of the gate to apply.
+    /// * `qubits` - The indices of the qubits the gate acts on.
+    pub fn unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        expect_ok(self.try_unitary(gate, qubits));
+    }
+
+    /// Fallible version of [`QuantumRegister::unitary`], returning an error instead of panicking.
+    pub fn try_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Unitary(gate.clone()), qubits)
+    }
+
+    /// Measures a specific qubit in the quantum register.
+    ///
+    /// Only the measured qubit collapses: the amplitudes consistent with the outcome are renormalised and the
+    /// register stays usable for further gates and measurements.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the qubit was observed in |1>, `false` otherwise.
     pub fn measure_qubit(&mut self, qubit_to_measure: usize) -> bool {
-        let measured_classical_register = self.measure(); // Measure all qubits
+        expect_ok(self.try_measure_qubit(qubit_to_measure))
+    }
+
+    /// Fallible version of [`QuantumRegister::measure_qubit`], returning an error instead of panicking.
+    pub fn try_measure_qubit(&mut self, qubit_to_measure: usize) -> Result<bool, QuriustError> {
+        Ok(self.try_measure_qubits(&[qubit_to_measure])?.value() == 1)
+    }
+
+    /// Measures a subset of the qubits, collapsing only the part of the state they determine.
+    ///
+    /// The state collapses onto the actual outcome, while the returned bits go through the readout errors of
+    /// the noise model, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `qubits` - The qubits to measure.
+    ///
+    /// # Returns
+    ///
+    /// A classical register holding the observed bits, the first listed qubit being the most significant one.
+    pub fn measure_qubits(&mut self, qubits: &[usize]) -> ClassicalRegister {
+        expect_ok(self.try_measure_qubits(qubits))
+    }
+
+    /// Fallible version of [`QuantumRegister::measure_qubits`], returning an error instead of panicking.
+    pub fn try_measure_qubits(&mut self, qubits: &[usize]) -> Result<ClassicalRegister, QuriustError> {
+        self.check(qubits)?;
+
+        let bits = self.prob_amplitudes.measure_qubits(qubits, &mut self.rng);
+        let reported = qubits.iter().zip(bits).map(|(&qubit, bit)| self.noise_model.read_out(qubit, bit, &mut self.rng)).collect();
+
+        Ok(ClassicalRegister::new(reported))
+    }
+
+    /// Measures a qubit and stores the outcome in a bit of a classical register.
+    ///
+    /// Like `measure_qubit`, only the measured qubit collapses and the register stays usable, so later
+    /// gates can be conditioned on the stored bit with `apply_if`.
+    ///
+    /// # Arguments
+    ///
+    /// * `qubit` - The qubit to measure.
+    /// * `classical_register` - The classical register receiving the outcome.
+    /// * `bit` - The bit of the classical register to write, indexed like in [`ClassicalRegister::bit`].
+    pub fn measure_into(&mut self, qubit: usize, classical_register: &mut ClassicalRegister, bit: usize) -> bool {
+        expect_ok(self.try_measure_into(qubit, classical_register, bit))
+    }
+
+    /// Fallible version of [`QuantumRegister::measure_into`], returning an error instead of panicking.
+    pub fn try_measure_into(&mut self, qubit: usize, classical_register: &mut ClassicalRegister, bit: usize) -> Result<bool, QuriustError> {
+        classical_register.check_bit(bit)?;
+        let outcome = self.try_measure_qubit(qubit)?;
+        classical_register.set_bit(bit, outcome as usize);
 
+        Ok(outcome)
+    }
+
+    /// Resets a qubit to |0>, leaving the other qubits usable.
+    ///
+    /// The qubit is measured and flipped back when found in |1>, so any entanglement with it is broken
+    pub fn reset_qubit(&mut self, qubit: usize) {
+        expect_ok(self.try_reset_qubit(qubit))
+    }
 
-        let qubit_state_index = measured_classical_register.value() >> (self.len() - qubit_to_measure);
-        let qubit_state = (qubit_state_index & 1) != 0;
+    /// Fallible version of [`QuantumRegister::reset_qubit`], returning an error instead of panicking.
+    pub fn try_reset_qubit(&mut self, qubit: usize) -> Result<(), QuriustError> {
+        self.check(&[qubit])?;
+        self.prob_amplitudes.reset_qubit(qubit, &mut self.rng);
 
-        qubit_state // Return true if the qubit state is |1>, else false
+        Ok(())
+    }
+
+    /// Applies a noise channel to the given qubits, picking one of its Kraus operators at random.
+    ///
+    /// The register stays a pure state, so this simulates one quantum trajectory; averaging many runs
+    /// reproduces the noisy density matrix. A single-qubit channel acts independently on every listed qubit
+    pub fn apply_channel(&mut self, channel: &KrausChannel, qubits: &[usize]) {
+        expect_ok(self.try_apply_channel(channel, qubits))
+    }
+
+    /// Fallible version of [`QuantumRegister::apply_channel`], returning an error instead of panicking.
+    pub fn try_apply_channel(&mut self, channel: &KrausChannel, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.check(qubits)?;
+        self.prob_amplitudes.try_apply_channel(channel, qubits, &mut self.rng)?;
+
+        Ok(())
+    }
+
+    /// Applies a gate only if a condition on a classical register holds.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - The condition deciding whether the gate is applied.
+    /// * `classical_register` - The classical register the condition is evaluated on.
+    /// * `gate` - The gate to apply.
+    /// * `qubits` - The indices of the qubits the gate acts on, in the order of its matrix basis.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the condition held and the gate was applied.
+    pub fn apply_if(&mut self, condition: &Condition, classical_register: &ClassicalRegister, gate: &Gate, qubits: &[usize]) -> bool {
+        expect_ok(self.try_apply_if(condition, classical_register, gate, qubits))
+    }
+
+    /// Fallible version of [`QuantumRegister::apply_if`], returning an error instead of panicking.
+    ///
+    /// The qubits are validated whether or not the condition holds.
+    pub fn try_apply_if(&mut self, condition: &Condition, classical_register: &ClassicalRegister, gate: &Gate, qubits: &[usize]) -> Result<bool, QuriustError> {
+        self.check(qubits)?;
+        if !condition.evaluate(classical_register)? {
+            return Ok(false);
+        }
+
+        self.try_apply(gate, qubits)?;
+        Ok(true)
     }
 }
 
@@ -227,6 +1123,7 @@ fn test_classical_value() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_init() {
     let cr = ClassicalRegister::zeros(8);
     let qr: QuantumRegister = QuantumRegister::new(&cr);
@@ -267,6 +1164,7 @@ fn test_measurement() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_measure_single_qubit(){
     let mut qr1: QuantumRegister = QuantumRegister::init(1);
     let mut qr2: QuantumRegister = QuantumRegister::init(5);
@@ -278,5 +1176,282 @@ fn test_measure_single_qubit(){
 
     assert_eq!(m1, true); 
     assert_eq!(m2, false);
+    assert!(!qr1.measured);
+
+    // A measured qubit collapses its entangled partners, and the register keeps computing
+    for seed in 0..10 {
+        let mut qr: QuantumRegister = QuantumRegister::init(3).with_seed(seed);
+        qr.x(3);
+        qr.h(1);
+        qr.cnot(1, 2);
+
+        let first = qr.measure_qubit(1);
+        assert_eq!(qr.measure_qubit(2), first);
+        qr.x(3);
+        assert_eq!(qr.measure_qubits(&[3, 2, 1]), ClassicalRegister::new(vec![0, first as usize, first as usize]));
+    }
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    assert_eq!(qr.try_measure_qubits(&[1, 1]), Err(QuriustError::InvalidQubit { qubit: 1, qubit_count: 2 }));
+    assert_eq!(qr.try_measure_qubit(3), Err(QuriustError::InvalidQubit { qubit: 3, qubit_count: 2 }));
+}
+
+
+#[test]
+fn test_phase_and_rotation_gates(){
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.sx(1);
+    qr.sx(1);
+    qr.h(2);
+    qr.t(2);
+    qr.t(2);
+    qr.sdg(2);
+    qr.h(2);
+
+    assert_eq!(ClassicalRegister::new(vec![0,1]), qr.measure());
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.rx(2, 0.8);
+    qr.ry(1, -1.9);
+    qr.rz(2, 0.3);
+    qr.u3(1, 1.9, 0.0, 0.0);
+    qr.rz(2, -0.3);
+    qr.rx(2, -0.8);
+    qr.p(1, 2.4);
+    qr.id(2);
+
+    assert_eq!(ClassicalRegister::new(vec![0,0]), qr.measure());
+}
+
+#[test]
+fn test_two_qubit_gates(){
+    let mut qr: QuantumRegister = QuantumRegister::init(3);
+    qr.x(1);
+    qr.swap(1, 3);
+
+    assert_eq!(ClassicalRegister::new(vec![1,0,0]), qr.measure());
+
+    let mut qr: QuantumRegister = QuantumRegister::init(3);
+    qr.x(2);
+    qr.iswap(2, 3);
+    qr.iswap(2, 3);
+    qr.sqrt_swap(1, 2);
+    qr.sqrt_swap(1, 2);
+
+    assert_eq!(ClassicalRegister::new(vec![0,0,1]), qr.measure());
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.h(1);
+    qr.h(2);
+    qr.cz(1, 2);
+    qr.cphase(2, 1, std::f64::consts::PI);
+    qr.rxx(1, 2, 0.4);
+    qr.ryy(1, 2, 1.3);
+    qr.rzz(1, 2, -0.8);
+    qr.rzz(1, 2, 0.8);
+    qr.ryy(1, 2, -1.3);
+    qr.rxx(1, 2, -0.4);
+    qr.fsim(1, 2, 0.7, 0.2);
+    qr.fsim(1, 2, -0.7, -0.2);
+    qr.h(1);
+    qr.h(2);
+
+    assert_eq!(ClassicalRegister::new(vec![0,0]), qr.measure());
+}
+
+#[test]
+fn test_multi_controlled_gates(){
+    let mut qr: QuantumRegister = QuantumRegister::init(3);
+    qr.x(1);
+    qr.x(2);
+    qr.ccx(1, 2, 3);
+    qr.cswap(3, 1, 2);
+    qr.x(2);
+    qr.cswap(1, 2, 3);
+
+    assert_eq!(ClassicalRegister::new(vec![0,1,1]), qr.measure());
+
+    // A three-controlled Z marks |1111⟩, which turns |+⟩ on the target into |-⟩
+    let mut qr: QuantumRegister = QuantumRegister::init(4);
+    qr.x(1);
+    qr.x(2);
+    qr.x(3);
+    qr.h(4);
+    qr.controlled(crate::gates::pauli_z(), &[1, 2, 3], 4);
+    qr.h(4);
+
+    assert_eq!(ClassicalRegister::new(vec![1,1,1,1]), qr.measure());
+
+    let mut qr: QuantumRegister = QuantumRegister::init(4);
+    qr.x(1);
+    qr.x(3);
+    qr.h(4);
+    qr.controlled(crate::gates::pauli_z(), &[1, 2, 3], 4);
+    qr.h(4);
+
+    assert_eq!(ClassicalRegister::new(vec![0,1,0,1]), qr.measure());
+}
+
+#[test]
+fn test_fallible_operations(){
+    assert_eq!(QuantumRegister::try_new(&ClassicalRegister::zeros(3)).unwrap_err(), QuriustError::DimensionMismatch { expected: 4, found: 3 });
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    assert_eq!(qr.try_x(0), Err(QuriustError::InvalidQubit { qubit: 0, qubit_count: 2 }));
+    assert_eq!(qr.try_cnot(1, 3), Err(QuriustError::InvalidQubit { qubit: 3, qubit_count: 2 }));
+    assert_eq!(qr.try_swap(2, 2), Err(QuriustError::InvalidQubit { qubit: 2, qubit_count: 2 }));
+    assert_eq!(qr.try_unitary(&crate::gates::hadamard(), &[1, 2]), Err(QuriustError::DimensionMismatch { expected: 4, found: 2 }));
+    assert!(matches!(qr.try_controlled(crate::gates::hadamard() * Complex::new(2.0, 0.0), &[1], 2), Err(QuriustError::NonUnitaryGate { .. })));
+    assert!(matches!(qr.try_unitary(&(crate::gates::swap() * Complex::new(0.5, 0.0)), &[1, 2]), Err(QuriustError::NonUnitaryGate { .. })));
+    assert_eq!(qr.try_measure_qubit(3), Err(QuriustError::InvalidQubit { qubit: 3, qubit_count: 2 }));
+
+    assert_eq!(qr.try_x(1), Ok(()));
+    assert_eq!(qr.try_unitary(&crate::gates::cnot(), &[1, 2]), Ok(()));
+    assert_eq!(qr.try_measure(), Ok(ClassicalRegister::new(vec![1,1])));
+
+    assert_eq!(qr.try_h(1), Err(QuriustError::RegisterMeasured));
+    assert_eq!(qr.try_measure(), Err(QuriustError::RegisterMeasured));
+    assert_eq!(qr.try_measure_qubit(1), Err(QuriustError::RegisterMeasured));
+}
+
+#[test]
+#[should_panic(expected = "the quantum register has already been measured")]
+fn test_gate_after_measurement_panics(){
+    let mut qr: QuantumRegister = QuantumRegister::init(1);
+    qr.measure();
+    qr.x(1);
+}
+
+#[test]
+fn test_sample_counts(){
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.h(1);
+    qr.cnot(1, 2);
+
+    let counts = qr.sample(1000);
+    assert_eq!(counts.values().sum::<usize>(), 1000);
+    assert_eq!(counts.len(), 2);
+    assert!(counts[&ClassicalRegister::new(vec![0,0])] > 400);
+    assert!(counts[&ClassicalRegister::new(vec![1,1])] > 400);
+    assert!(!qr.measured);
+
+    qr.measure();
+    assert_eq!(qr.try_sample(10), Err(QuriustError::RegisterMeasured));
+    assert_eq!(ClassicalRegister::new(vec![0,1,1]).to_string(), "011");
+}
+
+#[test]
+fn test_seeded_measurements(){
+    let run = |seed: u64| {
+        let mut qr = QuantumRegister::init(4).with_seed(seed);
+        for qubit in 1..=4 {
+            qr.h(qubit);
+        }
+        let counts = qr.sample(200);
+        (counts, qr.measure())
+    };
+
+    assert_eq!(run(42), run(42));
+    assert!((0..8).map(run).any(|outcome| outcome != run(42)));
+
+    let mut first = QuantumRegister::init(3).with_rng(StdRng::seed_from_u64(5));
+    let mut second = QuantumRegister::init(3).with_rng(StdRng::seed_from_u64(5));
+    first.h(1);
+    second.h(1);
+    assert_eq!(first.sample(100), second.sample(100));
+
+    // The given generator itself draws the outcomes, so a constant stream always picks the same end
+    for (rng, bit) in [(StepRng::new(0, 0), 0), (StepRng::new(u64::MAX, 0), 1)] {
+        let mut qr = QuantumRegister::init(1).with_rng(rng);
+        qr.h(1);
+        assert_eq!(qr.sample(20), Counts::from([(ClassicalRegister::new(vec![bit]), 20)]));
+        assert_eq!(qr.clone().measure_qubit(1), bit == 1);
+    }
 }
 
+#[test]
+fn test_classical_bits_and_conditions(){
+    let mut cr = ClassicalRegister::zeros(3);
+    cr.set_bit(1, 1);
+    cr.set_bit(3, 1);
+    assert_eq!(cr.bits(), vec![1, 0, 1]);
+    assert_eq!((cr.bit(1), cr.bit(2), cr.bit(3)), (1, 0, 1));
+
+    assert_eq!(Condition::Bit { bit: 3, value: 1 }.evaluate(&cr), Ok(true));
+    assert_eq!(Condition::Bit { bit: 2, value: 1 }.evaluate(&cr), Ok(false));
+    assert_eq!(Condition::Equals(5).evaluate(&cr), Ok(true));
+    assert_eq!(Condition::Equals(13).evaluate(&cr), Ok(false));
+
+    let mut wide = ClassicalRegister::zeros(70);
+    wide.set_bit(1, 1);
+    wide.set_bit(70, 1);
+    assert_eq!(Condition::Equals(1 << 69 | 1).evaluate(&wide), Ok(true));
+    assert_eq!(Condition::Equals(1).evaluate(&wide), Ok(false));
+    assert_eq!(Condition::Bit { bit: 4, value: 1 }.evaluate(&cr), Err(QuriustError::InvalidBit { bit: 4, bit_count: 3 }));
+
+    // Measuring every qubit into its own bit matches a measurement of the whole register
+    let mut qr: QuantumRegister = QuantumRegister::init(3);
+    qr.x(1);
+    qr.x(2);
+    let mut measured = ClassicalRegister::zeros(3);
+    for qubit in 1..=3 {
+        qr.measure_into(qubit, &mut measured, qubit);
+    }
+    assert_eq!(measured, qr.measure());
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.h(1);
+    qr.cnot(1, 2);
+    qr.reset_qubit(1);
+    assert!(!qr.apply_if(&Condition::Equals(1), &ClassicalRegister::zeros(2), &Gate::X, &[1]));
+    assert!(!qr.measure_qubit(1));
+}
+
+#[test]
+fn test_noise_channels(){
+    let mut qr: QuantumRegister = QuantumRegister::init(2).with_seed(3);
+    qr.apply_channel(&KrausChannel::bit_flip(1.0), &[2]);
+    assert_eq!(qr.measure(), ClassicalRegister::new(vec![1, 0]));
+
+    let mut flips = 0;
+    let mut qr: QuantumRegister = QuantumRegister::init(1).with_seed(4);
+    for _ in 0..1000 {
+        qr.reset();
+        qr.apply_channel(&KrausChannel::depolarizing(0.4), &[1]);
+        flips += qr.measure().value() as usize;
+    }
+    assert!(flips > 150 && flips < 250);
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    assert_eq!(qr.try_apply_channel(&KrausChannel::two_qubit_depolarizing(0.1), &[1]), Err(QuriustError::DimensionMismatch { expected: 2, found: 1 }));
+}
+
+#[test]
+fn test_noise_model(){
+    let mut model = NoiseModel::new();
+    model.add_gate_error("x", KrausChannel::bit_flip(1.0));
+    model.add_readout_error(2, ReadoutError::new(1.0, 0.0));
+
+    // The ideal register and the noisy one run the very same operations
+    let program = |qr: &mut QuantumRegister| {
+        qr.x(1);
+        qr.h(2);
+        qr.h(2);
+        qr.measure()
+    };
+    assert_eq!(program(&mut QuantumRegister::init(2)), ClassicalRegister::new(vec![0, 1]));
+    let mut noisy = QuantumRegister::init(2).with_noise_model(model.clone());
+    assert_eq!(program(&mut noisy), ClassicalRegister::new(vec![1, 0]));
+
+    let counts = QuantumRegister::init(2).with_noise_model(model.clone()).sample(20);
+    assert_eq!(counts[&ClassicalRegister::new(vec![1, 0])], 20);
+
+    // Readout errors change the reported bits, not the collapsed state
+    let mut qr = QuantumRegister::init(2).with_noise_model(model);
+    assert_eq!(qr.measure_qubits(&[2, 1]), ClassicalRegister::new(vec![1, 0]));
+    assert!((qr.state()[0].norm_sqr() - 1.0).abs() < 1e-12);
+
+    qr.set_noise_model(NoiseModel::new());
+    assert!(qr.noise_model().is_ideal());
+    assert!(!qr.measure_qubit(2));
+}
@@ -0,0 +1,397 @@
+use super::backend;
+#[cfg(test)]
+use super::backend::Backend;
+use super::error::{expect_ok, QuriustError};
+use super::gates::Gate;
+use super::state::{State, DEFAULT_TOLERANCE};
+#[cfg(test)]
+use super::gates;
+#[cfg(test)]
+use super::registers::ClassicalRegister;
+#[cfg(test)]
+use super::state::kronecker_product;
+use nalgebra::DMatrix;
+use num_complex::Complex;
+use rand::Rng;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::BTreeMap;
+
+/// Default fraction of nonzero amplitudes above which a sparse state switches to the dense representation
+pub const DEFAULT_DENSE_THRESHOLD: f64 = 0.125;
+/// Amplitudes with a smaller squared norm are dropped from the sparse representation
+const SPARSE_CUTOFF: f64 = 1e-30;
+
+/// Amplitudes of a sparse state, in either representation
+#[derive(Debug, Clone)]
+enum Amplitudes {
+    Sparse(BTreeMap<usize, Complex<f64>>),
+    Dense(State),
+}
+
+/// Represents a quantum state that only stores its nonzero amplitudes.
+///
+/// The amplitudes are kept in a map from basis index to amplitude, where bit k-1 of the index holds qubit k
+/// like in `State`, so oracle and arithmetic circuits that keep a few basis states populated can run on many
+/// more qubits than the dense state vector allows. A gate visits every populated group of basis states that
+/// differ only in its qubits, so its cost grows with the number of nonzero amplitudes instead of 2^n. Once
+/// more than `dense_threshold` of the 2^n amplitudes are nonzero, the state switches to a dense `State` for
+/// good, since the map is then slower and larger than the vector
+#[derive(Debug, Clone)]
+pub struct SparseState {
+    qubit_count: usize,
+    amplitudes: Amplitudes,
+    dense_threshold: f64,
+}
+
+impl SparseState {
+    /// Creates the state |0...0> on the specified number of qubits
+    pub fn new(qubit_count: usize) -> SparseState {
+        assert!(qubit_count < usize::BITS as usize, "a sparse state supports at most {} qubits", usize::BITS - 1);
+
+        SparseState {
+            qubit_count,
+            amplitudes: Amplitudes::Sparse(BTreeMap::from([(0, Complex::new(1.0, 0.0))])),
+            dense_threshold: DEFAULT_DENSE_THRESHOLD,
+        }
+    }
+
+    /// Sets the fraction of nonzero amplitudes above which the state switches to the dense representation.
+    ///
+    /// A threshold of 1 or more keeps the state sparse whatever its filling
+    pub fn with_dense_threshold(mut self, dense_threshold: f64) -> SparseState {
+        self.dense_threshold = dense_threshold;
+        self.switch_if_full();
+        self
+    }
+
+    /// Resets every qubit to |0>, going back to the sparse representation with the same threshold
+    pub(crate) fn reset(&mut self) {
+        *self = SparseState::new(self.qubit_count).with_dense_threshold(self.dense_threshold);
+    }
+
+    /// Returns the number of qubits of the state
+    pub fn get_qubit_count(&self) -> usize {
+        self.qubit_count
+    }
+
+    /// Returns true once the state has switched to the dense representation
+    pub fn is_dense(&self) -> bool {
+        matches!(self.amplitudes, Amplitudes::Dense(_))
+    }
+
+    /// Returns the number of stored amplitudes: the nonzero ones while sparse, 2^n once dense
+    pub fn stored_amplitudes(&self) -> usize {
+        match &self.amplitudes {
+            Amplitudes::Sparse(map) => map.len(),
+            Amplitudes::Dense(state) => 1 << state.get_qubit_count(),
+        }
+    }
+
+    /// Returns the amplitude of a basis state, where bit k-1 of `index` holds qubit k
+    pub fn amplitude(&self, index: usize) -> Complex<f64> {
+        match &self.amplitudes {
+            Amplitudes::Sparse(map) => map.get(&index).copied().unwrap_or_default(),
+            Amplitudes::Dense(state) => state.amplitude(index),
+        }
+    }
+
+    /// Returns the probability of measuring the given basis state
+    pub fn probability(&self, index: usize) -> f64 {
+        self.amplitude(index).norm_sqr()
+    }
+
+    /// Returns the nonzero amplitudes with their basis index, in increasing index order
+    pub fn nonzero_amplitudes(&self) -> Vec<(usize, Complex<f64>)> {
+        match &self.amplitudes {
+            Amplitudes::Sparse(map) => map.iter().map(|(&index, &amplitude)| (index, amplitude)).collect(),
+            Amplitudes::Dense(state) => state.amplitudes().into_iter().enumerate()
+                .filter(|(_, amplitude)| amplitude.norm_sqr() > 0.0)
+                .collect(),
+        }
+    }
+
+    /// Converts the state to a dense `State`, allocating all 2^n amplitudes
+    pub fn to_state(&self) -> State {
+        match &self.amplitudes {
+            Amplitudes::Sparse(map) => {
+                let mut amplitudes = vec![Complex::new(0.0, 0.0); 1 << self.qubit_count];
+                for (&index, &amplitude) in map {
+                    amplitudes[index] = amplitude;
+                }
+                State::from_amplitudes(amplitudes)
+            }
+            Amplitudes::Dense(state) => state.clone(),
+        }
+    }
+
+    /// Returns the total probability of the state
+    pub fn norm_sqr(&self) -> f64 {
+        match &self.amplitudes {
+            Amplitudes::Sparse(map) => map.values().map(|amplitude| amplitude.norm_sqr()).sum(),
+            Amplitudes::Dense(state) => state.norm_sqr(),
+        }
+    }
+
+    /// Checks that every qubit index is between 1 and the number of qubits and appears only once
+    pub fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_qubits(qubits, self.qubit_count)
+    }
+
+    /// Applies the given gate to the specified ordered list of qubits.
+    ///
+    /// Panics if the qubits are invalid or a user-supplied matrix is not unitary
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        expect_ok(self.try_apply(gate, qubits));
+    }
+
+    /// Fallible version of [`SparseState::apply`], returning an error instead of panicking.
+    pub fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_gate(gate, qubits, self.qubit_count, DEFAULT_TOLERANCE)?;
+
+        match &mut self.amplitudes {
+            Amplitudes::Sparse(_) => self.apply_sparse(&gate.matrix(), qubits),
+            Amplitudes::Dense(state) => state.apply(gate, qubits),
+        }
+
+        Ok(())
+    }
+
+    /// Applies a 2^k x 2^k unitary to the specified ordered list of k qubits.
+    ///
+    /// The matrix is written in the basis of the given qubits like in `State::apply_unitary`. Panics if the
+    /// matrix does not fit the qubits or is not unitary
+    pub fn apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        expect_ok(self.try_apply_unitary(gate, qubits));
+    }
+
+    /// Fallible version of [`SparseState::apply_unitary`], returning an error instead of panicking.
+    pub fn try_apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) -> Result<(), QuriustError> {
+        self.try_apply(&Gate::Unitary(gate.clone()), qubits)
+    }
+
+    /// Applies a matrix to the sparse amplitudes, then switches to the dense representation if they filled up
+    fn apply_sparse(&mut self, operator: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        if let Amplitudes::Sparse(map) = &mut self.amplitudes {
+            *map = apply_to_map(map, operator, qubits);
+        }
+        self.switch_if_full();
+    }
+
+    /// Moves the amplitudes to a dense `State` once more than `dense_threshold` of them are nonzero
+    fn switch_if_full(&mut self) {
+        if let Amplitudes::Sparse(map) = &self.amplitudes {
+            if map.len() as f64 > self.dense_threshold * (1u64 << self.qubit_count) as f64 {
+                self.amplitudes = Amplitudes::Dense(self.to_state());
+            }
+        }
+    }
+
+    /// Returns the expectation value ⟨ψ|O|ψ⟩ of an observable acting on the given ordered qubits.
+    ///
+    /// The observable is written in the basis of its qubits like in `State::expectation`
+    pub fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        match &self.amplitudes {
+            Amplitudes::Sparse(map) => {
+                assert_eq!(observable.shape(), (1 << qubits.len(), 1 << qubits.len()), "an observable on {} qubits must be a {}x{} matrix", qubits.len(), 1 << qubits.len(), 1 << qubits.len());
+                expect_ok(self.check_qubits(qubits));
+
+                apply_to_map(map, observable, qubits).iter()
+                    .filter_map(|(index, value)| map.get(index).map(|amplitude| amplitude.conj() * value))
+                    .sum::<Complex<f64>>().re
+            }
+            Amplitudes::Dense(state) => state.expectation(observable, qubits),
+        }
+    }
+
+    /// Samples basis states from the probability distribution of the state, without collapsing it.
+    ///
+    /// Like `State::sample`, every shot is a binary search on the cumulative distribution, which only runs over
+    /// the nonzero amplitudes while the state is sparse
+    pub fn sample<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        let map = match &self.amplitudes {
+            Amplitudes::Sparse(map) => map,
+            Amplitudes::Dense(state) => return state.sample(shots, rng),
+        };
+
+        let mut indices = Vec::with_capacity(map.len());
+        let mut cumulative = Vec::with_capacity(map.len());
+        let mut total = 0.0;
+        for (&index, amplitude) in map {
+            total += amplitude.norm_sqr();
+            indices.push(index);
+            cumulative.push(total);
+        }
+
+        (0..shots)
+            .map(|_| {
+                let rand_num: f64 = rng.gen::<f64>() * total;
+                indices[cumulative.partition_point(|&cum| cum <= rand_num).min(cumulative.len() - 1)]
+            })
+            .collect()
+    }
+
+    /// Projectively measures the given qubits, collapsing the state onto the observed outcome.
+    ///
+    /// # Returns
+    ///
+    /// The observed bits, in the same order as `qubits`.
+    pub fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        expect_ok(self.check_qubits(qubits));
+
+        let index = self.sample(1, rng)[0];
+        let bits: Vec<usize> = qubits.iter().map(|&qubit| (index >> (qubit - 1)) & 1).collect();
+        self.collapse(qubits, &bits);
+
+        bits
+    }
+
+    /// Projects the state onto the outcome `bits` of the given qubits and renormalises it.
+    ///
+    /// # Returns
+    ///
+    /// The probability the outcome had before the projection. The state is left untouched when it is zero.
+    pub fn collapse(&mut self, qubits: &[usize], bits: &[usize]) -> f64 {
+        let map = match &mut self.amplitudes {
+            Amplitudes::Sparse(map) => map,
+            Amplitudes::Dense(state) => return state.collapse(qubits, bits),
+        };
+        assert_eq!(qubits.len(), bits.len(), "every measured qubit needs exactly one outcome bit");
+
+        let (mask, value) = qubits.iter().zip(bits).fold((0, 0), |(mask, value), (&qubit, &bit)| {
+            (mask | 1 << (qubit - 1), value | (bit & 1) << (qubit - 1))
+        });
+
+        let total: f64 = map.values().map(|amplitude| amplitude.norm_sqr()).sum();
+        let probability: f64 = map.iter()
+            .filter(|&(index, _)| index & mask == value)
+            .map(|(_, amplitude)| amplitude.norm_sqr())
+            .sum();
+
+        if probability > 0.0 {
+            let scale = (total / probability).sqrt();
+            map.retain(|index, _| index & mask == value);
+            for amplitude in map.values_mut() {
+                *amplitude *= scale;
+            }
+        }
+
+        probability / total
+    }
+}
+
+/// Applies a matrix to a map of amplitudes, one populated group of basis states differing only in the target
+/// qubits at a time, and returns the nonzero results
+fn apply_to_map(map: &BTreeMap<usize, Complex<f64>>, operator: &DMatrix<Complex<f64>>, qubits: &[usize]) -> BTreeMap<usize, Complex<f64>> {
+    let count = qubits.len();
+    let dimension = 1 << count;
+    let mask = qubits.iter().fold(0, |mask, &qubit| mask | 1 << (qubit - 1));
+    // The first listed qubit is the most significant bit of the matrix index
+    let scatter = |local: usize| {
+        qubits.iter().enumerate().fold(0, |index, (j, &qubit)| index | ((local >> (count - 1 - j)) & 1) << (qubit - 1))
+    };
+    let gather = |index: usize| {
+        qubits.iter().enumerate().fold(0, |local, (j, &qubit)| local | ((index >> (qubit - 1)) & 1) << (count - 1 - j))
+    };
+
+    let mut groups: BTreeMap<usize, Vec<Complex<f64>>> = BTreeMap::new();
+    for (&index, &amplitude) in map {
+        groups.entry(index & !mask).or_insert_with(|| vec![Complex::new(0.0, 0.0); dimension])[gather(index)] = amplitude;
+    }
+
+    let mut result = BTreeMap::new();
+    for (base, values) in groups {
+        for row in 0..dimension {
+            let amplitude: Complex<f64> = values.iter().enumerate()
+                .filter(|(_, value)| value.norm_sqr() > 0.0)
+                .map(|(column, value)| operator[(row, column)] * value)
+                .sum();
+            if amplitude.norm_sqr() > SPARSE_CUTOFF {
+                result.insert(base | scatter(row), amplitude);
+            }
+        }
+    }
+
+    result
+}
+
+#[test]
+fn gates_match_state_vector_test() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let qubit_count = 5;
+    let mut sparse = SparseState::new(qubit_count).with_dense_threshold(1.0);
+
+    let circuit = [
+        (Gate::H, vec![2]),
+        (Gate::X, vec![4]),
+        (Gate::Cnot, vec![2, 5]),
+        (Gate::Ccx, vec![5, 4, 1]),
+        (Gate::Ry(0.6), vec![3]),
+        (Gate::Sdg, vec![5]),
+        (Gate::ISwap, vec![3, 1]),
+        (Gate::CSwap, vec![1, 2, 4]),
+        (Gate::Rxx(0.3), vec![5, 3]),
+        (Gate::Controlled { gate: gates::hadamard(), controls: 2 }, vec![2, 3, 1]),
+        (Gate::Unitary(kronecker_product(&gates::sqrt_x(), &gates::t())), vec![4, 2]),
+        (Gate::H, vec![3]),
+    ];
+    let zx = kronecker_product(&gates::pauli_z(), &gates::pauli_x());
+    let assert_matches = |sparse: &SparseState, state: &State| backend::assert_amplitudes_match(|index| sparse.amplitude(index), state);
+    let mut state = backend::assert_gates_match_state(&mut sparse, &circuit, &[(zx, vec![3, 1])], assert_matches);
+
+    assert!(!sparse.is_dense());
+    assert!(sparse.stored_amplitudes() < 1 << qubit_count);
+    assert!((sparse.norm_sqr() - 1.0).abs() < 1e-10);
+    assert_eq!(sparse.to_state().amplitudes().len(), 1 << qubit_count);
+
+    let bits = sparse.measure_qubits(&[1, 4], &mut rng);
+    state.collapse(&[1, 4], &bits);
+    assert_matches(&sparse, &state);
+
+    assert_eq!(sparse.try_apply(&Gate::Swap, &[2, 2]), Err(QuriustError::InvalidQubit { qubit: 2, qubit_count }));
+    assert!(matches!(sparse.try_apply(&Gate::Unitary(gates::pauli_z() * Complex::new(0.5, 0.0)), &[1]), Err(QuriustError::NonUnitaryGate { .. })));
+}
+
+#[test]
+fn wide_sparse_state_test() {
+    let mut rng = StdRng::seed_from_u64(2);
+
+    // A GHZ state and an adder-like permutation on 40 qubits only ever populate two basis states
+    let qubit_count = 40;
+    let mut sparse = SparseState::new(qubit_count);
+    sparse.apply(&Gate::H, &[1]);
+    for qubit in 2..=qubit_count {
+        sparse.apply(&Gate::Cnot, &[qubit - 1, qubit]);
+    }
+    for qubit in (3..=qubit_count).step_by(3) {
+        sparse.apply(&Gate::Ccx, &[qubit - 2, qubit - 1, qubit]);
+    }
+
+    assert!(!sparse.is_dense());
+    assert_eq!(sparse.stored_amplitudes(), 2);
+    assert!((sparse.probability(0) - 0.5).abs() < 1e-12);
+
+    let samples = sparse.sample(100, &mut rng);
+    assert!(samples.iter().all(|&index| index == 0 || sparse.probability(index) > 0.49));
+    let bit = sparse.measure_qubits(&[17], &mut rng)[0];
+    assert_eq!(sparse.stored_amplitudes(), 1);
+    assert_eq!(sparse.measure_qubits(&[1], &mut rng), vec![bit]);
+    assert_eq!(sparse.reset_qubit(1, &mut rng), bit);
+}
+
+#[test]
+fn dense_switch_test() {
+    let mut sparse = SparseState::new(4).with_dense_threshold(0.5);
+    let mut state = State::from_cr(&ClassicalRegister::zeros(16));
+    for qubit in 1..=4 {
+        sparse.apply(&Gate::H, &[qubit]);
+        state.apply(&Gate::H, &[qubit]);
+        assert_eq!(sparse.is_dense(), qubit == 4);
+    }
+
+    sparse.apply(&Gate::Cz, &[1, 3]);
+    state.apply(&Gate::Cz, &[1, 3]);
+    backend::assert_amplitudes_match(|index| sparse.amplitude(index), &state);
+    assert_eq!(sparse.stored_amplitudes(), 16);
+    assert_eq!(sparse.nonzero_amplitudes().len(), 16);
+}
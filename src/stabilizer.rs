@@ -0,0 +1,506 @@
+use super::backend;
+#[cfg(test)]
+use super::backend::Backend;
+use super::error::{expect_ok, QuriustError};
+use super::gates::{self, Gate};
+use super::registers::ClassicalRegister;
+use super::state::{kronecker_product, DEFAULT_TOLERANCE};
+#[cfg(test)]
+use super::state::State;
+use nalgebra::DMatrix;
+use num_complex::Complex;
+use rand::Rng;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Represents a single-qubit Pauli operator, used to build Pauli products to measure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+/// Represents a stabilizer state with the Aaronson-Gottesman tableau.
+///
+/// The state of n qubits is described by n stabilizer and n destabilizer Pauli generators, each stored as
+/// n X bits, n Z bits and a sign bit, so memory grows as O(n²) instead of O(2^n) and hundreds of qubits are
+/// cheap. Only Clifford gates (H, S, X, Y, Z, CNOT, CZ, SWAP and their products) keep a state stabilizer, so
+/// no other gate can be applied. Qubits are indexed from 1, like in `State`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StabilizerState {
+    qubit_count: usize,
+    /// X bits of the 2n generators plus one scratch row, row-major
+    x: Vec<bool>,
+    /// Z bits of the 2n generators plus one scratch row, row-major
+    z: Vec<bool>,
+    /// Sign bits, set for a -1 phase
+    r: Vec<bool>,
+}
+
+impl StabilizerState {
+    /// Creates the stabilizer state |0...0> on the specified number of qubits
+    pub fn new(qubit_count: usize) -> StabilizerState {
+        let rows = 2 * qubit_count + 1;
+        let mut state = StabilizerState {
+            qubit_count,
+            x: vec![false; rows * qubit_count],
+            z: vec![false; rows * qubit_count],
+            r: vec![false; rows],
+        };
+
+        // Destabilizer i is X_i and stabilizer i is Z_i
+        for i in 0..qubit_count {
+            state.x[i * qubit_count + i] = true;
+            state.z[(qubit_count + i) * qubit_count + i] = true;
+        }
+
+        state
+    }
+
+    /// Returns the number of qubits of the state
+    pub fn get_qubit_count(&self) -> usize {
+        self.qubit_count
+    }
+
+    /// Checks that every qubit index is between 1 and the number of qubits and appears only once
+    pub fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_qubits(qubits, self.qubit_count)
+    }
+
+    /// Index of the bit of `qubit` (1-based) in generator `row`
+    fn bit(&self, row: usize, qubit: usize) -> usize {
+        row * self.qubit_count + qubit - 1
+    }
+
+    /// Returns true if the gate can be simulated on a stabilizer state
+    pub fn supports(gate: &Gate) -> bool {
+        matches!(gate, Gate::Id | Gate::X | Gate::Y | Gate::Z | Gate::H | Gate::S | Gate::Sdg | Gate::Cnot | Gate::Cz | Gate::Swap)
+    }
+
+    /// Applies the given Clifford gate to the specified ordered list of qubits.
+    ///
+    /// Panics if the gate is not supported or the qubits are invalid
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        expect_ok(self.try_apply(gate, qubits));
+    }
+
+    /// Fallible version of [`StabilizerState::apply`], returning an error instead of panicking.
+    pub fn try_apply(&mut self, gate: &Gate, qubits: &[usize]) -> Result<(), QuriustError> {
+        if !StabilizerState::supports(gate) {
+            return Err(QuriustError::UnsupportedGate { gate: gate.name().to_string() });
+        }
+        backend::check_gate(gate, qubits, self.qubit_count, DEFAULT_TOLERANCE)?;
+
+        match gate {
+            Gate::Id => {}
+            Gate::X => self.x_gate(qubits[0]),
+            Gate::Y => self.y_gate(qubits[0]),
+            Gate::Z => self.z_gate(qubits[0]),
+            Gate::H => self.h_gate(qubits[0]),
+            Gate::S => self.s_gate(qubits[0]),
+            Gate::Sdg => self.sdg_gate(qubits[0]),
+            Gate::Cnot => self.cnot_gate(qubits[0], qubits[1]),
+            Gate::Cz => self.cz_gate(qubits[0], qubits[1]),
+            Gate::Swap => self.swap_gate(qubits[0], qubits[1]),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// Applies the Hadamard gate, exchanging the X and Z parts of every generator
+    pub fn h_gate(&mut self, qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let index = self.bit(row, qubit);
+            self.r[row] ^= self.x[index] && self.z[index];
+            let (x, z) = (self.x[index], self.z[index]);
+            self.x[index] = z;
+            self.z[index] = x;
+        }
+    }
+
+    /// Applies the phase gate S, mapping X to Y
+    pub fn s_gate(&mut self, qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let index = self.bit(row, qubit);
+            self.r[row] ^= self.x[index] && self.z[index];
+            self.z[index] ^= self.x[index];
+        }
+    }
+
+    /// Applies the inverse phase gate S† = S³
+    pub fn sdg_gate(&mut self, qubit: usize) {
+        self.s_gate(qubit);
+        self.s_gate(qubit);
+        self.s_gate(qubit);
+    }
+
+    /// Applies the Pauli-X gate, flipping the sign of the generators that anticommute with it
+    pub fn x_gate(&mut self, qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let index = self.bit(row, qubit);
+            self.r[row] ^= self.z[index];
+        }
+    }
+
+    /// Applies the Pauli-Y gate, flipping the sign of the generators that anticommute with it
+    pub fn y_gate(&mut self, qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let index = self.bit(row, qubit);
+            self.r[row] ^= self.x[index] ^ self.z[index];
+        }
+    }
+
+    /// Applies the Pauli-Z gate, flipping the sign of the generators that anticommute with it
+    pub fn z_gate(&mut self, qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let index = self.bit(row, qubit);
+            self.r[row] ^= self.x[index];
+        }
+    }
+
+    /// Applies the CNOT gate with the given control and target qubits
+    pub fn cnot_gate(&mut self, control_qubit: usize, target_qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let (control, target) = (self.bit(row, control_qubit), self.bit(row, target_qubit));
+            self.r[row] ^= self.x[control] && self.z[target] && !(self.x[target] ^ self.z[control]);
+            self.x[target] ^= self.x[control];
+            self.z[control] ^= self.z[target];
+        }
+    }
+
+    /// Applies the controlled-Z gate, as H·CNOT·H on the second qubit
+    pub fn cz_gate(&mut self, control_qubit: usize, target_qubit: usize) {
+        self.h_gate(target_qubit);
+        self.cnot_gate(control_qubit, target_qubit);
+        self.h_gate(target_qubit);
+    }
+
+    /// Applies the SWAP gate, exchanging the columns of the two qubits
+    pub fn swap_gate(&mut self, first_qubit: usize, second_qubit: usize) {
+        for row in 0..2 * self.qubit_count {
+            let (first, second) = (self.bit(row, first_qubit), self.bit(row, second_qubit));
+            self.x.swap(first, second);
+            self.z.swap(first, second);
+        }
+    }
+
+    /// Multiplies generator `target` by generator `source`, keeping track of the phase (the rowsum of CHP)
+    fn row_multiply(&mut self, target: usize, source: usize) {
+        // Exponent of i picked up by the product, counted modulo 4
+        let mut phase = 2 * (self.r[target] as i32 + self.r[source] as i32);
+        for qubit in 1..=self.qubit_count {
+            let (t, s) = (self.bit(target, qubit), self.bit(source, qubit));
+            let (x1, z1, x2, z2) = (self.x[s], self.z[s], self.x[t], self.z[t]);
+            phase += match (x1, z1) {
+                (false, false) => 0,
+                (true, true) => z2 as i32 - x2 as i32,
+                (true, false) => z2 as i32 * (2 * x2 as i32 - 1),
+                (false, true) => x2 as i32 * (1 - 2 * z2 as i32),
+            };
+            self.x[t] ^= x1;
+            self.z[t] ^= z1;
+        }
+
+        self.r[target] = phase.rem_euclid(4) == 2;
+    }
+
+    /// Copies generator `source` over generator `target`
+    fn copy_row(&mut self, target: usize, source: usize) {
+        for qubit in 1..=self.qubit_count {
+            let (t, s) = (self.bit(target, qubit), self.bit(source, qubit));
+            self.x[t] = self.x[s];
+            self.z[t] = self.z[s];
+        }
+        self.r[target] = self.r[source];
+    }
+
+    /// Returns a stabilizer generator anticommuting with Z on the qubit, which makes its measurement random
+    fn random_pivot(&self, qubit: usize) -> Option<usize> {
+        (self.qubit_count..2 * self.qubit_count).find(|&row| self.x[self.bit(row, qubit)])
+    }
+
+    /// Returns the outcome of measuring Z on a qubit whose measurement is not random.
+    ///
+    /// ±Z is then a product of stabilizers, accumulated in the scratch row whose sign is the outcome
+    fn determined_outcome(&mut self, qubit: usize) -> usize {
+        let n = self.qubit_count;
+        let scratch = 2 * n;
+        for other in 1..=n {
+            let index = self.bit(scratch, other);
+            self.x[index] = false;
+            self.z[index] = false;
+        }
+        self.r[scratch] = false;
+        for row in 0..n {
+            if self.x[self.bit(row, qubit)] {
+                self.row_multiply(scratch, row + n);
+            }
+        }
+
+        self.r[scratch] as usize
+    }
+
+    /// Measures a qubit in the computational basis, returning the observed bit
+    fn measure_z<R: Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> usize {
+        let n = self.qubit_count;
+        let p = match self.random_pivot(qubit) {
+            Some(p) => p,
+            None => return self.determined_outcome(qubit),
+        };
+
+        // Every other generator anticommuting with Z is multiplied by the pivot, which then becomes ±Z
+        for row in 0..2 * n {
+            if row != p && self.x[self.bit(row, qubit)] {
+                self.row_multiply(row, p);
+            }
+        }
+        self.copy_row(p - n, p);
+        for other in 1..=n {
+            let index = self.bit(p, other);
+            self.x[index] = false;
+            self.z[index] = false;
+        }
+        let outcome = rng.gen::<bool>();
+        let index = self.bit(p, qubit);
+        self.z[index] = true;
+        self.r[p] = outcome;
+
+        outcome as usize
+    }
+
+    /// Projectively measures the given qubits in the computational basis.
+    ///
+    /// # Returns
+    ///
+    /// The observed bits, in the same order as `qubits`.
+    pub fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        expect_ok(self.check_qubits(qubits));
+
+        qubits.iter().map(|&qubit| self.measure_z(qubit, rng)).collect()
+    }
+
+    /// Measures every qubit, returning a classical register where bit k holds qubit k
+    pub fn measure_all<R: Rng + ?Sized>(&mut self, rng: &mut R) -> ClassicalRegister {
+        ClassicalRegister::new((1..=self.qubit_count).rev().map(|qubit| self.measure_z(qubit, rng)).collect())
+    }
+
+    /// Rotates a Pauli product onto Z on its last qubit, returning that qubit, or `None` for the identity.
+    ///
+    /// X and Y factors are turned into Z by H and S†·H, then CNOTs fold the parity of every factor onto the
+    /// last one. `unrotate_pauli` undoes the rotation
+    fn rotate_pauli(&mut self, paulis: &[(usize, Pauli)]) -> Option<usize> {
+        let factors: Vec<(usize, Pauli)> = paulis.iter().copied().filter(|&(_, pauli)| pauli != Pauli::I).collect();
+        for &(qubit, pauli) in &factors {
+            match pauli {
+                Pauli::X => self.h_gate(qubit),
+                Pauli::Y => {
+                    self.sdg_gate(qubit);
+                    self.h_gate(qubit);
+                }
+                _ => {}
+            }
+        }
+
+        let (&(last, _), rest) = factors.split_last()?;
+        for &(qubit, _) in rest {
+            self.cnot_gate(qubit, last);
+        }
+
+        Some(last)
+    }
+
+    /// Undoes `rotate_pauli`
+    fn unrotate_pauli(&mut self, paulis: &[(usize, Pauli)]) {
+        let factors: Vec<(usize, Pauli)> = paulis.iter().copied().filter(|&(_, pauli)| pauli != Pauli::I).collect();
+        if let Some((&(last, _), rest)) = factors.split_last() {
+            for &(qubit, _) in rest.iter().rev() {
+                self.cnot_gate(qubit, last);
+            }
+        }
+        for &(qubit, pauli) in &factors {
+            match pauli {
+                Pauli::X => self.h_gate(qubit),
+                Pauli::Y => {
+                    self.h_gate(qubit);
+                    self.s_gate(qubit);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks the qubits of a Pauli product
+    fn check_pauli(&self, paulis: &[(usize, Pauli)]) -> Result<(), QuriustError> {
+        let qubits: Vec<usize> = paulis.iter().map(|&(qubit, _)| qubit).collect();
+        self.check_qubits(&qubits)
+    }
+
+    /// Measures a Pauli product such as X1·Z3, collapsing the state onto one of its eigenspaces.
+    ///
+    /// # Returns
+    ///
+    /// 0 for the +1 eigenvalue and 1 for the -1 eigenvalue. The identity always gives 0.
+    pub fn measure_pauli<R: Rng + ?Sized>(&mut self, paulis: &[(usize, Pauli)], rng: &mut R) -> usize {
+        expect_ok(self.check_pauli(paulis));
+
+        let outcome = match self.rotate_pauli(paulis) {
+            Some(qubit) => self.measure_z(qubit, rng),
+            None => 0,
+        };
+        self.unrotate_pauli(paulis);
+
+        outcome
+    }
+
+    /// Returns the expectation value of a Pauli product: ±1 if the state is one of its eigenstates, 0 otherwise
+    pub fn pauli_expectation(&self, paulis: &[(usize, Pauli)]) -> f64 {
+        expect_ok(self.check_pauli(paulis));
+
+        let mut rotated = self.clone();
+        let qubit = match rotated.rotate_pauli(paulis) {
+            Some(qubit) => qubit,
+            None => return 1.0,
+        };
+
+        if rotated.random_pivot(qubit).is_some() {
+            return 0.0;
+        }
+
+        1.0 - 2.0 * rotated.determined_outcome(qubit) as f64
+    }
+
+    /// Returns the expectation value ⟨ψ|O|ψ⟩ of an observable acting on the given ordered qubits.
+    ///
+    /// The observable is written in the basis of its qubits like in `State::expectation`, and is expanded on
+    /// the 4^k Pauli products of its k qubits, each evaluated with `pauli_expectation`
+    pub fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        let dimension = 1 << qubits.len();
+        assert_eq!(observable.shape(), (dimension, dimension), "an observable on {} qubits must be a {}x{} matrix", qubits.len(), dimension, dimension);
+        expect_ok(self.check_qubits(qubits));
+
+        let paulis = [(Pauli::I, gates::identity()), (Pauli::X, gates::pauli_x()), (Pauli::Y, gates::pauli_y()), (Pauli::Z, gates::pauli_z())];
+        let mut value = 0.0;
+        for product in 0..1usize << (2 * qubits.len()) {
+            // Two bits of `product` pick the Pauli of every qubit, the first qubit being the leftmost factor
+            let mut matrix = DMatrix::from_element(1, 1, Complex::new(1.0, 0.0));
+            let mut factors = Vec::with_capacity(qubits.len());
+            for (j, &qubit) in qubits.iter().enumerate() {
+                let (pauli, pauli_matrix) = &paulis[(product >> (2 * (qubits.len() - 1 - j))) & 3];
+                matrix = kronecker_product(&matrix, pauli_matrix);
+                factors.push((qubit, *pauli));
+            }
+
+            let coefficient = (matrix.adjoint() * observable).trace().re / dimension as f64;
+            if coefficient != 0.0 {
+                value += coefficient * self.pauli_expectation(&factors);
+            }
+        }
+
+        value
+    }
+
+    /// Samples the measurement of every qubit many times, returning the outcome of every shot in order
+    pub(crate) fn sample_outcomes<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<ClassicalRegister> {
+        (0..shots).map(|_| self.clone().measure_all(rng)).collect()
+    }
+}
+
+
+#[cfg(test)]
+fn random_clifford_circuit<R: Rng>(qubit_count: usize, length: usize, rng: &mut R) -> Vec<(Gate, Vec<usize>)> {
+    let single = [Gate::H, Gate::S, Gate::Sdg, Gate::X, Gate::Y, Gate::Z];
+    let double = [Gate::Cnot, Gate::Cz, Gate::Swap];
+
+    (0..length).map(|_| {
+        let first = rng.gen_range(1..=qubit_count);
+        if rng.gen_bool(0.5) {
+            (single[rng.gen_range(0..single.len())].clone(), vec![first])
+        } else {
+            let second = (first + rng.gen_range(1..qubit_count) - 1) % qubit_count + 1;
+            (double[rng.gen_range(0..double.len())].clone(), vec![first, second])
+        }
+    }).collect()
+}
+
+#[test]
+fn deterministic_states_test() {
+    let mut rng = StdRng::seed_from_u64(1);
+
+    let mut state = StabilizerState::new(3);
+    state.apply(&Gate::X, &[1]);
+    state.apply(&Gate::Swap, &[1, 3]);
+    assert_eq!(state.measure_all(&mut rng), ClassicalRegister::new(vec![1, 0, 0]));
+
+    // A GHZ state on hundreds of qubits always measures all zeros or all ones
+    let qubit_count = 300;
+    let mut ghz = StabilizerState::new(qubit_count);
+    ghz.h_gate(1);
+    for qubit in 2..=qubit_count {
+        ghz.cnot_gate(qubit - 1, qubit);
+    }
+    assert_eq!(ghz.pauli_expectation(&[(1, Pauli::Z), (qubit_count, Pauli::Z)]), 1.0);
+    assert_eq!(ghz.pauli_expectation(&[(1, Pauli::Z)]), 0.0);
+    let all_x: Vec<(usize, Pauli)> = (1..=qubit_count).map(|qubit| (qubit, Pauli::X)).collect();
+    assert_eq!(ghz.pauli_expectation(&all_x), 1.0);
+
+    for _ in 0..5 {
+        let bits = ghz.clone().measure_all(&mut rng).bits();
+        assert!(bits.iter().all(|&bit| bit == bits[0]));
+    }
+
+    assert_eq!(state.try_apply(&Gate::T, &[1]), Err(QuriustError::UnsupportedGate { gate: "t".to_string() }));
+    assert_eq!(state.try_apply(&Gate::Cnot, &[1, 4]), Err(QuriustError::InvalidQubit { qubit: 4, qubit_count: 3 }));
+}
+
+#[test]
+fn pauli_measurement_test() {
+    let mut rng = StdRng::seed_from_u64(2);
+
+    // |+> is the +1 eigenstate of X, and Y measurements after S give the same answer
+    let mut state = StabilizerState::new(2);
+    state.h_gate(1);
+    assert_eq!(state.measure_pauli(&[(1, Pauli::X)], &mut rng), 0);
+    state.s_gate(1);
+    assert_eq!(state.pauli_expectation(&[(1, Pauli::Y)]), 1.0);
+    assert_eq!(state.measure_pauli(&[(1, Pauli::Y)], &mut rng), 0);
+
+    // Measuring X1·X2 on |00> projects onto a Bell state, after which Z1·Z2 and X1·X2 are both fixed
+    let mut state = StabilizerState::new(2);
+    let outcome = state.measure_pauli(&[(1, Pauli::X), (2, Pauli::X)], &mut rng);
+    assert_eq!(state.pauli_expectation(&[(1, Pauli::Z), (2, Pauli::Z)]), 1.0);
+    assert_eq!(state.pauli_expectation(&[(1, Pauli::X), (2, Pauli::X)]), 1.0 - 2.0 * outcome as f64);
+    assert_eq!(state.measure_pauli(&[(1, Pauli::X), (2, Pauli::X)], &mut rng), outcome);
+    assert_eq!(state.measure_pauli(&[(2, Pauli::I)], &mut rng), 0);
+}
+
+#[test]
+fn statistics_match_state_vector_test() {
+    let mut rng = StdRng::seed_from_u64(3);
+
+    for _ in 0..20 {
+        let circuit = random_clifford_circuit(3, 25, &mut rng);
+        let mut stabilizer = StabilizerState::new(3);
+        let mut state = State::from_cr(&ClassicalRegister::zeros(8));
+        for (gate, qubits) in circuit.iter() {
+            stabilizer.apply(gate, qubits);
+            state.apply(gate, qubits);
+        }
+
+        let shots = 800;
+        let counts = stabilizer.sample_counts(shots, &mut rng);
+        for (index, probability) in state.probabilities().iter().enumerate() {
+            let observed = *counts.get(&ClassicalRegister::from_value(3, index as u32)).unwrap_or(&0) as f64 / shots as f64;
+            assert!((observed - probability).abs() < 0.08);
+            if *probability < 1e-12 {
+                assert_eq!(observed, 0.0);
+            }
+        }
+
+        let z1z3 = kronecker_product(&gates::pauli_z(), &gates::pauli_z());
+        assert!((stabilizer.pauli_expectation(&[(1, Pauli::Z), (3, Pauli::Z)]) - state.expectation(&z1z3, &[1, 3])).abs() < 1e-9);
+        assert!((stabilizer.pauli_expectation(&[(2, Pauli::X)]) - state.expectation(&gates::pauli_x(), &[2])).abs() < 1e-9);
+    }
+}
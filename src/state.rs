@@ -1,165 +1,938 @@
 use num_complex::Complex;
-use super::registers::{ClassicalRegister, QuantumRegister};
+use super::backend;
+use super::error::{expect_ok, QuriustError};
+use super::gates::{self, Gate};
+use super::noise::KrausChannel;
+use super::registers::ClassicalRegister;
+#[cfg(test)]
+use super::registers::QuantumRegister;
 
-use nalgebra::{DMatrix, DVector};
+use nalgebra::{DMatrix, RealField};
+#[cfg(test)]
+use nalgebra::DVector;
+use rand::Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(test)]
+use std::cell::Cell;
+use std::iter::Sum;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
 
-/// Represents the state of a quantum system, defined by a vector of complex amplitudes
+/// A floating-point type the amplitudes of a `State` can be stored in.
+///
+/// Implemented for `f64`, the default, and `f32`, which halves the memory of a state. Gate matrices and angles
+/// stay in f64 and are rounded to the scalar once per gate, while probabilities and expectation values are
+/// accumulated and returned in f64
+pub trait Real: RealField + Copy {
+    /// Tolerance of the unitarity and norm checks run on states stored in this type
+    const TOLERANCE: f64;
+
+    /// Rounds an f64 to this type
+    fn from_double(value: f64) -> Self;
+
+    /// Converts this value to an f64
+    fn to_double(self) -> f64;
+}
+
+impl Real for f32 {
+    const TOLERANCE: f64 = 1e-5;
+
+    fn from_double(value: f64) -> f32 {
+        value as f32
+    }
+
+    fn to_double(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Real for f64 {
+    const TOLERANCE: f64 = DEFAULT_TOLERANCE;
+
+    fn from_double(value: f64) -> f64 {
+        value
+    }
+
+    fn to_double(self) -> f64 {
+        self
+    }
+}
+
+/// Represents the state of a quantum system, defined by a vector of complex amplitudes.
+///
+/// The amplitudes are `Complex<f64>` unless another `Real` scalar is picked, e.g. `State::<f32>::basis_state(&cr)`
 #[derive(Debug, Clone)]
-pub struct State{
-    amplitudes: Vec<Complex<f64>>
+pub struct State<T: Real = f64>{
+    amplitudes: Vec<Complex<T>>
 }
 
 impl State{
      /// Creates a new quantum state with the specified number of amplitudes, initialized to zero
     pub fn new(n: usize)-> State{
-        State{amplitudes: vec![Complex{re: 0.0, im: 0.0}; n]}
+        State::zeros(n)
     }
 
     /// Creates a quantum state from a classical register with all amplitudes set to zero
     /// except the amplitude corresponding to the value of the classical register, which is set to one
     pub fn from_cr(cr: &ClassicalRegister) -> State{
-        let mut state = State::new(cr.len());
-        state.amplitudes[cr.value() as usize] = Complex{re: 1.0, im: 0.0};
-        
+        State::basis_state(cr)
+    }
+
+    /// Creates a new quantum state with the specified number of amplitudes, initialized to zero.
+    ///
+    /// Returns an error if the number of amplitudes is not a power of two
+    pub fn try_new(n: usize) -> Result<State, QuriustError> {
+        if !n.is_power_of_two() {
+            return Err(QuriustError::DimensionMismatch { expected: n.next_power_of_two(), found: n });
+        }
+
+        Ok(State::new(n))
+    }
+
+    /// Creates a quantum state from a classical register, like `from_cr`.
+    ///
+    /// Returns an error if the register length is not a power of two or its value does not fit in it
+    pub fn try_from_cr(cr: &ClassicalRegister) -> Result<State, QuriustError> {
+        let mut state = State::try_new(cr.len())?;
+        let index = cr.value() as usize;
+        if index >= cr.len() {
+            return Err(QuriustError::DimensionMismatch { expected: cr.len(), found: index + 1 });
+        }
+        state.amplitudes[index] = Complex{re: 1.0, im: 0.0};
+
+        Ok(state)
+    }
+}
+
+impl<T: Real> State<T>{
+    /// Creates a new quantum state of any precision with the specified number of amplitudes, initialized to zero
+    pub fn zeros(n: usize) -> State<T> {
+        State{amplitudes: vec![Complex::new(T::zero(), T::zero()); n]}
+    }
+
+    /// Creates a quantum state of any precision from a classical register, like `from_cr`
+    pub fn basis_state(cr: &ClassicalRegister) -> State<T> {
+        let mut state = State::zeros(cr.len());
+        state.amplitudes[cr.value() as usize] = Complex::new(T::one(), T::zero());
+
         state
     }
 
     /// Returns the amplitudes of the quantum state    
-    pub fn amplitudes(&self) -> Vec<Complex<f64>>{
+    pub fn amplitudes(&self) -> Vec<Complex<T>>{
         self.amplitudes.clone()
     }
 
+    /// Creates a quantum state from its amplitudes, where bit k-1 of the index holds qubit k
+    pub(crate) fn from_amplitudes(amplitudes: Vec<Complex<T>>) -> State<T> {
+        State { amplitudes }
+    }
+
+    /// Returns the amplitude of a single basis state
+    pub fn amplitude(&self, index: usize) -> Complex<T> {
+        self.amplitudes[index]
+    }
+
     /// Returns the number of qubits represented by the quantum state
     pub fn get_qubit_count(&self) -> usize {
         (self.amplitudes.len() as f64).log2() as usize
     }
 
+    /// Returns the probability of every basis state, i.e. the squared norms of the amplitudes
+    pub fn probabilities(&self) -> Vec<f64> {
+        #[cfg(feature = "parallel")]
+        if runs_parallel(self.amplitudes.len()) {
+            return self.amplitudes.par_iter().map(|amplitude| amplitude.norm_sqr().to_double()).collect();
+        }
+
+        self.amplitudes.iter().map(|amplitude| amplitude.norm_sqr().to_double()).collect()
+    }
+
+    /// Samples basis states from the probability distribution of the state, without collapsing it.
+    ///
+    /// The cumulative distribution is built once, then every shot is a binary search on it, so drawing many
+    /// shots costs far less than simulating the state again. All the randomness is drawn from `rng`, so a
+    /// seeded generator gives reproducible samples
+    pub fn sample<R: Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Vec<usize> {
+        sample_distribution(&self.probabilities(), shots, rng)
+    }
+
+    /// Checks that every qubit index is between 1 and the number of qubits and appears only once
+    pub fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuriustError> {
+        backend::check_qubits(qubits, self.get_qubit_count())
+    }
+
     /// Applies the Pauli-X gate (NOT gate) to the specified target qubit.
     ///
     /// The Pauli-X gate flips the state of the target qubit
     pub fn pauli_x_gate(&mut self, target_qubit: usize){
-        let matr = vec![
-            Complex::new(0.0, 0.0), Complex::new(1.0, 0.0),
-            Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
-        ];
-
-        let pauli_x_matrix = DMatrix::<Complex<f64>>::from_row_slice(2,2,&matr);
-        self.apply_gate_to_qubit(pauli_x_matrix, target_qubit);
-
+        self.apply_gate_to_qubit(gates::pauli_x(), target_qubit);
     }
 
     /// Applies the Pauli-Y gate to the specified target qubit.
     ///
     /// The Pauli-Y gate introduces a phase flip if the qubit is in the |1⟩ state
     pub fn pauli_y_gate(&mut self, target_qubit: usize) {
-        let matr = vec![
-            Complex::new(0.0, 0.0), Complex::new(0.0, -1.0),
-            Complex::new(0.0, 1.0), Complex::new(0.0, 0.0),
-        ];
-    
-        let pauli_y_matrix = DMatrix::<Complex<f64>>::from_row_slice(2, 2, &matr);
-        self.apply_gate_to_qubit(pauli_y_matrix, target_qubit);
-
+        self.apply_gate_to_qubit(gates::pauli_y(), target_qubit);
     }
 
     /// Applies the Pauli-Z gate to the specified target qubit.
     ///
     /// The Pauli-Z gate introduces a phase flip iif the qubit is in the |1⟩ state  
     pub fn pauli_z_gate(&mut self, target_qubit: usize) {
-        let matr = vec![
-            Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
-            Complex::new(0.0, 0.0), Complex::new(-1.0, 0.0),
-        ];
-    
-        let pauli_z_matrix = DMatrix::<Complex<f64>>::from_row_slice(2, 2, &matr);
-        self.apply_gate_to_qubit(pauli_z_matrix, target_qubit);
+        self.apply_gate_to_qubit(gates::pauli_z(), target_qubit);
     }  
 
     /// Applies the Hadamard gate to the specified target qubit.
     ///
     /// The Hadamard gate creates superposition by putting the qubit in a state of equal probability of |0⟩ and |1⟩
     pub fn hadamard_gate(&mut self, target_qubit: usize) {
-        let q = 1.0 / (2.0 as f64).sqrt();
-        let matr = vec![
-            Complex::new(q, 0.0),
-            Complex::new(q, 0.0),
-            Complex::new(q, 0.0),
-            Complex::new(-q, 0.0),
-        ];
-        let hadamard_matrix = DMatrix::<Complex<f64>>::from_row_slice(2, 2, &matr);
+        self.apply_gate_to_qubit(gates::hadamard(), target_qubit);
+    }
+
+    /// Applies the identity gate to the specified target qubit.
+    ///
+    /// The identity gate leaves the state unchanged
+    pub fn identity_gate(&mut self, target_qubit: usize) {
+        self.apply_gate_to_qubit(gates::identity(), target_qubit);
+    }
+
+    /// Applies the S gate to the specified target qubit.
+    ///
+    /// The S gate multiplies the |1⟩ component by i, i.e. it is a quarter turn around the Z-axis
+    pub fn s_gate(&mut self, target_qubit: usize) {
+        self.apply_gate_to_qubit(gates::s(), target_qubit);
+    }
+
+    /// Applies the S† gate to the specified target qubit.
+    ///
+    /// The S† gate is the inverse of the S gate and multiplies the |1⟩ component by -i
+    pub fn s_dagger_gate(&mut self, target_qubit: usize) {
+        self.apply_gate_to_qubit(gates::s_dagger(), target_qubit);
+    }
+
+    /// Applies the T gate to the specified target qubit.
+    ///
+    /// The T gate multiplies the |1⟩ component by e^(iπ/4)
+    pub fn t_gate(&mut self, target_qubit: usize) {
+        self.apply_gate_to_qubit(gates::t(), target_qubit);
+    }
+
+    /// Applies the T† gate to the specified target qubit.
+    ///
+    /// The T† gate is the inverse of the T gate and multiplies the |1⟩ component by e^(-iπ/4)
+    pub fn t_dagger_gate(&mut self, target_qubit: usize) {
+        self.apply_gate_to_qubit(gates::t_dagger(), target_qubit);
+    }
+
+    /// Applies the √X gate to the specified target qubit.
+    ///
+    /// Applying the √X gate twice is equivalent to a Pauli-X gate
+    pub fn sqrt_x_gate(&mut self, target_qubit: usize) {
+        self.apply_gate_to_qubit(gates::sqrt_x(), target_qubit);
+    }
 
-        self.apply_gate_to_qubit(hadamard_matrix, target_qubit);
+    /// Applies the phase gate P(λ) to the specified target qubit.
+    ///
+    /// The phase gate multiplies the |1⟩ component by e^(iλ)
+    pub fn phase_gate(&mut self, target_qubit: usize, lambda: f64) {
+        self.apply_gate_to_qubit(gates::phase(lambda), target_qubit);
+    }
+
+    /// Applies a rotation by `theta` around the X-axis to the specified target qubit
+    pub fn rx_gate(&mut self, target_qubit: usize, theta: f64) {
+        self.apply_gate_to_qubit(gates::rx(theta), target_qubit);
+    }
+
+    /// Applies a rotation by `theta` around the Y-axis to the specified target qubit
+    pub fn ry_gate(&mut self, target_qubit: usize, theta: f64) {
+        self.apply_gate_to_qubit(gates::ry(theta), target_qubit);
+    }
+
+    /// Applies a rotation by `theta` around the Z-axis to the specified target qubit
+    pub fn rz_gate(&mut self, target_qubit: usize, theta: f64) {
+        self.apply_gate_to_qubit(gates::rz(theta), target_qubit);
+    }
+
+    /// Applies the general single-qubit gate U3(θ, φ, λ) to the specified target qubit.
+    ///
+    /// Any single-qubit unitary can be written as U3 up to a global phase
+    pub fn u3_gate(&mut self, target_qubit: usize, theta: f64, phi: f64, lambda: f64) {
+        self.apply_gate_to_qubit(gates::u3(theta, phi, lambda), target_qubit);
     }
     
+    /// Applies the given gate to the specified ordered list of qubits.
+    ///
+    /// Every gate goes through its dedicated kernel, so this is the same as calling the named method. Panics if
+    /// the number of qubits does not match the gate
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        assert_eq!(qubits.len(), gate.num_qubits(), "gate {} acts on {} qubits", gate.name(), gate.num_qubits());
+
+        match gate {
+            Gate::Id => self.identity_gate(qubits[0]),
+            Gate::X => self.pauli_x_gate(qubits[0]),
+            Gate::Y => self.pauli_y_gate(qubits[0]),
+            Gate::Z => self.pauli_z_gate(qubits[0]),
+            Gate::H => self.hadamard_gate(qubits[0]),
+            Gate::S => self.s_gate(qubits[0]),
+            Gate::Sdg => self.s_dagger_gate(qubits[0]),
+            Gate::T => self.t_gate(qubits[0]),
+            Gate::Tdg => self.t_dagger_gate(qubits[0]),
+            Gate::Sx => self.sqrt_x_gate(qubits[0]),
+            Gate::P(lambda) => self.phase_gate(qubits[0], *lambda),
+            Gate::Rx(theta) => self.rx_gate(qubits[0], *theta),
+            Gate::Ry(theta) => self.ry_gate(qubits[0], *theta),
+            Gate::Rz(theta) => self.rz_gate(qubits[0], *theta),
+            Gate::U3(theta, phi, lambda) => self.u3_gate(qubits[0], *theta, *phi, *lambda),
+            Gate::Cnot => self.cnot_gate(qubits[0], qubits[1]),
+            Gate::Cz => self.cz_gate(qubits[0], qubits[1]),
+            Gate::CPhase(phi) => self.controlled_phase_gate(qubits[0], qubits[1], *phi),
+            Gate::Swap => self.swap_gate(qubits[0], qubits[1]),
+            Gate::SqrtSwap => self.sqrt_swap_gate(qubits[0], qubits[1]),
+            Gate::ISwap => self.iswap_gate(qubits[0], qubits[1]),
+            Gate::Rxx(theta) => self.rxx_gate(qubits[0], qubits[1], *theta),
+            Gate::Ryy(theta) => self.ryy_gate(qubits[0], qubits[1], *theta),
+            Gate::Rzz(theta) => self.rzz_gate(qubits[0], qubits[1], *theta),
+            Gate::FSim(theta, phi) => self.fsim_gate(qubits[0], qubits[1], *theta, *phi),
+            Gate::Ccx => self.toffoli_gate(qubits[0], qubits[1], qubits[2]),
+            Gate::CSwap => self.fredkin_gate(qubits[0], qubits[1], qubits[2]),
+            Gate::Controlled { gate, .. } => {
+                let (target_qubit, controls) = qubits.split_last().unwrap();
+                let controls: Vec<Control> = controls.iter().map(|&qubit| Control::Positive(qubit)).collect();
+                self.apply_controlled_gate(gate.clone(), &controls, *target_qubit);
+            }
+            Gate::Unitary(matrix) => self.apply_unitary(matrix, qubits),
+        }
+    }
+
     /// Applies a quantum gate to the specified target qubit.
     ///
-    /// This method applies the given gate to the target qubit using the Kronecker product method. Note that you can use this
+    /// This method applies the given 2x2 gate in place by walking the pairs of amplitudes that differ only in
+    /// the target qubit, so it needs O(2^n) time and no extra memory. Note that you can use this
     /// method also for user-defined gates
     pub fn apply_gate_to_qubit(&mut self, gate: DMatrix<Complex<f64>>, target_qubit: usize) {
+        self.apply_controlled_gate(gate, &[], target_qubit);
+    } 
+
+    /// Applies a quantum gate to the specified target qubit, conditioned on a set of control qubits.
+    ///
+    /// The gate only acts on the amplitude pairs whose control bits match every control: positive controls
+    /// must be in the |1⟩ state and negative controls in the |0⟩ state. Like `apply_gate_to_qubit`, this
+    /// works in place and accepts user-defined 2x2 gates
+    pub fn apply_controlled_gate(&mut self, gate: DMatrix<Complex<f64>>, controls: &[Control], target_qubit: usize) {
         let qubit_count = self.get_qubit_count();
         assert!(qubit_count >= target_qubit);
         assert!(target_qubit!=0);
+        assert_eq!(gate.shape(), (2, 2));
+
+        let (control_mask, control_value) = self.control_mask(controls, &[target_qubit]);
+        let gate = gate.map(scalar::<T>);
+        let (g00, g01, g10, g11) = (gate[(0, 0)], gate[(0, 1)], gate[(1, 0)], gate[(1, 1)]);
+        let stride = 1 << (target_qubit - 1);
 
-        let mut full_gate = DMatrix::identity(2, 2);
+        for_each_pair(&mut self.amplitudes, stride, |index, a0, a1| {
+            if index & control_mask != control_value {
+                return;
+            }
 
-        for i in 1..=qubit_count {
-            let current_gate = if i == target_qubit {
-                gate.clone() // Apply the provided gate if the current qubit is the target qubit
-            } else {
-                DMatrix::identity(2, 2) // Identity gate for other qubits
-            };
+            let (v0, v1) = (*a0, *a1);
+            *a0 = g00 * v0 + g01 * v1;
+            *a1 = g10 * v0 + g11 * v1;
+        });
+    }
 
-            full_gate = if i == 1 {
-                current_gate.clone() // Assign the gate to the first qubit directly
-            } else {
-                kronecker_product(&current_gate, &full_gate) // Apply Kronecker product for subsequent qubits
-            };
+    /// Applies a 2^k x 2^k unitary to the specified ordered list of k qubits.
+    ///
+    /// The matrix is written in the basis |q1 q2 ... qk⟩ of the given qubits, so the first qubit in the list is
+    /// the most significant bit of the matrix index. The update gathers the 2^k amplitudes that differ only in
+    /// the target qubits, multiplies them by the matrix and scatters them back, so the full 2^n operator is
+    /// never built. Panics if the matrix dimensions do not match the number of qubits
+    pub fn apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize]) {
+        let qubit_count = self.get_qubit_count();
+        assert!(!qubits.is_empty(), "at least one target qubit is required");
+        for (i, &qubit) in qubits.iter().enumerate() {
+            assert!(qubit != 0 && qubit <= qubit_count, "qubit {} is out of range", qubit);
+            assert!(!qubits[..i].contains(&qubit), "qubit {} is repeated", qubit);
         }
 
-        self.apply_gate(full_gate);
-    } 
-    
-    /// Applies a quantum gate to the entire quantum state.
+        let dimension = 1 << qubits.len();
+        assert_eq!(gate.shape(), (dimension, dimension), "a gate on {} qubits must be a {}x{} matrix", qubits.len(), dimension, dimension);
+
+        apply_matrix(&mut self.amplitudes, &gate.map(scalar), qubits);
+    }
+
+    /// Applies a 2^k x 2^k unitary to the specified ordered list of k qubits, validating it first.
+    ///
+    /// The matrix dimensions are always checked. If a `tolerance` is given, the matrix is also rejected when
+    /// it is not unitary within that tolerance, and the total probability of the state is checked after the
+    /// update. A rejected gate leaves the state untouched
+    pub fn try_apply_unitary(&mut self, gate: &DMatrix<Complex<f64>>, qubits: &[usize], tolerance: Option<f64>) -> Result<(), QuriustError> {
+        if qubits.is_empty() {
+            return Err(QuriustError::DimensionMismatch { expected: gate.nrows(), found: 0 });
+        }
+        self.check_qubits(qubits)?;
+
+        let dimension = 1 << qubits.len();
+        if gate.nrows() != dimension || gate.ncols() != dimension {
+            let found = if gate.nrows() != dimension { gate.nrows() } else { gate.ncols() };
+            return Err(QuriustError::DimensionMismatch { expected: dimension, found });
+        }
+
+        match tolerance {
+            Some(tolerance) => self.checked(gate, tolerance, |state| state.apply_unitary(gate, qubits)),
+            None => {
+                self.apply_unitary(gate, qubits);
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies a quantum gate to the specified target qubit, validating it first.
+    ///
+    /// This is the checked counterpart of `apply_gate_to_qubit`: the gate must be 2x2 and, if a `tolerance` is
+    /// given, unitary within that tolerance and norm-preserving on this state
+    pub fn try_apply_gate_to_qubit(&mut self, gate: DMatrix<Complex<f64>>, target_qubit: usize, tolerance: Option<f64>) -> Result<(), QuriustError> {
+        self.try_apply_controlled_gate(gate, &[], target_qubit, tolerance)
+    }
+
+    /// Applies a quantum gate to the specified target qubit, conditioned on a set of control qubits, validating it first.
     ///
-    /// This method applies the given gate to the entire quantum state vector
-    fn apply_gate(&mut self, gate: DMatrix<Complex<f64>>){
-        let ampl = DVector::<Complex<f64>>::from_iterator(self.amplitudes.len(), self.amplitudes.clone().into_iter());
+    /// This is the checked counterpart of `apply_controlled_gate`: the target and control qubits must be valid and
+    /// distinct, the gate must be 2x2 and, if a `tolerance` is given, unitary within that tolerance and
+    /// norm-preserving on this state
+    pub fn try_apply_controlled_gate(&mut self, gate: DMatrix<Complex<f64>>, controls: &[Control], target_qubit: usize, tolerance: Option<f64>) -> Result<(), QuriustError> {
+        let mut qubits: Vec<usize> = controls.iter().map(Control::qubit).collect();
+        qubits.push(target_qubit);
+        self.check_qubits(&qubits)?;
 
-        let new_amplitudes = gate * ampl;
-        self.amplitudes = new_amplitudes.as_slice().to_vec();
+        if gate.nrows() != 2 || gate.ncols() != 2 {
+            let found = if gate.nrows() != 2 { gate.nrows() } else { gate.ncols() };
+            return Err(QuriustError::DimensionMismatch { expected: 2, found });
+        }
+
+        match tolerance {
+            Some(tolerance) => self.checked(&gate.clone(), tolerance, |state| state.apply_controlled_gate(gate, controls, target_qubit)),
+            None => {
+                self.apply_controlled_gate(gate, controls, target_qubit);
+                Ok(())
+            }
+        }
+    }
+
+    /// Runs `update` only if `gate` is unitary within `tolerance`, then checks that the total probability
+    /// did not drift by more than `tolerance`, restoring the previous amplitudes if it did
+    fn checked<F: FnOnce(&mut State<T>)>(&mut self, gate: &DMatrix<Complex<f64>>, tolerance: f64, update: F) -> Result<(), QuriustError> {
+        let deviation = unitarity_deviation(gate);
+        if deviation > tolerance {
+            return Err(QuriustError::NonUnitaryGate { deviation, tolerance });
+        }
+
+        let before = self.norm_sqr();
+        let previous = self.amplitudes.clone();
+        update(self);
+        let after = self.norm_sqr();
+
+        if (after - before).abs() > tolerance {
+            self.amplitudes = previous;
+            return Err(QuriustError::NormDrift { before, after, tolerance });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the total probability of the state, i.e. the sum of the squared norms of the amplitudes
+    pub fn norm_sqr(&self) -> f64 {
+        let amplitudes = &self.amplitudes;
+        blocked_sum(amplitudes.len(), |index| amplitudes[index].norm_sqr().to_double())
+    }
+
+    /// Returns the expectation value ⟨ψ|O|ψ⟩ of an observable acting on the given ordered qubits.
+    ///
+    /// The observable is written in the basis of its qubits like the matrix of `apply_unitary`, and should be
+    /// Hermitian: only the real part of the result is returned
+    pub fn expectation(&self, observable: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+        assert_eq!(observable.shape(), (1 << qubits.len(), 1 << qubits.len()), "an observable on {} qubits must be a {}x{} matrix", qubits.len(), 1 << qubits.len(), 1 << qubits.len());
+        expect_ok(self.check_qubits(qubits));
+
+        quadratic_form(&self.amplitudes, observable, qubits)
+    }
+
+    /// Projectively measures the given qubits, collapsing the state onto the observed outcome.
+    ///
+    /// Only the amplitudes consistent with the outcome survive, renormalised, so the unmeasured qubits keep
+    /// their conditional state and the computation can go on.
+    ///
+    /// # Returns
+    ///
+    /// The observed bits, in the same order as `qubits`.
+    pub fn measure_qubits<R: Rng + ?Sized>(&mut self, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        expect_ok(self.check_qubits(qubits));
+
+        let index = self.sample(1, rng)[0];
+        let bits: Vec<usize> = qubits.iter().map(|&qubit| (index >> (qubit - 1)) & 1).collect();
+        self.collapse(qubits, &bits);
+
+        bits
+    }
+
+    /// Applies a noise channel stochastically, as one step of a quantum trajectory.
+    ///
+    /// One Kraus operator K is picked with probability ‖Kψ‖² and the state becomes Kψ/‖Kψ‖, so that averaging
+    /// over many trajectories reproduces the action of the channel on the density matrix. Like
+    /// `DensityMatrix::apply_channel`, a single-qubit channel acts independently on every listed qubit.
+    ///
+    /// # Returns
+    ///
+    /// The index of the Kraus operator picked for every group of qubits the channel acted on.
+    pub fn apply_channel<R: Rng + ?Sized>(&mut self, channel: &KrausChannel, qubits: &[usize], rng: &mut R) -> Vec<usize> {
+        expect_ok(self.try_apply_channel(channel, qubits, rng))
+    }
+
+    /// Fallible version of [`State::apply_channel`], returning an error instead of panicking.
+    pub fn try_apply_channel<R: Rng + ?Sized>(&mut self, channel: &KrausChannel, qubits: &[usize], rng: &mut R) -> Result<Vec<usize>, QuriustError> {
+        self.check_qubits(qubits)?;
+
+        let mut branches = Vec::new();
+        for targets in channel.placements(qubits)? {
+            let total = self.norm_sqr();
+            let rand_num: f64 = rng.gen::<f64>() * total;
+            let mut cumulative = 0.0;
+            let mut chosen = None;
+
+            // The weight ‖Kψ‖² of every branch is ⟨ψ|K†K|ψ⟩, so only the chosen operator is ever applied
+            for (branch, operator) in channel.operators().iter().enumerate() {
+                let weight = quadratic_form(&self.amplitudes, &(operator.adjoint() * operator), targets);
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                cumulative += weight;
+                chosen = Some((branch, operator, weight));
+                if rand_num < cumulative {
+                    break;
+                }
+            }
+
+            // Rounding can leave the draw just above the last cumulative weight: the last branch is kept then
+            if let Some((branch, operator, weight)) = chosen {
+                apply_matrix(&mut self.amplitudes, &operator.map(scalar), targets);
+                let scale = T::from_double((total / weight).sqrt());
+                for_each_chunk(&mut self.amplitudes, SUM_BLOCK, |_, chunk| {
+                    for amplitude in chunk {
+                        *amplitude *= scale;
+                    }
+                });
+                branches.push(branch);
+            }
+        }
+
+        Ok(branches)
+    }
+
+    /// Projects the state onto the outcome `bits` of the given qubits and renormalises it.
+    ///
+    /// # Returns
+    ///
+    /// The probability the outcome had before the projection. The state is left untouched when it is zero.
+    pub fn collapse(&mut self, qubits: &[usize], bits: &[usize]) -> f64 {
+        assert_eq!(qubits.len(), bits.len(), "every measured qubit needs exactly one outcome bit");
+
+        let (mask, value) = qubits.iter().zip(bits).fold((0, 0), |(mask, value), (&qubit, &bit)| {
+            (mask | 1 << (qubit - 1), value | (bit & 1) << (qubit - 1))
+        });
+
+        let total = self.norm_sqr();
+        let amplitudes = &self.amplitudes;
+        let probability = blocked_sum(amplitudes.len(), |index| {
+            if index & mask == value { amplitudes[index].norm_sqr().to_double() } else { 0.0 }
+        });
+
+        if probability > 0.0 {
+            let scale = T::from_double((total / probability).sqrt());
+            for_each_chunk(&mut self.amplitudes, SUM_BLOCK, |base, chunk| {
+                for (offset, amplitude) in chunk.iter_mut().enumerate() {
+                    if (base + offset) & mask == value {
+                        *amplitude *= scale;
+                    } else {
+                        *amplitude = Complex::new(T::zero(), T::zero());
+                    }
+                }
+            });
+        }
+
+        probability / total
+    }
+
+    /// Computes the bit mask of the control qubits and the value those bits must take for the controls to fire.
+    ///
+    /// Every control contributes one bit to the mask, and positive controls also to the expected value. The
+    /// controls must be distinct, valid qubits and must not overlap with the target qubits
+    fn control_mask(&self, controls: &[Control], target_qubits: &[usize]) -> (usize, usize) {
+        let qubit_count = self.get_qubit_count();
+        let mut control_mask = 0;
+        let mut control_value = 0;
+
+        for control in controls {
+            let qubit = control.qubit();
+            assert!(qubit != 0 && qubit <= qubit_count);
+            assert!(!target_qubits.contains(&qubit));
+
+            let bit = 1 << (qubit - 1);
+            assert_eq!(control_mask & bit, 0);
+            control_mask |= bit;
+            if let Control::Positive(_) = control {
+                control_value |= bit;
+            }
+        }
+
+        (control_mask, control_value)
     }
 
     /// Applies the Controlled-NOT (CNOT) gate to the specified control and target qubits.
     ///
     /// The CNOT gate flips the target qubit if and only if the control qubit is in the |1⟩ state
     pub fn cnot_gate(&mut self, control_qubit: usize, target_qubit: usize) {
-        let num_qubits = (self.amplitudes.len() as f32).log2() as usize;
-        assert!(control_qubit > 0 && target_qubit > 0 && control_qubit <= num_qubits && target_qubit <= num_qubits);
-
-        let mut cnot_gate = DMatrix::<Complex<f64>>::identity(1 << num_qubits, 1 << num_qubits);
-
-        for i in 0..(1 << num_qubits) {
-            let bit_i = 1 << i;
-
-            if (i & bit_i) == bit_i {
-                let gate = if i & (1 << (control_qubit - 1)) != 0 && i & (1 << (target_qubit - 1)) == 0 {
-                    // Apply CNOT gate
-                    DMatrix::<Complex<f64>>::from_row_slice(2, 2, &[
-                        Complex::new(1.0, 0.0), Complex::new(0.0, 0.0),
-                        Complex::new(0.0, 0.0), Complex::new(1.0, 0.0),
-                    ])
-                } else {
-                    // Apply identity gate
-                    DMatrix::<Complex<f64>>::identity(2, 2)
-                };
-
-                cnot_gate = kronecker_product(&cnot_gate, &gate);
+        self.apply_controlled_gate(gates::pauli_x(), &[Control::Positive(control_qubit)], target_qubit);
+    }
+
+    /// Applies the Toffoli (CCNOT) gate to the specified control and target qubits.
+    ///
+    /// The Toffoli gate flips the target qubit if and only if both control qubits are in the |1⟩ state
+    pub fn toffoli_gate(&mut self, first_control: usize, second_control: usize, target_qubit: usize) {
+        let controls = [Control::Positive(first_control), Control::Positive(second_control)];
+        self.apply_controlled_gate(gates::pauli_x(), &controls, target_qubit);
+    }
+
+    /// Applies the Controlled-Z (CZ) gate to the specified control and target qubits.
+    ///
+    /// The CZ gate flips the phase of the |11⟩ component, so control and target are interchangeable
+    pub fn cz_gate(&mut self, control_qubit: usize, target_qubit: usize) {
+        self.controlled_phase_gate(control_qubit, target_qubit, std::f64::consts::PI);
+    }
+
+    /// Applies the controlled-phase gate with angle `phi` to the specified control and target qubits.
+    ///
+    /// The controlled-phase gate multiplies the |11⟩ component by e^(i*phi)
+    pub fn controlled_phase_gate(&mut self, control_qubit: usize, target_qubit: usize, phi: f64) {
+        self.apply_controlled_gate(gates::phase(phi), &[Control::Positive(control_qubit)], target_qubit);
+    }
+
+    /// Applies the SWAP gate to the specified pair of qubits.
+    ///
+    /// The SWAP gate exchanges the states of the two qubits
+    pub fn swap_gate(&mut self, first_qubit: usize, second_qubit: usize) {
+        self.apply_controlled_swap(&[], first_qubit, second_qubit);
+    }
+
+    /// Applies the Fredkin (controlled-SWAP) gate to the specified control qubit and pair of target qubits.
+    ///
+    /// The Fredkin gate exchanges the states of the two target qubits if and only if the control qubit is in the |1⟩ state
+    pub fn fredkin_gate(&mut self, control_qubit: usize, first_qubit: usize, second_qubit: usize) {
+        self.apply_controlled_swap(&[Control::Positive(control_qubit)], first_qubit, second_qubit);
+    }
+
+    /// Exchanges the states of two qubits on the amplitudes whose control bits match every control
+    fn apply_controlled_swap(&mut self, controls: &[Control], first_qubit: usize, second_qubit: usize) {
+        let qubit_count = self.get_qubit_count();
+        assert!(first_qubit != 0 && second_qubit != 0 && first_qubit <= qubit_count && second_qubit <= qubit_count);
+        assert!(first_qubit != second_qubit);
+
+        let (control_mask, control_value) = self.control_mask(controls, &[first_qubit, second_qubit]);
+        let first_bit = 1 << (first_qubit - 1);
+        let second_bit = 1 << (second_qubit - 1);
+
+        // Only |01⟩ and |10⟩ move, so it is enough to swap those two amplitudes of every group. Chunks aligned on
+        // the highest of the two bits hold whole groups
+        let chunk_len = (first_bit.max(second_bit) << 1).max(SUM_BLOCK);
+        for_each_chunk(&mut self.amplitudes, chunk_len, |base, chunk| {
+            for i in 0..chunk.len() {
+                if i & (first_bit | second_bit) == first_bit && (base + i) & control_mask == control_value {
+                    chunk.swap(i, i ^ first_bit ^ second_bit);
+                }
             }
+        });
+    }
+
+    /// Applies the √SWAP gate to the specified pair of qubits.
+    ///
+    /// Applying the √SWAP gate twice is equivalent to a SWAP gate
+    pub fn sqrt_swap_gate(&mut self, first_qubit: usize, second_qubit: usize) {
+        self.apply_unitary(&gates::sqrt_swap(), &[first_qubit, second_qubit]);
+    }
+
+    /// Applies the iSWAP gate to the specified pair of qubits.
+    ///
+    /// The iSWAP gate exchanges the states of the two qubits and multiplies |01⟩ and |10⟩ by i
+    pub fn iswap_gate(&mut self, first_qubit: usize, second_qubit: usize) {
+        self.apply_unitary(&gates::iswap(), &[first_qubit, second_qubit]);
+    }
+
+    /// Applies the Ising XX coupling RXX(θ) to the specified pair of qubits
+    pub fn rxx_gate(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        self.apply_unitary(&gates::rxx(theta), &[first_qubit, second_qubit]);
+    }
+
+    /// Applies the Ising YY coupling RYY(θ) to the specified pair of qubits
+    pub fn ryy_gate(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        self.apply_unitary(&gates::ryy(theta), &[first_qubit, second_qubit]);
+    }
+
+    /// Applies the Ising ZZ coupling RZZ(θ) to the specified pair of qubits.
+    ///
+    /// RZZ(θ) is diagonal, so it only rephases the amplitudes according to the parity of the two qubits
+    pub fn rzz_gate(&mut self, first_qubit: usize, second_qubit: usize, theta: f64) {
+        let qubit_count = self.get_qubit_count();
+        assert!(first_qubit != 0 && second_qubit != 0 && first_qubit <= qubit_count && second_qubit <= qubit_count);
+        assert!(first_qubit != second_qubit);
+
+        let first_bit = 1 << (first_qubit - 1);
+        let second_bit = 1 << (second_qubit - 1);
+        let even = scalar(Complex::from_polar(1.0, -theta / 2.0));
+        let odd = scalar(Complex::from_polar(1.0, theta / 2.0));
+
+        for_each_chunk(&mut self.amplitudes, SUM_BLOCK, |base, chunk| {
+            for (offset, amplitude) in chunk.iter_mut().enumerate() {
+                let i = base + offset;
+                let parity = (i & first_bit != 0) != (i & second_bit != 0);
+                *amplitude *= if parity { odd } else { even };
+            }
+        });
+    }
+
+    /// Applies the fSim(θ, φ) gate to the specified pair of qubits.
+    ///
+    /// The fSim gate rotates |01⟩ and |10⟩ into each other by θ and multiplies |11⟩ by e^(-iφ)
+    pub fn fsim_gate(&mut self, first_qubit: usize, second_qubit: usize, theta: f64, phi: f64) {
+        self.apply_unitary(&gates::fsim(theta, phi), &[first_qubit, second_qubit]);
+    }
+}
+
+/// A condition on a control qubit, identified by its index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// The gate acts only when the qubit is in the |1⟩ state
+    Positive(usize),
+    /// The gate acts only when the qubit is in the |0⟩ state
+    Negative(usize),
+}
+
+impl Control {
+    /// Returns the index of the control qubit
+    pub fn qubit(&self) -> usize {
+        match *self {
+            Control::Positive(qubit) | Control::Negative(qubit) => qubit,
         }
-        
+    }
+}
+
+/// Applies a 2^k x 2^k matrix to the given ordered qubits of an amplitude vector, in place.
+///
+/// The first qubit in the list is the most significant bit of the matrix index. Only two buffers of 2^k
+/// amplitudes are allocated per chunk of the vector, whatever its size
+pub(crate) fn apply_matrix<T: Real>(amplitudes: &mut [Complex<T>], gate: &DMatrix<Complex<T>>, qubits: &[usize]) {
+    let k = qubits.len();
+    let dimension = 1 << k;
+
+    // offsets[l] is the distance from the group base to the amplitude whose target bits spell l
+    let bits: Vec<usize> = qubits.iter().map(|&qubit| 1 << (qubit - 1)).collect();
+    let mask = bits.iter().fold(0, |mask, bit| mask | bit);
+    let offsets: Vec<usize> = (0..dimension)
+        .map(|l| (0..k).filter(|j| l & (1 << (k - 1 - j)) != 0).map(|j| bits[j]).sum())
+        .collect();
 
-        self.apply_gate(cnot_gate);
+    // Chunks aligned on the highest target bit hold whole groups
+    let chunk_len = (bits.iter().max().unwrap() << 1).max(SUM_BLOCK);
+    for_each_chunk(amplitudes, chunk_len, |_, chunk| {
+        let mut values = vec![Complex::new(T::zero(), T::zero()); dimension];
+        let mut updated = vec![Complex::new(T::zero(), T::zero()); dimension];
+
+        for base in 0..chunk.len() {
+            if base & mask != 0 {
+                continue;
+            }
+
+            for (value, offset) in values.iter_mut().zip(offsets.iter()) {
+                *value = chunk[base + offset];
+            }
+            for (row, new_value) in updated.iter_mut().enumerate() {
+                *new_value = values.iter().enumerate().map(|(col, value)| gate[(row, col)] * value).sum();
+            }
+            for (new_value, offset) in updated.iter().zip(offsets.iter()) {
+                chunk[base + offset] = *new_value;
+            }
+        }
+    });
+}
+
+/// Draws `shots` indices from a distribution given by its probabilities, which need not be normalised.
+///
+/// The cumulative distribution is built once and every shot is a binary search on it, drawing one number from `rng`
+pub(crate) fn sample_distribution<R: Rng + ?Sized>(probabilities: &[f64], shots: usize, rng: &mut R) -> Vec<usize> {
+    let mut cumulative = Vec::with_capacity(probabilities.len());
+    let mut total = 0.0;
+    for probability in probabilities {
+        total += probability;
+        cumulative.push(total);
+    }
+
+    (0..shots)
+        .map(|_| {
+            let rand_num: f64 = rng.gen::<f64>() * total;
+            cumulative.partition_point(|&cum| cum <= rand_num).min(cumulative.len() - 1)
+        })
+        .collect()
+}
+
+/// Returns ⟨ψ|M|ψ⟩ for a matrix acting on the given ordered qubits, without copying the amplitudes.
+///
+/// Every group of 2^k amplitudes differing only in the target qubits contributes v†Mv, read in place; only
+/// the real part is returned
+fn quadratic_form<T: Real>(amplitudes: &[Complex<T>], matrix: &DMatrix<Complex<f64>>, qubits: &[usize]) -> f64 {
+    let k = qubits.len();
+    let dimension = 1 << k;
+
+    let bits: Vec<usize> = qubits.iter().map(|&qubit| 1 << (qubit - 1)).collect();
+    let offsets: Vec<usize> = (0..dimension)
+        .map(|l| (0..k).filter(|j| l & (1 << (k - 1 - j)) != 0).map(|j| bits[j]).sum())
+        .collect();
+    let mut sorted_bits = bits.clone();
+    sorted_bits.sort_unstable();
+
+    blocked_sum(amplitudes.len() >> k, |group| {
+        // Spreads the group index over the bits of the other qubits, leaving the target bits at zero
+        let base = sorted_bits.iter().fold(group, |base, &bit| (base & (bit - 1)) | ((base & !(bit - 1)) << 1));
+        let value = |l: usize| {
+            let amplitude = amplitudes[base + offsets[l]];
+            Complex::new(amplitude.re.to_double(), amplitude.im.to_double())
+        };
+
+        (0..dimension)
+            .map(|row| {
+                let column_sum: Complex<f64> = (0..dimension).map(|col| matrix[(row, col)] * value(col)).sum();
+                (value(row).conj() * column_sum).re
+            })
+            .sum::<f64>()
+    })
+}
+
+/// Number of qubits from which the kernels of `State` split their work across threads, when the `parallel`
+/// feature is enabled
+pub const PARALLEL_THRESHOLD: usize = 14;
+
+/// Number of consecutive terms added in order before the partial sums are combined. The serial and the threaded
+/// paths share the same blocks, so their sums are identical bit for bit
+const SUM_BLOCK: usize = 1 << 12;
+
+/// Returns true if the kernels should split a vector of `len` amplitudes across threads
+#[cfg(feature = "parallel")]
+fn runs_parallel(len: usize) -> bool {
+    #[cfg(test)]
+    if FORCE_SERIAL.with(Cell::get) {
+        return false;
     }
+
+    len >= 1 << PARALLEL_THRESHOLD
+}
+
+#[cfg(all(test, feature = "parallel"))]
+thread_local! {
+    /// Lets the tests run the serial kernels on large vectors, to compare them with the threaded ones
+    static FORCE_SERIAL: Cell<bool> = const { Cell::new(false) };
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of passes the kernels made over a vector, so that the tests can count the passes of a computation
+    pub(crate) static PASSES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Sums `term(i)` for every index below `len`, one block of `SUM_BLOCK` terms at a time
+pub(crate) fn blocked_sum<T, F>(len: usize, term: F) -> T
+where
+    T: Send + Sum<T>,
+    F: Fn(usize) -> T + Sync,
+{
+    #[cfg(test)]
+    PASSES.with(|passes| passes.set(passes.get() + 1));
+    let block_sum = |block: usize| (block * SUM_BLOCK..len.min((block + 1) * SUM_BLOCK)).map(&term).sum::<T>();
+    let blocks = len.div_ceil(SUM_BLOCK);
+
+    #[cfg(feature = "parallel")]
+    if runs_parallel(len) {
+        let partial_sums: Vec<T> = (0..blocks).into_par_iter().map(block_sum).collect();
+        return partial_sums.into_iter().sum();
+    }
+
+    (0..blocks).map(block_sum).sum()
+}
+
+/// Calls `update(base, chunk)` on consecutive chunks of `chunk_len` amplitudes, `base` being the index of the
+/// first amplitude of the chunk
+fn for_each_chunk<T: Real, F>(amplitudes: &mut [Complex<T>], chunk_len: usize, update: F)
+where
+    F: Fn(usize, &mut [Complex<T>]) + Sync,
+{
+    #[cfg(test)]
+    PASSES.with(|passes| passes.set(passes.get() + 1));
+    #[cfg(feature = "parallel")]
+    if runs_parallel(amplitudes.len()) {
+        amplitudes.par_chunks_mut(chunk_len).enumerate().for_each(|(chunk_index, chunk)| update(chunk_index * chunk_len, chunk));
+        return;
+    }
+
+    amplitudes.chunks_mut(chunk_len).enumerate().for_each(|(chunk_index, chunk)| update(chunk_index * chunk_len, chunk));
+}
+
+/// Calls `update(i, a_i, a_j)` on every pair of amplitudes whose indices i < j differ only in the bit `stride`.
+///
+/// Every block of 2*stride amplitudes holds `stride` pairs (i, i + stride) with the bit at 0 and 1. Small blocks
+/// are spread across threads, while the pairs of large blocks are split among them
+fn for_each_pair<T: Real, F>(amplitudes: &mut [Complex<T>], stride: usize, update: F)
+where
+    F: Fn(usize, &mut Complex<T>, &mut Complex<T>) + Sync,
+{
+    #[cfg(test)]
+    PASSES.with(|passes| passes.set(passes.get() + 1));
+    let update_block = |block_index: usize, block: &mut [Complex<T>]| {
+        let base = block_index * 2 * stride;
+        let (zeros, ones) = block.split_at_mut(stride);
+        for (offset, (a0, a1)) in zeros.iter_mut().zip(ones.iter_mut()).enumerate() {
+            update(base + offset, a0, a1);
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    if runs_parallel(amplitudes.len()) {
+        if stride < SUM_BLOCK {
+            amplitudes.par_chunks_mut(2 * stride).enumerate().for_each(|(block_index, block)| update_block(block_index, block));
+        } else {
+            for (block_index, block) in amplitudes.chunks_mut(2 * stride).enumerate() {
+                let base = block_index * 2 * stride;
+                let (zeros, ones) = block.split_at_mut(stride);
+                zeros.par_iter_mut().zip(ones.par_iter_mut()).enumerate().for_each(|(offset, (a0, a1))| update(base + offset, a0, a1));
+            }
+        }
+        return;
+    }
+
+    amplitudes.chunks_mut(2 * stride).enumerate().for_each(|(block_index, block)| update_block(block_index, block));
+}
+
+/// Rounds a complex number computed in f64 to the scalar of a state
+fn scalar<T: Real>(value: Complex<f64>) -> Complex<T> {
+    Complex::new(T::from_double(value.re), T::from_double(value.im))
+}
+
+/// Default tolerance used when checking that gates are unitary and preserve the norm of the state
+pub const DEFAULT_TOLERANCE: f64 = 1e-10;
+
+/// Returns how far a square matrix is from being unitary, as the largest entry of |U†U - I|
+pub fn unitarity_deviation(gate: &DMatrix<Complex<f64>>) -> f64 {
+    let product = gate.adjoint() * gate;
+    let identity = DMatrix::<Complex<f64>>::identity(gate.nrows(), gate.ncols());
+
+    (product - identity).iter().map(|entry| entry.norm()).fold(0.0, f64::max)
 }
 
 /// Calculates the Kronecker product of two matrices
@@ -273,6 +1046,7 @@ fn pauli_z_test() {
 
 
 #[test]
+#[allow(clippy::unnecessary_cast)]
 fn hadamard_test() {
     let q = 1.0/(2.0 as f64).sqrt();
 
@@ -294,13 +1068,14 @@ fn hadamard_test() {
 
 
 #[test]
+#[allow(unused_variables)]
 fn cnot_test() {
     let cr1 = ClassicalRegister::new(vec![0,0,0,0]);
     let mut qr1: QuantumRegister = QuantumRegister::new(&cr1);
     qr1.x(1);
     qr1.cnot(1, 2);
     let qr1_state = qr1.state();
-    assert_eq!(qr1_state, vec![Complex { re: 0.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }]);
+    assert_eq!(qr1_state, vec![Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 }]);
 
 
     let cr2 = ClassicalRegister::new(vec![0,0,0,0,0,0,0,0]);
@@ -310,3 +1085,549 @@ fn cnot_test() {
     let qr2_state = qr2.state();
     assert_eq!(qr2_state, vec![Complex { re: 1.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }]);
 }
+
+
+/// Builds the full 2^n x 2^n operator of a single-qubit gate with the Kronecker product method,
+/// used as a reference for the in-place kernel
+#[cfg(test)]
+fn kronecker_reference(gate: &DMatrix<Complex<f64>>, target_qubit: usize, qubit_count: usize) -> DMatrix<Complex<f64>> {
+    let mut full_gate = DMatrix::identity(1, 1);
+
+    for i in 1..=qubit_count {
+        let current_gate = if i == target_qubit { gate.clone() } else { DMatrix::identity(2, 2) };
+        full_gate = kronecker_product(&current_gate, &full_gate);
+    }
+
+    full_gate
+}
+
+#[test]
+fn strided_kernel_matches_kronecker_test() {
+    let gate = DMatrix::from_row_slice(2, 2, &[
+        Complex::new(0.6, 0.0), Complex::new(0.0, 0.8),
+        Complex::new(0.0, 0.8), Complex::new(0.6, 0.0),
+    ]);
+
+    for qubit_count in 1..=4 {
+        let len = 1 << qubit_count;
+        let amplitudes: Vec<Complex<f64>> = (0..len).map(|i| Complex::new(i as f64 + 1.0, 0.5 * i as f64)).collect();
+
+        for target_qubit in 1..=qubit_count {
+            let mut state = State{amplitudes: amplitudes.clone()};
+            state.apply_gate_to_qubit(gate.clone(), target_qubit);
+
+            let expected = kronecker_reference(&gate, target_qubit, qubit_count) * DVector::from_vec(amplitudes.clone());
+
+            for (a, b) in state.amplitudes().iter().zip(expected.iter()) {
+                assert!((a - b).norm() < 1e-12);
+            }
+        }
+    }
+}
+
+#[test]
+fn bell_state_test() {
+    let q = 1.0/2.0_f64.sqrt();
+
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.h(1);
+    qr.cnot(1, 2);
+    assert_eq!(qr.state(), vec![Complex { re: q, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, Complex { re: q, im: 0.0 }]);
+
+    // |Ψ+⟩ = (|01⟩ + |10⟩)/sqrt(2), built from a flipped target
+    let mut qr: QuantumRegister = QuantumRegister::init(2);
+    qr.h(2);
+    qr.x(1);
+    qr.cnot(2, 1);
+    assert_eq!(qr.state(), vec![Complex { re: 0.0, im: 0.0 }, Complex { re: q, im: 0.0 }, Complex { re: q, im: 0.0 }, Complex { re: 0.0, im: 0.0 }]);
+}
+
+#[test]
+fn ghz_state_test() {
+    let q = 1.0/2.0_f64.sqrt();
+
+    let mut qr: QuantumRegister = QuantumRegister::init(3);
+    qr.h(1);
+    qr.cnot(1, 2);
+    qr.cnot(2, 3);
+
+    let mut expected = vec![Complex::new(0.0, 0.0); 8];
+    expected[0] = Complex::new(q, 0.0);
+    expected[7] = Complex::new(q, 0.0);
+    assert_eq!(qr.state(), expected);
+}
+
+#[test]
+fn negative_control_test() {
+    let x = gates::pauli_x();
+
+    // Qubit 2 is |1⟩, qubit 1 is |0⟩: only the negative control on qubit 1 and positive control on qubit 2 fire
+    let mut state = State::from_cr(&ClassicalRegister::from_value(8, 2));
+    state.apply_controlled_gate(x.clone(), &[Control::Negative(1), Control::Positive(2)], 3);
+    assert_eq!(state.amplitudes()[6], Complex::new(1.0, 0.0));
+
+    let mut state = State::from_cr(&ClassicalRegister::from_value(8, 2));
+    state.apply_controlled_gate(x, &[Control::Negative(2)], 3);
+    assert_eq!(state.amplitudes()[2], Complex::new(1.0, 0.0));
+}
+
+#[test]
+fn cz_and_controlled_phase_test() {
+    let mut state = State::new(4);
+    state.amplitudes = vec![Complex::new(0.5, 0.0); 4];
+    state.cz_gate(1, 2);
+    let amplitudes = state.amplitudes();
+    assert_eq!(&amplitudes[..3], &[Complex::new(0.5, 0.0); 3]);
+    assert!((amplitudes[3] - Complex::new(-0.5, 0.0)).norm() < 1e-12);
+
+    // CZ is symmetric in control and target
+    let mut swapped = State::new(4);
+    swapped.amplitudes = vec![Complex::new(0.5, 0.0); 4];
+    swapped.cz_gate(2, 1);
+    assert_eq!(swapped.amplitudes(), amplitudes);
+
+    let mut state = State::new(4);
+    state.amplitudes = vec![Complex::new(0.5, 0.0); 4];
+    state.controlled_phase_gate(2, 1, std::f64::consts::FRAC_PI_2);
+    let amplitudes = state.amplitudes();
+    assert_eq!(&amplitudes[..3], &[Complex::new(0.5, 0.0); 3]);
+    assert!((amplitudes[3] - Complex::new(0.0, 0.5)).norm() < 1e-12);
+}
+
+#[test]
+fn gate_and_inverse_test() {
+    let original: Vec<Complex<f64>> = (0..8).map(|i| Complex::new(0.1 * i as f64, 0.05 * (8 - i) as f64)).collect();
+
+    type GateFn = fn(&mut State);
+    let pairs: Vec<(GateFn, GateFn)> = vec![
+        (|s| s.identity_gate(2), |s| s.identity_gate(2)),
+        (|s| s.s_gate(1), |s| s.s_dagger_gate(1)),
+        (|s| s.t_gate(3), |s| s.t_dagger_gate(3)),
+        (|s| s.sqrt_x_gate(2), |s| s.apply_gate_to_qubit(gates::sqrt_x().adjoint(), 2)),
+        (|s| s.phase_gate(1, 0.9), |s| s.phase_gate(1, -0.9)),
+        (|s| s.rx_gate(2, 1.3), |s| s.rx_gate(2, -1.3)),
+        (|s| s.ry_gate(3, -0.4), |s| s.ry_gate(3, 0.4)),
+        (|s| s.rz_gate(1, 2.2), |s| s.rz_gate(1, -2.2)),
+        (|s| s.u3_gate(2, 0.5, 1.5, -0.3), |s| s.u3_gate(2, -0.5, 0.3, -1.5)),
+    ];
+
+    for (gate, inverse) in pairs {
+        let mut state = State{amplitudes: original.clone()};
+        gate(&mut state);
+        inverse(&mut state);
+
+        for (a, b) in state.amplitudes().iter().zip(original.iter()) {
+            assert!((a - b).norm() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn rotation_gates_test() {
+    let q = 1.0/2.0_f64.sqrt();
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+    state.rx_gate(1, std::f64::consts::PI);
+    assert!((state.amplitudes()[1] - Complex::new(0.0, -1.0)).norm() < 1e-12);
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+    state.ry_gate(1, std::f64::consts::FRAC_PI_2);
+    assert!((state.amplitudes()[0] - Complex::new(q, 0.0)).norm() < 1e-12);
+    assert!((state.amplitudes()[1] - Complex::new(q, 0.0)).norm() < 1e-12);
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+    state.hadamard_gate(1);
+    state.s_gate(1);
+    assert!((state.amplitudes()[1] - Complex::new(0.0, q)).norm() < 1e-12);
+}
+
+/// Builds the full 2^n x 2^n operator of a gate on the given ordered qubits, written in the basis
+/// |q1 q2 ... qk⟩, used as a reference for the in-place kernels
+#[cfg(test)]
+fn unitary_reference(gate: &DMatrix<Complex<f64>>, qubits: &[usize], qubit_count: usize) -> DMatrix<Complex<f64>> {
+    let len = 1 << qubit_count;
+    let mask: usize = qubits.iter().map(|&qubit| 1 << (qubit - 1)).sum();
+    let local = |i: usize| qubits.iter().fold(0, |l, &qubit| 2 * l + ((i >> (qubit - 1)) & 1));
+
+    DMatrix::from_fn(len, len, |row, col| {
+        if row & !mask == col & !mask {
+            gate[(local(row), local(col))]
+        } else {
+            Complex::new(0.0, 0.0)
+        }
+    })
+}
+
+#[test]
+fn two_qubit_gates_match_reference_test() {
+    let amplitudes: Vec<Complex<f64>> = (0..8).map(|i| Complex::new(0.1 * i as f64 + 0.2, -0.03 * i as f64)).collect();
+
+    type TwoQubitGate = (DMatrix<Complex<f64>>, fn(&mut State, usize, usize));
+    let cases: Vec<TwoQubitGate> = vec![
+        (gates::swap(), |s, a, b| s.swap_gate(a, b)),
+        (gates::sqrt_swap(), |s, a, b| s.sqrt_swap_gate(a, b)),
+        (gates::iswap(), |s, a, b| s.iswap_gate(a, b)),
+        (gates::cz(), |s, a, b| s.cz_gate(a, b)),
+        (gates::cphase(0.6), |s, a, b| s.controlled_phase_gate(a, b, 0.6)),
+        (gates::cnot(), |s, a, b| s.cnot_gate(a, b)),
+        (gates::rxx(0.9), |s, a, b| s.rxx_gate(a, b, 0.9)),
+        (gates::ryy(-1.4), |s, a, b| s.ryy_gate(a, b, -1.4)),
+        (gates::rzz(2.1), |s, a, b| s.rzz_gate(a, b, 2.1)),
+        (gates::fsim(0.5, 1.3), |s, a, b| s.fsim_gate(a, b, 0.5, 1.3)),
+    ];
+
+    for (matrix, apply) in cases {
+        for (first_qubit, second_qubit) in [(1, 2), (2, 1), (1, 3), (3, 2)] {
+            let mut state = State{amplitudes: amplitudes.clone()};
+            apply(&mut state, first_qubit, second_qubit);
+
+            let expected = unitary_reference(&matrix, &[first_qubit, second_qubit], 3) * DVector::from_vec(amplitudes.clone());
+
+            for (a, b) in state.amplitudes().iter().zip(expected.iter()) {
+                assert!((a - b).norm() < 1e-12);
+            }
+        }
+    }
+}
+
+#[test]
+fn toffoli_truth_table_test() {
+    for value in 0..8 {
+        let mut state = State::from_cr(&ClassicalRegister::from_value(8, value));
+        state.toffoli_gate(1, 2, 3);
+
+        let expected = if value & 0b011 == 0b011 { value ^ 0b100 } else { value };
+        assert_eq!(state.amplitudes()[expected as usize], Complex::new(1.0, 0.0));
+    }
+}
+
+#[test]
+fn fredkin_truth_table_test() {
+    for value in 0..8 {
+        let mut state = State::from_cr(&ClassicalRegister::from_value(8, value));
+        state.fredkin_gate(2, 1, 3);
+
+        // Qubits 1 and 3 are bits 0 and 2, swapped only when bit 1 is set
+        let (first, second) = (value & 0b001, (value & 0b100) >> 2);
+        let expected = if value & 0b010 != 0 { (value & 0b010) | (first << 2) | second } else { value };
+        assert_eq!(state.amplitudes()[expected as usize], Complex::new(1.0, 0.0));
+    }
+}
+
+#[test]
+fn apply_unitary_matches_reference_test() {
+    let amplitudes: Vec<Complex<f64>> = (0..16).map(|i| Complex::new((i as f64).cos(), (i as f64).sin() * 0.5)).collect();
+
+    let three_qubit_gate = kronecker_product(&gates::u3(0.3, 1.1, -0.7), &gates::fsim(0.9, 0.4)) * kronecker_product(&gates::hadamard(), &gates::cnot());
+    let two_qubit_gate = gates::rxx(0.4) * kronecker_product(&gates::t(), &gates::ry(1.2));
+
+    for qubits in [vec![1, 2, 3], vec![3, 1, 4], vec![4, 3, 2], vec![2, 4, 1]] {
+        let mut state = State{amplitudes: amplitudes.clone()};
+        state.apply_unitary(&three_qubit_gate, &qubits);
+
+        let expected = unitary_reference(&three_qubit_gate, &qubits, 4) * DVector::from_vec(amplitudes.clone());
+        for (a, b) in state.amplitudes().iter().zip(expected.iter()) {
+            assert!((a - b).norm() < 1e-12);
+        }
+    }
+
+    for qubits in [vec![1, 4], vec![3, 2]] {
+        let mut state = State{amplitudes: amplitudes.clone()};
+        state.apply_unitary(&two_qubit_gate, &qubits);
+
+        let expected = unitary_reference(&two_qubit_gate, &qubits, 4) * DVector::from_vec(amplitudes.clone());
+        for (a, b) in state.amplitudes().iter().zip(expected.iter()) {
+            assert!((a - b).norm() < 1e-12);
+        }
+    }
+
+    // A single-qubit unitary goes through the same path as `apply_gate_to_qubit`
+    let mut state = State{amplitudes: amplitudes.clone()};
+    let mut reference = State{amplitudes: amplitudes.clone()};
+    state.apply_unitary(&gates::u3(0.2, 0.4, 0.6), &[3]);
+    reference.u3_gate(3, 0.2, 0.4, 0.6);
+    for (a, b) in state.amplitudes().iter().zip(reference.amplitudes().iter()) {
+        assert!((a - b).norm() < 1e-12);
+    }
+}
+
+#[test]
+#[should_panic(expected = "a gate on 2 qubits must be a 4x4 matrix")]
+fn apply_unitary_dimension_mismatch_test() {
+    let mut state = State::from_cr(&ClassicalRegister::zeros(8));
+    state.apply_unitary(&gates::hadamard(), &[1, 2]);
+}
+
+#[test]
+fn unitarity_check_test() {
+    let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+    state.hadamard_gate(1);
+    let original = state.amplitudes();
+
+    let not_unitary = DMatrix::from_row_slice(2, 2, &[
+        Complex::new(1.0, 0.0), Complex::new(1.0, 0.0),
+        Complex::new(0.0, 0.0), Complex::new(1.0, 0.0),
+    ]);
+    let result = state.try_apply_gate_to_qubit(not_unitary.clone(), 1, Some(DEFAULT_TOLERANCE));
+    assert!(matches!(result, Err(QuriustError::NonUnitaryGate { .. })));
+    assert_eq!(state.amplitudes(), original);
+
+    let result = state.try_apply_unitary(&kronecker_product(&not_unitary, &gates::pauli_x()), &[1, 2], Some(DEFAULT_TOLERANCE));
+    assert!(matches!(result, Err(QuriustError::NonUnitaryGate { .. })));
+    assert_eq!(state.amplitudes(), original);
+
+    // Without a tolerance the gate is applied as is
+    assert_eq!(state.try_apply_gate_to_qubit(not_unitary, 1, None), Ok(()));
+    assert!((state.norm_sqr() - 2.5).abs() < 1e-12);
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+    assert_eq!(state.try_apply_unitary(&gates::iswap(), &[2, 1], Some(DEFAULT_TOLERANCE)), Ok(()));
+    assert_eq!(state.try_apply_gate_to_qubit(gates::u3(0.1, 0.2, 0.3), 2, Some(DEFAULT_TOLERANCE)), Ok(()));
+}
+
+#[test]
+fn gate_dimension_check_test() {
+    let mut state = State::from_cr(&ClassicalRegister::zeros(8));
+
+    assert_eq!(state.try_apply_unitary(&gates::hadamard(), &[1, 3], None), Err(QuriustError::DimensionMismatch { expected: 4, found: 2 }));
+    assert_eq!(state.try_apply_gate_to_qubit(gates::swap(), 1, None), Err(QuriustError::DimensionMismatch { expected: 2, found: 4 }));
+    assert_eq!(state.try_apply_unitary(&DMatrix::identity(4, 2), &[1, 2], None), Err(QuriustError::DimensionMismatch { expected: 4, found: 2 }));
+}
+
+#[test]
+fn norm_drift_check_test() {
+    // U†U - I has entries of at most 2δ, but the total probability of |+⟩ grows by 2δ + δ²
+    let delta = 0.01;
+    let gate = DMatrix::from_row_slice(2, 2, &[
+        Complex::new(1.0, 0.0), Complex::new(delta, 0.0),
+        Complex::new(delta, 0.0), Complex::new(1.0, 0.0),
+    ]);
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+    state.hadamard_gate(1);
+
+    let result = state.try_apply_gate_to_qubit(gate, 1, Some(2.0 * delta + delta * delta / 2.0));
+    match result {
+        Err(QuriustError::NormDrift { before, after, .. }) => {
+            assert!((before - 1.0).abs() < 1e-12);
+            assert!((after - (1.0 + delta).powi(2)).abs() < 1e-12);
+        }
+        _ => panic!("expected a norm drift, got {:?}", result),
+    }
+
+    // The rejected gate leaves the amplitudes untouched
+    let mut expected = State::from_cr(&ClassicalRegister::zeros(2));
+    expected.hadamard_gate(1);
+    assert_eq!(state.amplitudes(), expected.amplitudes());
+}
+
+#[test]
+fn invalid_input_test() {
+    assert_eq!(State::try_new(6).unwrap_err(), QuriustError::DimensionMismatch { expected: 8, found: 6 });
+    assert_eq!(State::try_from_cr(&ClassicalRegister::from_value(4, 7)).unwrap_err(), QuriustError::DimensionMismatch { expected: 4, found: 8 });
+    assert!(State::try_from_cr(&ClassicalRegister::from_value(4, 3)).is_ok());
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(8));
+    assert_eq!(state.check_qubits(&[1, 2, 3]), Ok(()));
+    assert_eq!(state.check_qubits(&[0]), Err(QuriustError::InvalidQubit { qubit: 0, qubit_count: 3 }));
+    assert_eq!(state.check_qubits(&[4]), Err(QuriustError::InvalidQubit { qubit: 4, qubit_count: 3 }));
+    assert_eq!(state.check_qubits(&[2, 1, 2]), Err(QuriustError::InvalidQubit { qubit: 2, qubit_count: 3 }));
+
+    assert_eq!(state.try_apply_gate_to_qubit(gates::hadamard(), 5, None), Err(QuriustError::InvalidQubit { qubit: 5, qubit_count: 3 }));
+    assert_eq!(state.try_apply_unitary(&gates::swap(), &[3, 3], None), Err(QuriustError::InvalidQubit { qubit: 3, qubit_count: 3 }));
+    assert_eq!(state.try_apply_controlled_gate(gates::pauli_x(), &[Control::Negative(1)], 1, None), Err(QuriustError::InvalidQubit { qubit: 1, qubit_count: 3 }));
+    assert_eq!(state.amplitudes()[0], Complex::new(1.0, 0.0));
+}
+
+#[test]
+fn apply_gate_enum_test() {
+    let amplitudes: Vec<Complex<f64>> = (0..8).map(|i| Complex::new(0.2 * i as f64 - 0.5, 0.1 * i as f64)).collect();
+
+    let cases = vec![
+        (Gate::H, vec![2]),
+        (Gate::U3(0.3, -0.2, 1.4), vec![3]),
+        (Gate::Sx, vec![1]),
+        (Gate::Cnot, vec![3, 1]),
+        (Gate::CPhase(0.8), vec![2, 3]),
+        (Gate::Swap, vec![1, 3]),
+        (Gate::FSim(0.6, 0.3), vec![3, 2]),
+        (Gate::Rzz(1.1), vec![1, 2]),
+        (Gate::Ccx, vec![3, 1, 2]),
+        (Gate::CSwap, vec![2, 3, 1]),
+        (Gate::Controlled { gate: gates::ry(0.9), controls: 2 }, vec![1, 3, 2]),
+    ];
+
+    for (gate, qubits) in cases {
+        let mut state = State{amplitudes: amplitudes.clone()};
+        state.apply(&gate, &qubits);
+
+        let expected = unitary_reference(&gate.matrix(), &qubits, 3) * DVector::from_vec(amplitudes.clone());
+        for (a, b) in state.amplitudes().iter().zip(expected.iter()) {
+            assert!((a - b).norm() < 1e-12, "{:?} on {:?}", gate, qubits);
+        }
+    }
+}
+
+#[test]
+fn sample_test() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+    state.pauli_x_gate(2);
+    assert_eq!(state.sample(50, &mut rng), vec![2; 50]);
+
+    state.hadamard_gate(1);
+    let probabilities = state.probabilities();
+    assert!((probabilities[2] - 0.5).abs() < 1e-12 && (probabilities[3] - 0.5).abs() < 1e-12);
+
+    let samples = state.sample(2000, &mut rng);
+    assert!(samples.iter().all(|&index| index == 2 || index == 3));
+    let ones = samples.iter().filter(|&&index| index == 3).count();
+    assert!(ones > 850 && ones < 1150);
+
+    // Sampling does not collapse the state, and the same seed draws the same samples
+    assert_eq!(state.probabilities(), probabilities);
+    assert_eq!(state.sample(100, &mut StdRng::seed_from_u64(3)), state.sample(100, &mut StdRng::seed_from_u64(3)));
+}
+
+#[test]
+fn measure_qubits_test() {
+    let mut rng = StdRng::seed_from_u64(11);
+
+    // Measuring one qubit of a Bell pair collapses the other one too
+    for _ in 0..20 {
+        let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+        state.hadamard_gate(1);
+        state.cnot_gate(1, 2);
+
+        let bits = state.measure_qubits(&[1], &mut rng);
+        let expected = if bits[0] == 1 { 3 } else { 0 };
+        assert!((state.amplitudes()[expected].norm_sqr() - 1.0).abs() < 1e-12);
+        assert!((state.norm_sqr() - 1.0).abs() < 1e-12);
+    }
+
+    // The unmeasured qubits keep their superposition
+    let mut state = State::from_cr(&ClassicalRegister::zeros(8));
+    state.pauli_x_gate(3);
+    state.hadamard_gate(1);
+    assert_eq!(state.measure_qubits(&[3, 2], &mut rng), vec![1, 0]);
+    assert!((state.amplitudes()[4].norm_sqr() - 0.5).abs() < 1e-12);
+    assert!((state.amplitudes()[5].norm_sqr() - 0.5).abs() < 1e-12);
+
+    // Outcomes follow the Born rule
+    let ones = (0..2000).filter(|_| {
+        let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+        state.ry_gate(1, 2.0 * (0.3_f64).sqrt().asin());
+        state.measure_qubits(&[1], &mut rng)[0] == 1
+    }).count();
+    assert!(ones > 500 && ones < 700);
+
+    let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+    state.hadamard_gate(1);
+    assert!((state.collapse(&[1], &[1]) - 0.5).abs() < 1e-12);
+    assert!((state.amplitudes()[1].norm_sqr() - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn expectation_test() {
+    let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+    state.pauli_x_gate(2);
+    assert!((state.expectation(&gates::pauli_z(), &[1]) - 1.0).abs() < 1e-12);
+    assert!((state.expectation(&gates::pauli_z(), &[2]) + 1.0).abs() < 1e-12);
+
+    state.hadamard_gate(1);
+    assert!((state.expectation(&gates::pauli_x(), &[1]) - 1.0).abs() < 1e-12);
+    assert!(state.expectation(&kronecker_product(&gates::pauli_z(), &gates::pauli_z()), &[1, 2]).abs() < 1e-12);
+}
+
+#[test]
+fn trajectory_channel_test() {
+    let mut rng = StdRng::seed_from_u64(21);
+
+    // Averaged over trajectories, amplitude damping of |1> leaves it excited with probability 1 - γ
+    let excited = (0..4000).filter(|_| {
+        let mut state = State::from_cr(&ClassicalRegister::zeros(2));
+        state.pauli_x_gate(1);
+        state.apply_channel(&KrausChannel::amplitude_damping(0.3), &[1], &mut rng);
+        assert!((state.norm_sqr() - 1.0).abs() < 1e-12);
+        state.probabilities()[1] > 0.5
+    }).count();
+    assert!(excited > 2650 && excited < 2950);
+
+    // A bit flip with certainty always picks the X branch, on every listed qubit
+    let mut state = State::from_cr(&ClassicalRegister::zeros(4));
+    assert_eq!(state.apply_channel(&KrausChannel::bit_flip(1.0), &[1, 2], &mut rng), vec![1, 1]);
+    assert!((state.probabilities()[3] - 1.0).abs() < 1e-12);
+
+    assert!(state.try_apply_channel(&KrausChannel::bit_flip(0.5), &[3], &mut rng).is_err());
+}
+
+#[test]
+fn precision_test() {
+    fn run<T: Real>() -> State<T> {
+        let n = 6;
+        let mut state = State::<T>::basis_state(&ClassicalRegister::zeros(1 << n));
+        for qubit in 1..=n {
+            state.hadamard_gate(qubit);
+            state.rx_gate(qubit, 0.3 * qubit as f64);
+        }
+        for qubit in 1..n {
+            state.cnot_gate(qubit, qubit + 1);
+            state.rzz_gate(qubit, n, 0.7);
+        }
+        state.fredkin_gate(1, 2, n);
+        state.apply_unitary(&kronecker_product(&gates::hadamard(), &gates::t()), &[n, 2]);
+        state
+    }
+
+    // Single precision agrees with double precision to about the f32 epsilon, in half the memory
+    let single = run::<f32>();
+    let double = run::<f64>();
+    let deviation = single.amplitudes().iter().zip(double.amplitudes())
+        .map(|(a, b)| (Complex::new(a.re as f64, a.im as f64) - b).norm())
+        .fold(0.0, f64::max);
+    assert!(deviation > 0.0 && deviation < 1e-6);
+    assert!((single.norm_sqr() - 1.0).abs() < 1e-6);
+
+    let observable = kronecker_product(&gates::pauli_z(), &gates::pauli_x());
+    assert!((single.expectation(&observable, &[1, 4]) - double.expectation(&observable, &[1, 4])).abs() < 1e-6);
+    assert_eq!(2 * std::mem::size_of_val(&single.amplitudes[..]), std::mem::size_of_val(&double.amplitudes[..]));
+
+    // The checked gates of a register use a tolerance suited to the precision
+    let mut register = QuantumRegister::from_backend(State::<f32>::basis_state(&ClassicalRegister::zeros(8)));
+    for _ in 0..50 {
+        register.try_apply(&Gate::Unitary(kronecker_product(&gates::rx(0.1), &gates::hadamard())), &[1, 3]).unwrap();
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_kernels_test() {
+    let run = |serial: bool| {
+        FORCE_SERIAL.with(|force| force.set(serial));
+
+        let n = PARALLEL_THRESHOLD + 2;
+        let mut state = State::from_cr(&ClassicalRegister::zeros(1 << n));
+        for qubit in 1..=n {
+            state.hadamard_gate(qubit);
+            state.rx_gate(qubit, 0.1 * qubit as f64);
+        }
+        for qubit in 1..n {
+            state.cnot_gate(qubit, qubit + 1);
+            state.rzz_gate(qubit, n, 0.3);
+        }
+        state.fredkin_gate(1, 2, n);
+        state.swap_gate(3, n - 1);
+        state.apply_unitary(&kronecker_product(&gates::hadamard(), &gates::t()), &[n, 2]);
+
+        let expectation = state.expectation(&kronecker_product(&gates::pauli_z(), &gates::pauli_x()), &[1, n]);
+        let norm = state.norm_sqr();
+        let probability = state.collapse(&[2, n], &[1, 0]);
+        (state.amplitudes(), state.probabilities(), expectation, norm, probability)
+    };
+
+    // The threaded kernels give the serial results bit for bit
+    let parallel = run(false);
+    let serial = run(true);
+    FORCE_SERIAL.with(|force| force.set(false));
+    assert!(parallel == serial);
+}
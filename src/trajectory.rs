@@ -0,0 +1,225 @@
+use super::circuit::Circuit;
+use super::error::QuriustError;
+use super::noise::NoiseModel;
+use super::registers::{ClassicalRegister, Counts, QuantumRegister};
+#[cfg(test)]
+use super::circuit::Instruction;
+#[cfg(test)]
+use super::density_matrix::DensityMatrix;
+#[cfg(test)]
+use super::gates::{self, Gate};
+#[cfg(test)]
+use super::noise::KrausChannel;
+#[cfg(test)]
+use super::state::kronecker_product;
+use num_complex::Complex;
+use nalgebra::DMatrix;
+
+/// Represents a Hermitian observable acting on an ordered list of qubits
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observable {
+    pub matrix: DMatrix<Complex<f64>>,
+    pub qubits: Vec<usize>,
+}
+
+impl Observable {
+    /// Creates an observable from its matrix, written in the basis of the given qubits
+    pub fn new(matrix: DMatrix<Complex<f64>>, qubits: &[usize]) -> Observable {
+        Observable { matrix, qubits: qubits.to_vec() }
+    }
+}
+
+/// Statistical estimate of a quantity averaged over trajectories
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// Sample mean over the trajectories
+    pub mean: f64,
+    /// Standard error of the mean, i.e. the sample standard deviation divided by √trajectories
+    pub standard_error: f64,
+}
+
+impl Estimate {
+    /// Computes the mean and its standard error from one sample per trajectory.
+    ///
+    /// Panics if there is no sample, since the mean is then undefined
+    pub fn from_samples(samples: &[f64]) -> Estimate {
+        assert!(!samples.is_empty(), "an estimate needs at least one sample");
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+        if samples.len() < 2 {
+            return Estimate { mean, standard_error: 0.0 };
+        }
+
+        let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        Estimate { mean, standard_error: (variance / count).sqrt() }
+    }
+}
+
+/// Outcome of a trajectory simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrajectoryResult {
+    /// Number of simulated trajectories
+    pub trajectories: usize,
+    /// Estimate of every requested observable at the end of the circuit, in the order they were given
+    pub expectations: Vec<Estimate>,
+    /// Histogram of the classical register of every trajectory, measured in full when the circuit records no
+    /// measurement
+    pub counts: Counts,
+}
+
+/// Simulates noisy circuits by Monte-Carlo quantum trajectories.
+///
+/// Every trajectory runs the circuit on a pure `State`, picking one Kraus operator at random for every error
+/// channel, so memory grows like a state-vector simulation instead of a density matrix. Averaging the
+/// observables over the trajectories converges to the density-matrix values, with error bars shrinking as
+/// 1/√trajectories
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrajectorySimulator {
+    noise_model: NoiseModel,
+    trajectories: usize,
+    seed: Option<u64>,
+}
+
+impl TrajectorySimulator {
+    /// Creates a simulator running the given number of trajectories with the noise of the model.
+    ///
+    /// Panics if `trajectories` is zero
+    pub fn new(noise_model: NoiseModel, trajectories: usize) -> TrajectorySimulator {
+        assert!(trajectories > 0, "a trajectory simulation needs at least one trajectory");
+        TrajectorySimulator { noise_model, trajectories, seed: None }
+    }
+
+    /// Seeds the random number generator, so the same simulation gives identical results
+    pub fn with_seed(mut self, seed: u64) -> TrajectorySimulator {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Returns the number of trajectories of every run
+    pub fn trajectories(&self) -> usize {
+        self.trajectories
+    }
+
+    /// Runs the trajectories of a circuit, averaging the observables over them.
+    ///
+    /// Observables are evaluated on the state at the end of every trajectory, after its measurements if any.
+    /// Returns an error if the circuit fails or an observable does not fit its qubits
+    pub fn run(&self, circuit: &Circuit, observables: &[Observable]) -> Result<TrajectoryResult, QuriustError> {
+        let mut register = QuantumRegister::init(circuit.num_qubits()).with_noise_model(self.noise_model.clone());
+        if let Some(seed) = self.seed {
+            register = register.with_seed(seed);
+        }
+
+        for observable in observables {
+            register.prob_amplitudes.check_qubits(&observable.qubits)?;
+            let dimension = 1 << observable.qubits.len();
+            if observable.qubits.is_empty() || observable.matrix.shape() != (dimension, dimension) {
+                return Err(QuriustError::DimensionMismatch { expected: dimension, found: observable.matrix.nrows() });
+            }
+        }
+
+        let mut samples = vec![Vec::with_capacity(self.trajectories); observables.len()];
+        let mut counts = Counts::new();
+        for _ in 0..self.trajectories {
+            register.reset();
+            let mut classical_register = ClassicalRegister::zeros(circuit.num_qubits());
+            circuit.apply_with_classical(&mut register, &mut classical_register)?;
+
+            for (observable, values) in observables.iter().zip(samples.iter_mut()) {
+                values.push(register.prob_amplitudes.expectation(&observable.matrix, &observable.qubits));
+            }
+
+            if !circuit.has_measurements() {
+                classical_register = register.try_measure()?;
+            }
+            *counts.entry(classical_register).or_insert(0) += 1;
+        }
+
+        Ok(TrajectoryResult {
+            trajectories: self.trajectories,
+            expectations: samples.iter().map(|values| Estimate::from_samples(values)).collect(),
+            counts,
+        })
+    }
+
+    /// Estimates a single observable over the trajectories of a circuit, like `run`
+    pub fn expectation(&self, circuit: &Circuit, observable: &Observable) -> Result<Estimate, QuriustError> {
+        Ok(self.run(circuit, std::slice::from_ref(observable))?.expectations[0])
+    }
+}
+
+
+#[test]
+fn estimate_test() {
+    let estimate = Estimate::from_samples(&[1.0, -1.0, 1.0, -1.0]);
+    assert!(estimate.mean.abs() < 1e-12);
+    assert!((estimate.standard_error - (4.0_f64 / 3.0 / 4.0).sqrt()).abs() < 1e-12);
+    assert_eq!(Estimate::from_samples(&[0.5]), Estimate { mean: 0.5, standard_error: 0.0 });
+}
+
+#[test]
+#[should_panic(expected = "an estimate needs at least one sample")]
+fn empty_estimate_panics_test() {
+    Estimate::from_samples(&[]);
+}
+
+#[test]
+#[should_panic(expected = "a trajectory simulation needs at least one trajectory")]
+fn zero_trajectories_panics_test() {
+    TrajectorySimulator::new(NoiseModel::new(), 0);
+}
+
+#[test]
+fn trajectories_match_density_matrix_test() {
+    let mut model = NoiseModel::new();
+    model.add_gate_error("h", KrausChannel::amplitude_damping(0.2))
+        .add_gate_error("cnot", KrausChannel::two_qubit_depolarizing(0.15))
+        .add_gate_error("ry", KrausChannel::phase_damping(0.3));
+
+    let mut circuit = Circuit::new(3);
+    circuit.h(1).cnot(1, 2).ry(3, 0.8).cnot(2, 3).h(1);
+
+    let mut rho = DensityMatrix::new(3);
+    for instruction in circuit.instructions() {
+        if let Instruction::Gate { gate, qubits } = instruction {
+            rho.apply(gate, qubits);
+            for channel in model.gate_errors(gate, qubits) {
+                rho.apply_channel(channel, qubits);
+            }
+        }
+    }
+
+    let zz = kronecker_product(&gates::pauli_z(), &gates::pauli_z());
+    let observables = [
+        Observable::new(gates::pauli_z(), &[1]),
+        Observable::new(gates::pauli_x(), &[1]),
+        Observable::new(zz, &[2, 3]),
+    ];
+
+    let simulator = TrajectorySimulator::new(model, 3000).with_seed(12);
+    let result = simulator.run(&circuit, &observables).unwrap();
+    assert_eq!(result.trajectories, 3000);
+    assert_eq!(result.counts.values().sum::<usize>(), 3000);
+
+    for (estimate, observable) in result.expectations.iter().zip(observables.iter()) {
+        let exact = rho.expectation(&observable.matrix, &observable.qubits);
+        assert!(estimate.standard_error > 0.0 && estimate.standard_error < 0.03);
+        assert!((estimate.mean - exact).abs() < 4.0 * estimate.standard_error + 1e-9);
+    }
+
+    // The same seed replays the same trajectories
+    assert_eq!(simulator.run(&circuit, &observables).unwrap(), result);
+}
+
+#[test]
+fn ideal_trajectories_test() {
+    let mut circuit = Circuit::new(2);
+    circuit.x(1).gate(Gate::Cnot, &[1, 2]);
+
+    let simulator = TrajectorySimulator::new(NoiseModel::new(), 50);
+    let estimate = simulator.expectation(&circuit, &Observable::new(gates::pauli_z(), &[2])).unwrap();
+    assert_eq!(estimate, Estimate { mean: -1.0, standard_error: 0.0 });
+
+    assert_eq!(simulator.expectation(&circuit, &Observable::new(gates::pauli_z(), &[3])), Err(QuriustError::InvalidQubit { qubit: 3, qubit_count: 2 }));
+    assert!(simulator.expectation(&circuit, &Observable::new(gates::cnot(), &[1])).is_err());
+}